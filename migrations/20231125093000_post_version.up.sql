@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- incremented on every update so concurrent edits can be detected
+ALTER TABLE post ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
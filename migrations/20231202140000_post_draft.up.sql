@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE post_draft (
+    post_id VARCHAR NOT NULL PRIMARY KEY,
+    base_version INTEGER NOT NULL,
+    dt VARCHAR NOT NULL,
+    image_url VARCHAR NOT NULL,
+    title VARCHAR NOT NULL,
+    text VARCHAR NOT NULL,
+    saved_at VARCHAR NOT NULL
+);
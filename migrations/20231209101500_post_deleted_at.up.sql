@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- posts are moved to the trash by setting this, and purged later
+ALTER TABLE post ADD COLUMN deleted_at VARCHAR;
@@ -0,0 +1,17 @@
+-- Add down migration script here
+-- SQLite can't drop a column that references another table, so post is rebuilt without it
+CREATE TABLE post_without_author (
+    id VARCHAR NOT NULL PRIMARY KEY,
+    dt VARCHAR NOT NULL,
+    image_url VARCHAR,
+    title VARCHAR NOT NULL,
+    text VARCHAR NOT NULL,
+    version INTEGER NOT NULL DEFAULT 1,
+    deleted_at VARCHAR
+);
+INSERT INTO post_without_author (id, dt, image_url, title, text, version, deleted_at)
+SELECT id, dt, image_url, title, text, version, deleted_at FROM post;
+DROP TABLE post;
+ALTER TABLE post_without_author RENAME TO post;
+
+DROP TABLE user;
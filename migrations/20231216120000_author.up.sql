@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE user (
+    id VARCHAR NOT NULL PRIMARY KEY,
+    handle VARCHAR NOT NULL UNIQUE,
+    display_name VARCHAR NOT NULL,
+    bio VARCHAR NOT NULL DEFAULT '',
+    avatar_url VARCHAR NOT NULL DEFAULT ''
+);
+
+ALTER TABLE post ADD COLUMN author_id VARCHAR REFERENCES user (id);
@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE post DROP COLUMN status;
+DROP TABLE setting;
+DROP TABLE session;
+ALTER TABLE user DROP COLUMN password_hash;
+ALTER TABLE user DROP COLUMN role;
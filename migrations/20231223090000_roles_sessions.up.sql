@@ -0,0 +1,19 @@
+-- Add up migration script here
+-- role is one of contributor, author, editor, admin
+ALTER TABLE user ADD COLUMN role VARCHAR NOT NULL DEFAULT 'contributor';
+-- users without a password can be credited as authors but can't log in
+ALTER TABLE user ADD COLUMN password_hash VARCHAR;
+
+CREATE TABLE session (
+    token VARCHAR NOT NULL PRIMARY KEY,
+    user_id VARCHAR NOT NULL REFERENCES user (id) ON DELETE CASCADE,
+    expires_at VARCHAR NOT NULL
+);
+
+CREATE TABLE setting (
+    key VARCHAR NOT NULL PRIMARY KEY,
+    value VARCHAR NOT NULL
+);
+
+-- posts written before there were drafts are all public
+ALTER TABLE post ADD COLUMN status VARCHAR NOT NULL DEFAULT 'published';
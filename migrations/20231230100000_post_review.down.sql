@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE review_note;
+DROP TABLE post_transition;
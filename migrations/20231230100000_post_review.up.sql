@@ -0,0 +1,25 @@
+-- Add up migration script here
+-- every change of post.status, including the note left with it
+CREATE TABLE post_transition (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    post_id VARCHAR NOT NULL REFERENCES post (id) ON DELETE CASCADE,
+    from_status VARCHAR,
+    to_status VARCHAR NOT NULL,
+    actor_id VARCHAR REFERENCES user (id) ON DELETE SET NULL,
+    note VARCHAR NOT NULL DEFAULT '',
+    created_at VARCHAR NOT NULL
+);
+CREATE INDEX post_transition_post_id ON post_transition (post_id);
+
+-- reviewer comments attached to a paragraph of the post
+CREATE TABLE review_note (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    post_id VARCHAR NOT NULL REFERENCES post (id) ON DELETE CASCADE,
+    paragraph INTEGER NOT NULL,
+    quote VARCHAR NOT NULL,
+    body VARCHAR NOT NULL,
+    author_id VARCHAR REFERENCES user (id) ON DELETE SET NULL,
+    created_at VARCHAR NOT NULL,
+    resolved BOOLEAN NOT NULL DEFAULT 0
+);
+CREATE INDEX review_note_post_id ON review_note (post_id);
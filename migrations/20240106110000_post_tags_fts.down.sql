@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP TRIGGER post_fts_update;
+DROP TRIGGER post_fts_delete;
+DROP TRIGGER post_fts_insert;
+DROP TABLE post_fts;
+DROP TABLE post_tag;
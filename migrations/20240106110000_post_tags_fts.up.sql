@@ -0,0 +1,25 @@
+-- Add up migration script here
+CREATE TABLE post_tag (
+    post_id VARCHAR NOT NULL REFERENCES post (id) ON DELETE CASCADE,
+    tag VARCHAR NOT NULL,
+    PRIMARY KEY (post_id, tag)
+);
+CREATE INDEX post_tag_tag ON post_tag (tag);
+
+-- full-text index over the post table, kept in step by the triggers below
+CREATE VIRTUAL TABLE post_fts USING fts5 (title, text, content = 'post', content_rowid = 'rowid');
+
+CREATE TRIGGER post_fts_insert AFTER INSERT ON post BEGIN
+    INSERT INTO post_fts (rowid, title, text) VALUES (new.rowid, new.title, new.text);
+END;
+
+CREATE TRIGGER post_fts_delete AFTER DELETE ON post BEGIN
+    INSERT INTO post_fts (post_fts, rowid, title, text) VALUES ('delete', old.rowid, old.title, old.text);
+END;
+
+CREATE TRIGGER post_fts_update AFTER UPDATE OF title, text ON post BEGIN
+    INSERT INTO post_fts (post_fts, rowid, title, text) VALUES ('delete', old.rowid, old.title, old.text);
+    INSERT INTO post_fts (rowid, title, text) VALUES (new.rowid, new.title, new.text);
+END;
+
+INSERT INTO post_fts (post_fts) VALUES ('rebuild');
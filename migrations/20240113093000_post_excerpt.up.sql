@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- shown on preview cards; when empty, one is generated from the text
+ALTER TABLE post ADD COLUMN excerpt VARCHAR;
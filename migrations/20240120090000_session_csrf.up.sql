@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- a secret per session that forms send back, to show they came from one of our pages
+ALTER TABLE session ADD COLUMN csrf_token VARCHAR NOT NULL DEFAULT '';
+UPDATE session SET csrf_token = lower(hex(randomblob(16)));
@@ -0,0 +1,19 @@
+-- Add up migration script here
+-- every webhook call for a post event; kept as a log once delivered or given up on
+CREATE TABLE webhook_delivery (
+    id VARCHAR NOT NULL PRIMARY KEY,
+    event VARCHAR NOT NULL,
+    -- no foreign key, so the log outlives purged posts
+    post_id VARCHAR NOT NULL,
+    url VARCHAR NOT NULL,
+    -- sent as is on every attempt, so retries carry the same body and signature
+    payload VARCHAR NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_response_status INTEGER,
+    last_error VARCHAR,
+    created_at VARCHAR NOT NULL,
+    next_attempt_at VARCHAR NOT NULL,
+    delivered_at VARCHAR
+);
+CREATE INDEX webhook_delivery_due ON webhook_delivery (status, next_attempt_at);
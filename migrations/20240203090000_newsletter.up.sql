@@ -0,0 +1,37 @@
+-- Add up migration script here
+-- readers who asked for new posts by email; they only get them once they confirm the address
+CREATE TABLE subscriber (
+    id VARCHAR NOT NULL PRIMARY KEY,
+    email VARCHAR NOT NULL UNIQUE,
+    status VARCHAR NOT NULL DEFAULT 'pending',
+    -- in the link of the confirmation email
+    confirm_token VARCHAR NOT NULL UNIQUE,
+    -- in the link at the bottom of every email, so it never changes
+    unsubscribe_token VARCHAR NOT NULL UNIQUE,
+    created_at VARCHAR NOT NULL,
+    confirmation_sent_at VARCHAR,
+    confirmed_at VARCHAR,
+    unsubscribed_at VARCHAR
+);
+
+-- every email to send, rendered when it is queued; kept as a log once sent or given up on
+CREATE TABLE newsletter_email (
+    id VARCHAR NOT NULL PRIMARY KEY,
+    subscriber_id VARCHAR NOT NULL REFERENCES subscriber (id) ON DELETE CASCADE,
+    -- the published post for digests, NULL for confirmation emails
+    post_id VARCHAR,
+    to_address VARCHAR NOT NULL,
+    subject VARCHAR NOT NULL,
+    html_body VARCHAR NOT NULL,
+    text_body VARCHAR NOT NULL,
+    unsubscribe_url VARCHAR,
+    status VARCHAR NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error VARCHAR,
+    created_at VARCHAR NOT NULL,
+    next_attempt_at VARCHAR NOT NULL,
+    sent_at VARCHAR,
+    -- a post published, unpublished and published again is only sent once
+    UNIQUE (post_id, subscriber_id)
+);
+CREATE INDEX newsletter_email_due ON newsletter_email (status, next_attempt_at);
@@ -0,0 +1,18 @@
+-- Add down migration script here
+CREATE TABLE post_draft_shared (
+    post_id VARCHAR NOT NULL PRIMARY KEY,
+    base_version INTEGER NOT NULL,
+    dt VARCHAR NOT NULL,
+    image_url VARCHAR NOT NULL,
+    title VARCHAR NOT NULL,
+    text VARCHAR NOT NULL,
+    saved_at VARCHAR NOT NULL
+);
+
+-- the most recently saved draft of each post is kept
+INSERT INTO post_draft_shared (post_id, base_version, dt, image_url, title, text, saved_at)
+SELECT post_id, base_version, dt, image_url, title, text, MAX(saved_at)
+FROM post_draft GROUP BY post_id;
+
+DROP TABLE post_draft;
+ALTER TABLE post_draft_shared RENAME TO post_draft;
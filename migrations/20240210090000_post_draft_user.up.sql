@@ -0,0 +1,25 @@
+-- Add up migration script here
+-- autosaved edits belong to whoever made them, so people editing the same post, or each
+-- starting a new one, don't overwrite or see each other's
+CREATE TABLE post_draft_by_user (
+    post_id VARCHAR NOT NULL,
+    user_id VARCHAR NOT NULL REFERENCES user (id) ON DELETE CASCADE,
+    base_version INTEGER NOT NULL,
+    dt VARCHAR NOT NULL,
+    image_url VARCHAR NOT NULL,
+    title VARCHAR NOT NULL,
+    text VARCHAR NOT NULL,
+    saved_at VARCHAR NOT NULL,
+    PRIMARY KEY (post_id, user_id)
+);
+
+-- who wrote the existing drafts isn't known, so they go to the post's author; drafts of
+-- new posts have no author to go to and are dropped
+INSERT INTO post_draft_by_user (post_id, user_id, base_version, dt, image_url, title, text, saved_at)
+SELECT post_draft.post_id, post.author_id, post_draft.base_version, post_draft.dt,
+    post_draft.image_url, post_draft.title, post_draft.text, post_draft.saved_at
+FROM post_draft JOIN post ON post.id = post_draft.post_id
+WHERE post.author_id IS NOT NULL;
+
+DROP TABLE post_draft;
+ALTER TABLE post_draft_by_user RENAME TO post_draft;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- published posts whose emails the background task hasn't written yet, so publishing doesn't
+-- wait for an email per subscriber
+CREATE TABLE newsletter_digest (
+    post_id VARCHAR NOT NULL PRIMARY KEY,
+    created_at VARCHAR NOT NULL
+);
@@ -1,24 +1,64 @@
 use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
+use crate::component::auth::{provide_auth, use_auth, LoginPage, UserMenu};
+use crate::component::author_page::AuthorPage;
+use crate::component::authors::Authors;
+use crate::component::drafts::Drafts;
+use crate::component::review::{ReviewPost, ReviewQueue};
 use crate::component::edit_post::EditPost;
+use crate::component::newsletter::{NewsletterConfirm, NewsletterUnsubscribe};
 use crate::component::blog_previews::BlogPreviews;
+use crate::component::settings::{provide_settings, use_settings, AdminSettings};
+use crate::component::theme::{provide_theme, ThemeSwitcher};
 use crate::component::toast::Toast;
+use crate::component::trash::Trash;
+use crate::component::users::AdminUsers;
 use crate::component::view_post::ViewPost;
+use crate::component::webhooks::AdminWebhooks;
 
 #[component]
 pub fn Navbar() -> impl IntoView {
+    let auth = use_auth();
+    let settings = use_settings();
+
+    // only link to the pages the current user can use
+    let role_links = move || {
+        auth.current_user().map(|user| {
+            view! {
+                <li><a href="/edit" class="hover:text-blue-400">Create</a></li>
+                <li><a href="/drafts" class="hover:text-blue-400">Drafts</a></li>
+                {user.is_reviewer().then(|| view! {
+                    <li><a href="/review" class="hover:text-blue-400">Review</a></li>
+                })}
+                {user.can_manage_trash().then(|| view! {
+                    <li><a href="/trash" class="hover:text-blue-400">Trash</a></li>
+                })}
+                {user.can_manage_users().then(|| view! {
+                    <li><a href="/admin/users" class="hover:text-blue-400">Users</a></li>
+                    <li><a href="/admin/settings" class="hover:text-blue-400">Settings</a></li>
+                    <li><a href="/admin/webhooks" class="hover:text-blue-400">Webhooks</a></li>
+                })}
+            }
+        })
+    };
+
     view! {
-        <div class="dark:bg-gray-800 text-white p-4">
+        <div class="bg-gray-200 text-gray-900 dark:bg-gray-800 dark:text-white p-4">
             <div class="container mx-auto flex justify-between items-center">
                 // title on the left
-                <a href="/" class="text-2xl font-bold">Moonbound</a>
+                <a href="/" class="text-2xl font-bold">
+                    <Transition fallback=|| ()>{move || settings.get().blog_title}</Transition>
+                </a>
 
                 // nav bar
                 <nav>
-                    <ul class="flex space-x-4">
+                    <ul class="flex items-center space-x-4">
                         <li><a href="/" class="hover:text-blue-400">Blog</a></li>
-                        <li><a href="/edit" class="hover:text-blue-400">Create</a></li>
+                        <li><a href="/authors" class="hover:text-blue-400">Authors</a></li>
+                        <Transition fallback=|| ()>{role_links}</Transition>
+                        <li><ThemeSwitcher/></li>
+                        <li><UserMenu/></li>
                     </ul>
                 </nav>
             </div>
@@ -26,29 +66,66 @@ pub fn Navbar() -> impl IntoView {
     }
 }
 
+/// The compiled stylesheet. The server links to its fingerprinted URL; in the browser,
+/// the page already links to it, so that link is kept as it is.
+fn stylesheet_href() -> String {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "ssr")] {
+            format!("{}/leptos_start.css", crate::server::assets::pkg_url())
+        } else if #[cfg(feature = "hydrate")] {
+            document()
+                .get_element_by_id("leptos")
+                .and_then(|link| link.get_attribute("href"))
+                .unwrap_or_else(|| "/pkg/leptos_start.css".to_string())
+        } else {
+            "/pkg/leptos_start.css".to_string()
+        }
+    }
+}
+
 #[component]
 pub fn App() -> impl IntoView {
     // Provides context that manages stylesheets, titles, meta tags, etc.
     provide_meta_context();
+    #[cfg(feature = "ssr")]
+    crate::server::security::record_nonce();
+    let theme = provide_theme();
+    provide_auth();
+    let settings = provide_settings();
 
     view! {
         // injects a stylesheet into the document <head>
         // id=leptos means cargo-leptos will hot-reload this stylesheet
-        <Stylesheet id="leptos" href="/pkg/leptos_start.css"/>
+        <Stylesheet id="leptos" href=stylesheet_href()/>
+        // the theme class is rendered on the server from a cookie, so there is no flash of the wrong theme
+        <Html class=move || theme.get().html_class()/>
 
         // sets the document title
-        <Title text="Moonbound"/>
+        <Title text=move || settings.get().blog_title/>
         <Toast/>
 
-        <Navbar/>
-        // content for this welcome page
-
         <Router>
-            <main class="dark:bg-gray-700 dark:text-gray-200 p-8 h-full">
+            // inside the router, since it has forms for logging out
+            <Navbar/>
+            <main class="bg-white text-gray-900 dark:bg-gray-700 dark:text-gray-200 p-8 h-full">
                 <Routes>
                     <Route path="" view=BlogPreviews/>
                     <Route path="/edit/:post_id?" view=EditPost/>
-                    <Route path="/view/:post_id?" view=ViewPost/>
+                    // the post blocks rendering so a missing one can still set a 404 status
+                    <Route path="/view/:post_id?" view=ViewPost ssr=SsrMode::PartiallyBlocked/>
+                    <Route path="/trash" view=Trash/>
+                    <Route path="/drafts" view=Drafts/>
+                    <Route path="/review" view=ReviewQueue/>
+                    <Route path="/review/:post_id" view=ReviewPost/>
+                    <Route path="/login" view=LoginPage/>
+                    <Route path="/admin/users" view=AdminUsers/>
+                    <Route path="/admin/settings" view=AdminSettings/>
+                    <Route path="/admin/webhooks" view=AdminWebhooks/>
+                    <Route path="/authors" view=Authors/>
+                    <Route path="/newsletter/confirm/:token" view=NewsletterConfirm/>
+                    <Route path="/newsletter/unsubscribe/:token" view=NewsletterUnsubscribe/>
+                    <Route path="/author/:handle" view=AuthorPage ssr=SsrMode::Async/>
+                    <Route path="/*any" view=NotFound/>
                 </Routes>
             </main>
         </Router>
@@ -57,7 +134,7 @@ pub fn App() -> impl IntoView {
 
 /// 404 - Not Found
 #[component]
-fn NotFound() -> impl IntoView {
+pub fn NotFound() -> impl IntoView {
     // set an HTTP status code 404
     // this is feature gated because it can only be done during
     // initial server-side rendering
@@ -73,6 +150,10 @@ fn NotFound() -> impl IntoView {
     }
 
     view! {
-        <h1>"Not Found"</h1>
+        <div class="flex flex-col items-center pt-10">
+            <h1 class="text-4xl pb-4">"Not Found"</h1>
+            <p class="pb-4">"We couldn't find what you were looking for."</p>
+            <a href="/" class="hover:text-blue-400">"Back to the blog"</a>
+        </div>
     }
 }
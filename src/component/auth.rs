@@ -0,0 +1,149 @@
+use leptos::*;
+use leptos_router::*;
+
+use super::toast::use_toasts;
+use super::toast::ToastMessage;
+use crate::error::AppError;
+use crate::model::user::CurrentUser;
+use crate::repository::user_repository::get_csrf_token;
+use crate::repository::user_repository::get_current_user;
+use crate::repository::user_repository::Login;
+use crate::repository::user_repository::Logout;
+
+/// Name of the form field carrying the session's CSRF token. Server functions called from
+/// code take it as a `_csrf` argument they don't use themselves: the CSRF middleware checks
+/// it before they run.
+pub const CSRF_FIELD: &str = "_csrf";
+
+/// Who is logged in, and the actions that change it. Available in context.
+#[derive(Clone, Copy)]
+pub struct Auth {
+    pub login: Action<Login, Result<CurrentUser, ServerFnError>>,
+    pub logout: Action<Logout, Result<(), ServerFnError>>,
+    /// Reloaded whenever someone logs in or out.
+    pub user: Resource<(usize, usize), Option<CurrentUser>>,
+    /// The session's CSRF token, which every request that changes something has to send.
+    pub csrf_token: Resource<(usize, usize), Option<String>>,
+}
+
+impl Auth {
+    pub fn current_user(&self) -> Option<CurrentUser> {
+        self.user.get().flatten()
+    }
+
+    /// The CSRF token, for server functions called directly rather than through a form.
+    pub fn csrf_token(&self) -> String {
+        self.csrf_token
+            .get_untracked()
+            .flatten()
+            .unwrap_or_default()
+    }
+}
+
+pub fn provide_auth() -> Auth {
+    let login = create_server_action::<Login>();
+    let logout = create_server_action::<Logout>();
+    let user = create_resource(
+        move || (login.version().get(), logout.version().get()),
+        // a failed lookup is as good as being logged out
+        |_| async move { get_current_user().await.ok().flatten() },
+    );
+    let csrf_token = create_resource(
+        move || (login.version().get(), logout.version().get()),
+        |_| async move { get_csrf_token().await.ok().flatten() },
+    );
+
+    let auth = Auth {
+        login,
+        logout,
+        user,
+        csrf_token,
+    };
+    provide_context(auth);
+    auth
+}
+
+pub fn use_auth() -> Auth {
+    expect_context::<Auth>()
+}
+
+/// The hidden field with the session's CSRF token. Every `ActionForm` needs one, or the
+/// server turns the form away.
+#[component]
+pub fn CsrfField() -> impl IntoView {
+    let auth = use_auth();
+
+    view! {
+        <Transition fallback=|| ()>
+            {move || view! {
+                <input type="hidden" name=CSRF_FIELD value=auth.csrf_token.get().flatten().unwrap_or_default()/>
+            }}
+        </Transition>
+    }
+}
+
+/// The logged-in user's name and a logout button, or a login link.
+#[component]
+pub fn UserMenu() -> impl IntoView {
+    let auth = use_auth();
+
+    view! {
+        <Transition fallback=|| ()>
+            {move || match auth.current_user() {
+                Some(user) => view! {
+                    <div class="flex items-center space-x-2">
+                        <span>{user.display_name}</span>
+                        <ActionForm action=auth.logout>
+                            <CsrfField/>
+                            <input type="submit" value="Log out" class="hover:text-blue-400 cursor-pointer bg-transparent"/>
+                        </ActionForm>
+                    </div>
+                }
+                .into_view(),
+                None => view! { <a href="/login" class="hover:text-blue-400">"Log in"</a> }.into_view(),
+            }}
+        </Transition>
+    }
+}
+
+#[component]
+pub fn LoginPage() -> impl IntoView {
+    let auth = use_auth();
+    let toasts = use_toasts();
+
+    create_effect(move |_| match auth.login.value().get() {
+        Some(Ok(user)) => {
+            toasts.show(ToastMessage::success(format!(
+                "Welcome back, {}.",
+                user.display_name
+            )));
+            let navigate = use_navigate();
+            navigate("/", Default::default());
+        }
+        Some(Err(e)) => {
+            let message = AppError::from_server_fn_error(&e)
+                .map(|e| e.to_string())
+                .unwrap_or(e.to_string());
+            toasts.show(ToastMessage::error(message));
+        }
+        None => {}
+    });
+
+    view! {
+        <div class="max-w-sm mx-auto">
+            <div class="text-4xl pb-6">"Log in"</div>
+            <ActionForm action=auth.login>
+                <CsrfField/>
+                <label class="block mb-4">
+                    <span>"Handle"</span>
+                    <input class="mt-1 p-2 w-full" type="text" name="handle" autocomplete="username" required/>
+                </label>
+                <label class="block mb-4">
+                    <span>"Password"</span>
+                    <input class="mt-1 p-2 w-full" type="password" name="password" autocomplete="current-password" required/>
+                </label>
+                <input type="submit" value="Log in" class="bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+            </ActionForm>
+        </div>
+    }
+}
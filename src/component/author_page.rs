@@ -0,0 +1,99 @@
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use super::blog_preview_card::BlogPreviewCard;
+use super::errors_fallback::error_fallback;
+use crate::app::NotFound;
+use crate::error::AppError;
+use crate::model::author::Author;
+use crate::model::blog_post::Post;
+use crate::repository::author_repository::get_author;
+use crate::repository::blog_repository::get_previews;
+
+#[derive(Params, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+struct AuthorPageParams {
+    handle: Option<String>,
+}
+
+#[component]
+fn AuthorHeader(author: Author) -> impl IntoView {
+    let avatar = author.avatar_src.map(|src| {
+        view! {
+            <div class="mb-5 h-40 w-40 shadow-xl overflow-hidden rounded-full">
+                <img src=src alt="" class="h-full w-full object-cover"/>
+            </div>
+        }
+    });
+
+    view! {
+        <div class="p-5 flex flex-col items-center">
+            {avatar}
+            <div class="p-2 text-4xl">{author.display_name}</div>
+            <div class="p-2 text-xl">{author.bio}</div>
+        </div>
+    }
+}
+
+/// Lists the posts written by the author in the `:handle` path parameter.
+#[component]
+pub fn AuthorPage() -> impl IntoView {
+    let params: Memo<Result<_, _>> = use_params::<AuthorPageParams>();
+    let handle = move || {
+        params.with(|params| match params {
+            Ok(AuthorPageParams {
+                handle: Some(handle),
+            }) => Some(handle.clone()),
+            _ => None,
+        })
+    };
+
+    let author_resource: Resource<_, Result<Author, ServerFnError>> =
+        create_resource(handle, |handle| async move {
+            match handle {
+                Some(handle) => get_author(handle).await,
+                None => Err(AppError::NotFound("no author handle given".to_string()).into()),
+            }
+        });
+    let posts_resource = create_resource(handle, |handle| async move {
+        get_previews(None, None, handle, 40, 10).await
+    });
+
+    let author_view = move || {
+        author_resource.get().map(|res| match res {
+            Ok(author) => Ok(view! { <AuthorHeader author=author/> }.into_view()),
+            Err(e) => match AppError::from_server_fn_error(&e) {
+                Some(AppError::NotFound(_)) => Ok(view! { <NotFound/> }.into_view()),
+                _ => Err(e),
+            },
+        })
+    };
+
+    let posts_view = move || -> Option<Result<View, _>> {
+        // there is nothing to list for an unknown author
+        if let Some(Err(_)) = author_resource.get() {
+            return None;
+        }
+        posts_resource.and_then(|previews: &Vec<Post>| {
+            previews
+                .iter()
+                .map(|preview| {
+                    view! {
+                        <BlogPreviewCard blog_preview={preview.clone()}/>
+                    }
+                })
+                .collect_view()
+        })
+    };
+
+    view! {
+        <Suspense fallback=move || view! { <p>"Loading..."</p> }>
+            <ErrorBoundary fallback={error_fallback()}>
+                {author_view}
+                <div class="bg-gray-100 dark:bg-gray-800 p-8 rounded-lg flex flex-wrap">
+                    {posts_view}
+                </div>
+            </ErrorBoundary>
+        </Suspense>
+    }
+}
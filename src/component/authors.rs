@@ -0,0 +1,97 @@
+use leptos::*;
+
+use super::auth::use_auth;
+use super::auth::CsrfField;
+use super::errors_fallback::error_fallback;
+use super::toast::use_toasts;
+use super::toast::ToastMessage;
+use crate::error::AppError;
+use crate::model::author::Author;
+use crate::repository::author_repository::get_authors;
+use crate::repository::author_repository::UpsertAuthor;
+
+/// Lists the blog's authors, with a form for admins to add one or update a profile.
+#[component]
+pub fn Authors() -> impl IntoView {
+    let upsert_author = create_server_action::<UpsertAuthor>();
+    let authors_resource = create_resource(
+        move || upsert_author.version().get(),
+        |_| async move { get_authors().await },
+    );
+
+    let auth = use_auth();
+    let can_manage_users = move || {
+        auth.current_user()
+            .map_or(false, |user| user.can_manage_users())
+    };
+
+    let toasts = use_toasts();
+    create_effect(move |_| match upsert_author.value().get() {
+        Some(Ok(handle)) => {
+            toasts.show(ToastMessage::success(format!("Saved author @{}.", handle)));
+        }
+        Some(Err(e)) => {
+            let message = AppError::from_server_fn_error(&e)
+                .map(|e| e.to_string())
+                .unwrap_or(e.to_string());
+            toasts.show(ToastMessage::error(message));
+        }
+        None => {}
+    });
+
+    let authors_view = move || {
+        authors_resource.and_then(|authors: &Vec<Author>| {
+            authors
+                .iter()
+                .map(|author| {
+                    view! {
+                        <li class="pb-2">
+                            <a href=format!("/author/{}", author.handle) class="hover:text-blue-400">
+                                {author.display_name.clone()}
+                            </a>
+                            <span class="text-gray-500">{format!(" @{}", author.handle)}</span>
+                        </li>
+                    }
+                })
+                .collect_view()
+        })
+    };
+
+    view! {
+        <div class="max-w-3xl mx-auto">
+            <div class="text-4xl pb-6">"Authors"</div>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback={error_fallback()}>
+                    <ul class="pb-8">{authors_view}</ul>
+                </ErrorBoundary>
+            </Transition>
+            <Show when=can_manage_users fallback=|| ()>
+            <div class="bg-gray-100 dark:bg-gray-800 p-10 rounded-md">
+                <div class="text-2xl pb-4">"Add or update an author"</div>
+                <ActionForm action=upsert_author>
+                    <CsrfField/>
+                    <label class="block mb-4">
+                        <span>Handle</span>
+                        <input class="mt-1 p-2 w-full" type="text" name="handle"/>
+                    </label>
+                    <label class="block mb-4">
+                        <span>Display name</span>
+                        <input class="mt-1 p-2 w-full" type="text" name="display_name"/>
+                    </label>
+                    <label class="block mb-4">
+                        <span>Avatar URL</span>
+                        <input class="mt-1 p-2 w-full" type="text" name="avatar_url"/>
+                    </label>
+                    <label class="block mb-4">
+                        <span>Bio</span>
+                        <textarea class="mt-1 p-2 w-full" name="bio"></textarea>
+                    </label>
+                    <div class="flex justify-center">
+                        <input type="submit" value="Save" class="mx-auto w-1/3 bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+                    </div>
+                </ActionForm>
+            </div>
+            </Show>
+        </div>
+    }
+}
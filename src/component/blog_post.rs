@@ -1,17 +1,54 @@
 use leptos::*;
 
+use super::byline::Byline;
 use crate::model::blog_post::Post;
+use crate::model::post_body::{parse_blocks, Block};
 
 #[component]
 pub fn BlogPost(post: Post) -> impl IntoView {
     let dt = format!("{}", post.dt.format("%B %e, %Y %I:%M%P"));
+    let mut paragraphs_html = post.paragraphs_html.clone().into_iter();
+    let body = parse_blocks(&post.text)
+        .into_iter()
+        .map(|block| match block {
+            // the post title is the page's top heading, so `#` starts one level below it
+            Block::Heading(heading) => match heading.level {
+                1 => view! { <h2 id=heading.anchor class="text-3xl pt-6 pb-2 scroll-mt-4">{heading.text}</h2> }.into_view(),
+                2 => view! { <h3 id=heading.anchor class="text-2xl pt-4 pb-2 scroll-mt-4">{heading.text}</h3> }.into_view(),
+                _ => view! { <h4 id=heading.anchor class="text-xl pt-2 pb-2 scroll-mt-4">{heading.text}</h4> }.into_view(),
+            },
+            // as text until the server has sanitized it, as in the editor's preview of unsaved
+            // edits
+            Block::Paragraph(text) => match paragraphs_html.next() {
+                Some(html) => view! { <p class="whitespace-pre-wrap pb-4" inner_html=html></p> }.into_view(),
+                None => view! { <p class="whitespace-pre-wrap pb-4">{text}</p> }.into_view(),
+            },
+        })
+        .collect_view();
+
+    let image = post.image_src.clone().map(|src| {
+        view! { <img src=src alt="Post thumbnail" class="w-96 h-32 rounded-lg object-cover my-10"/> }
+    });
+
+    let tags = post
+        .tags
+        .iter()
+        .map(|tag| {
+            view! { <li class="bg-gray-200 dark:bg-gray-600 text-sm rounded px-2 py-1 mr-2 mb-2">{format!("#{}", tag)}</li> }
+        })
+        .collect_view();
 
     view! {
         <div class="block p-10">
             <div class="text-xl">{dt}</div>
-            <img src={&post.image_url} alt="Post thumbnail" class="w-96 h-32 rounded-lg object-cover my-10"/>
-            <div class="text-4xl pb-4">{&post.title}</div>
-            <div>{&post.text}</div>
+            {image}
+            <div class="text-4xl pb-2">{&post.title}</div>
+            <div class="pb-4">
+                <Byline handle=post.author_handle.clone() name=post.author_name.clone() avatar_src=post.author_avatar_src.clone()/>
+                <div class="text-sm text-gray-600 dark:text-gray-300 pt-1">{post.reading_stats.label()}</div>
+            </div>
+            <ul class="flex flex-wrap pb-4">{tags}</ul>
+            <div>{body}</div>
         </div>
     }
 }
@@ -1,22 +1,32 @@
 use leptos::*;
 
+use super::byline::Byline;
 use crate::model::blog_post::Post;
 
 #[component]
 pub fn BlogPreviewCard(blog_preview: Post) -> impl IntoView {
     let dt = format!("{}", blog_preview.dt.format("%b %e, %Y %I:%M%P"));
+    let image = blog_preview.image_src.map(|src| {
+        view! { <img src=src alt="Blog Thumbnail" class="w-32 h-32 rounded-lg object-cover mr-4"/> }
+    });
     view! {
         <a href={format!("/view/{}", blog_preview.id)}>
-            <div class="transform transition duration-300 hover:scale-105 hover:shadow-2xl dark:bg-gray-600 p-6 rounded-lg shadow-md mb-6 mr-10 flex flex-none w-96 h-48">
-                <img src={blog_preview.image_url} alt="Blog Thumbnail" class="w-32 h-32 rounded-lg object-cover mr-4"/>
+            <div class="transform transition duration-300 hover:scale-105 hover:shadow-2xl bg-white dark:bg-gray-600 p-6 rounded-lg shadow-md mb-6 mr-10 flex flex-none w-96 h-48">
+                {image}
 
                 <div class="flex-none">
-                    <h2 class="text-xl font-semibold mb-2 w-48 h-10 truncate">{blog_preview.title}</h2>
+                    <h2 class="text-xl font-semibold mb-1 w-48 truncate">{blog_preview.title}</h2>
+                    <div class="mb-2 w-48 truncate">
+                        // the whole card is already a link to the post
+                        <Byline handle=blog_preview.author_handle name=blog_preview.author_name
+                            avatar_src=blog_preview.author_avatar_src link=false/>
+                    </div>
 
-                    <p class="dark:text-gray-200 mb-4 w-48 h-18">{blog_preview.text}</p>
+                    <p class="text-gray-700 dark:text-gray-200 mb-4 w-48 h-18">{blog_preview.text}</p>
 
-                    <div class="flex justify-between">
-                        <span class="dark:text-gray-200">{dt}</span>
+                    <div class="flex flex-col text-gray-600 dark:text-gray-200">
+                        <span>{dt}</span>
+                        <span class="text-sm">{format!("{} min read", blog_preview.reading_stats.minutes)}</span>
                     </div>
                 </div>
             </div>
@@ -2,18 +2,24 @@ use leptos::*;
 
 use super::errors_fallback::error_fallback;
 use super::blog_preview_card::BlogPreviewCard;
+use super::newsletter::SubscribeForm;
+use super::settings::use_settings;
 use crate::model::blog_post::Post;
 use crate::repository::blog_repository::get_previews;
 
 #[component]
 fn BlogDescription() -> impl IntoView {
+    let settings = use_settings();
+
     view! {
         <div class="p-5 flex flex-col items-center">
             <div class="mb-5 h-40 w-40 shadow-xl overflow-hidden rounded-full">
                 <img src="http://cttm.io/images/CodeToTheMoonV1Square.png"/>
             </div>
-            <div class="p-2 text-4xl">"Moonbound"</div>
-            <div class="p-2 text-xl">"A travel blog about fun places"</div>
+            <Transition fallback=|| ()>
+                <div class="p-2 text-4xl">{move || settings.get().blog_title}</div>
+                <div class="p-2 text-xl">{move || settings.get().blog_tagline}</div>
+            </Transition>
         </div>
     }
 }
@@ -22,7 +28,7 @@ fn BlogDescription() -> impl IntoView {
 pub fn BlogPreviews() -> impl IntoView {
     let post_resource = create_resource(
         || {},
-        |_| async move { get_previews(None, None, 40, 10).await },
+        |_| async move { get_previews(None, None, None, 40, 10).await },
     );
 
     let previews_view = move || -> Option<Result<View, _>>{
@@ -40,7 +46,8 @@ pub fn BlogPreviews() -> impl IntoView {
 
     view! {
         <BlogDescription/>
-        <div class="dark:bg-gray-800 p-8 rounded-lg flex flex-wrap">
+        <SubscribeForm/>
+        <div class="bg-gray-100 dark:bg-gray-800 p-8 rounded-lg flex flex-wrap">
             <Suspense fallback=move || view! { <p>"Loading..."</p> }>
                 <ErrorBoundary fallback={error_fallback()}>
                     {previews_view}
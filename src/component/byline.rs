@@ -0,0 +1,35 @@
+use leptos::*;
+
+/// "by <author>" with the author's avatar. Renders nothing for posts without an author.
+#[component]
+pub fn Byline(
+    handle: Option<String>,
+    name: Option<String>,
+    /// As given by the server's image policy.
+    avatar_src: Option<String>,
+    /// Link the name to the author's page. Off inside elements that are links themselves.
+    #[prop(default = true)]
+    link: bool,
+) -> impl IntoView {
+    let (handle, name) = match (handle, name) {
+        (Some(handle), Some(name)) => (handle, name),
+        _ => return None,
+    };
+    let avatar = avatar_src.map(|src| {
+        view! { <img src=src alt="" class="w-6 h-6 rounded-full object-cover mr-2"/> }
+    });
+    let name = if link {
+        view! { <a href=format!("/author/{}", handle) class="hover:text-blue-400">{name}</a> }
+            .into_view()
+    } else {
+        name.into_view()
+    };
+
+    Some(view! {
+        <div class="flex items-center text-sm">
+            {avatar}
+            <span class="mr-1">"by"</span>
+            {name}
+        </div>
+    })
+}
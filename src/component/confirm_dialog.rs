@@ -0,0 +1,30 @@
+use leptos::*;
+
+/// A modal asking the user to confirm a destructive action.
+///
+/// The children provide the confirming control, typically an `ActionForm` with a submit button,
+/// so the action still goes through the regular form submission.
+#[component]
+pub fn ConfirmDialog(
+    #[prop(into)] title: String,
+    #[prop(into)] message: String,
+    #[prop(into)] on_cancel: Callback<()>,
+    children: Children,
+) -> impl IntoView {
+    view! {
+        <div class="fixed inset-0 bg-black bg-opacity-50 flex items-center justify-center z-30"
+            role="dialog" aria-modal="true">
+            <div class="dark:bg-gray-800 bg-gray-100 p-8 rounded-lg max-w-md w-full">
+                <div class="text-2xl pb-2">{title}</div>
+                <p class="pb-6">{message}</p>
+                <div class="flex justify-end items-center space-x-4">
+                    <button class="bg-gray-500 hover:bg-gray-700 text-white font-bold py-2 px-4 rounded"
+                        on:click=move |_| on_cancel.call(())>
+                        "Cancel"
+                    </button>
+                    {children()}
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,44 @@
+use leptos::*;
+
+use super::errors_fallback::error_fallback;
+use crate::model::blog_post::Post;
+use crate::repository::blog_repository::get_unpublished_posts;
+
+/// Posts that aren't public yet and that the current user can work on.
+#[component]
+pub fn Drafts() -> impl IntoView {
+    let drafts_resource = create_resource(|| (), |_| async move { get_unpublished_posts().await });
+
+    let drafts_view = move || {
+        drafts_resource.and_then(|posts: &Vec<Post>| {
+            if posts.is_empty() {
+                return view! { <p>"There are no drafts."</p> }.into_view();
+            }
+            posts
+                .iter()
+                .map(|post| {
+                    let dt = format!("{}", post.dt.format("%b %e, %Y %I:%M%P"));
+                    let byline = post.author_name.clone().unwrap_or("No author".to_string());
+                    view! {
+                        <a href=format!("/edit/{}", post.id)
+                            class="block bg-gray-100 dark:bg-gray-600 hover:bg-gray-200 dark:hover:bg-gray-500 p-4 rounded-lg mb-4">
+                            <div class="text-xl font-semibold">{post.title.clone()}</div>
+                            <div class="text-gray-600 dark:text-gray-200 text-sm">{format!("{} · {} · {}", post.status.label(), byline, dt)}</div>
+                        </a>
+                    }
+                })
+                .collect_view()
+        })
+    };
+
+    view! {
+        <div class="max-w-3xl mx-auto">
+            <div class="text-4xl pb-6">"Drafts"</div>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback={error_fallback()}>
+                    {drafts_view}
+                </ErrorBoundary>
+            </Transition>
+        </div>
+    }
+}
@@ -1,26 +1,73 @@
+use super::auth::use_auth;
+use super::auth::CsrfField;
 use super::blog_post::BlogPost;
+use super::confirm_dialog::ConfirmDialog;
 use super::errors_fallback::error_fallback;
+use super::local_draft;
+use super::merge_dialog::MergeDialog;
+use super::review::ReviewNotes;
+use super::toast::use_toasts;
 use super::toast::ToastMessage;
-use super::toast::ToastType;
 use chrono::Duration;
 use chrono::DurationRound;
 use chrono::Local;
 use chrono::NaiveDateTime;
-use leptos::logging::log;
+use leptos::leptos_dom::helpers::TimeoutHandle;
 use leptos::*;
 use leptos_router::*;
 
+use crate::error::AppError;
+use crate::model::author::Author;
+use crate::model::blog_post::parse_tags;
 use crate::model::blog_post::Post;
+use crate::model::blog_post::PostStatus;
+use crate::model::draft::Draft;
+use crate::model::user::CurrentUser;
+use crate::repository::author_repository::get_authors;
 use crate::repository::blog_repository::get_post;
+use crate::repository::blog_repository::render_paragraphs;
+use crate::repository::blog_repository::restore_post;
 use crate::repository::blog_repository::DeletePost;
 use crate::repository::blog_repository::UpsertPost;
+use crate::repository::draft_repository::get_draft;
+use crate::repository::draft_repository::DiscardDraft;
+use crate::repository::draft_repository::SaveDraft;
+use crate::repository::image_repository::get_image_src;
 use serde::{Deserialize, Serialize};
+use std::rc::Rc;
 
 #[derive(Params, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 struct EditPostParams {
     post_id: Option<String>,
 }
 
+/// How often unsaved edits are written out as a draft.
+const AUTOSAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How long typing has to pause before the preview asks the server to render it again.
+const PREVIEW_DELAY: std::time::Duration = std::time::Duration::from_millis(400);
+
+/// Follows `source`, but only once it has stopped changing for `delay`.
+fn debounced<T: Clone + PartialEq + 'static>(
+    source: Memo<T>,
+    delay: std::time::Duration,
+) -> ReadSignal<T> {
+    let (value, set_value) = create_signal(source.get_untracked());
+    create_effect(move |pending: Option<Option<TimeoutHandle>>| {
+        let next = source.get();
+        if let Some(Some(handle)) = pending {
+            handle.clear();
+        }
+        let settle = move || {
+            if value.get_untracked() != next {
+                set_value.set(next);
+            }
+        };
+        set_timeout_with_handle(settle, delay).ok()
+    });
+    value
+}
+
 fn format_dt(datetime: NaiveDateTime) -> String {
     datetime.format("%Y-%m-%dT%H:%M").to_string()
 }
@@ -39,47 +86,400 @@ pub fn EditPost() -> impl IntoView {
         },
     );
 
+    // only the server can say how an image URL is shown, so ask it once the URL settles
+    let image_url = create_memo(move |_| {
+        post_resource
+            .get()
+            .and_then(|res| res.ok())
+            .map(|post| post.image_url)
+            .unwrap_or_default()
+    });
+    let image_url = debounced(image_url, PREVIEW_DELAY);
+    let image_src_resource = create_resource(
+        move || image_url.get(),
+        |url| async move { get_image_src(url).await },
+    );
+
+    // likewise for the text, which only the server can sanitize
+    let text = create_memo(move |_| {
+        post_resource
+            .get()
+            .and_then(|res| res.ok())
+            .map(|post| post.text)
+            .unwrap_or_default()
+    });
+    let text = debounced(text, PREVIEW_DELAY);
+    let paragraphs_resource = create_resource(
+        move || text.get(),
+        |text| async move {
+            let html = render_paragraphs(text.clone()).await;
+            (text, html)
+        },
+    );
+
+    let authors_resource: Resource<(), Result<Vec<Author>, ServerFnError>> =
+        create_resource(|| (), |_| async move { get_authors().await });
+
+    // the server enforces these too, the editor only hides what wouldn't go through
+    let auth = use_auth();
+    let permitted = move |check: fn(&CurrentUser, &Post) -> bool| match (
+        auth.current_user(),
+        post_resource.get().and_then(|res| res.ok()),
+    ) {
+        (Some(user), Some(post)) => check(&user, &post),
+        _ => false,
+    };
+    // kept apart from the post, so permissions keep following the status it was saved with
+    let selected_status = create_rw_signal::<Option<PostStatus>>(None);
+    let status_value = move || {
+        selected_status.get().or_else(|| {
+            post_resource
+                .get()
+                .and_then(|res| res.ok())
+                .map(|post| post.status)
+        })
+    };
+    let can_set_status = move |status: PostStatus| match (
+        auth.current_user(),
+        post_resource.get().and_then(|res| res.ok()),
+    ) {
+        (Some(user), Some(post)) => user.can_set_status(&post, status),
+        _ => false,
+    };
+    let can_assign_author = move || {
+        auth.current_user()
+            .map_or(false, |user| user.can_assign_author())
+    };
+
     let upsert_post = create_server_action::<UpsertPost>();
     let delete_post = create_server_action::<DeletePost>();
+    let save_draft = create_server_action::<SaveDraft>();
+    let discard_draft = create_server_action::<DiscardDraft>();
+
+    let draft_key = move || {
+        params.with(|params| match params {
+            Ok(EditPostParams { post_id: Some(s) }) => Draft::key_for(s),
+            _ => Draft::key_for(""),
+        })
+    };
+
+    // edits mark the post dirty until they are autosaved or submitted
+    let dirty = create_rw_signal(false);
+
+    let store_local_draft = move || {
+        if let Some(Ok(post)) = untrack(move || post_resource.get()) {
+            local_draft::store(&Draft::from_post(&post, Local::now().naive_local()));
+        }
+    };
 
-    let set_toast: WriteSignal<ToastMessage> = expect_context();
+    let autosave = move || {
+        if !dirty.get_untracked() {
+            return;
+        }
+        if let Some(Ok(post)) = untrack(move || post_resource.get()) {
+            store_local_draft();
+            save_draft.dispatch(SaveDraft {
+                post_id: post.id,
+                base_version: post.version,
+                dt: format_dt(post.dt),
+                image_url: post.image_url,
+                title: post.title,
+                text: post.text,
+                _csrf: auth.csrf_token(),
+            });
+            dirty.set(false);
+        }
+    };
+
+    // effects only run in the browser, which is the only place timers and unload warnings
+    // make sense
+    create_effect(move |_| {
+        let autosave_handle = set_interval_with_handle(autosave, AUTOSAVE_INTERVAL).ok();
+        let unload_handle = window_event_listener(ev::beforeunload, move |ev| {
+            if dirty.get_untracked() {
+                // there is no time for a server round trip, but localStorage is synchronous
+                store_local_draft();
+                ev.prevent_default();
+                ev.set_return_value("You have unsaved changes.");
+            }
+        });
+        on_cleanup(move || {
+            if let Some(handle) = autosave_handle {
+                handle.clear();
+            }
+            unload_handle.remove();
+        });
+    });
+
+    // drafts left behind by an earlier session, on the server and in this browser
+    let server_draft = create_resource(
+        move || params.get(),
+        |params| async move {
+            match params {
+                Ok(EditPostParams { post_id }) => get_draft(post_id.unwrap_or_default()).await,
+                _ => Ok(None),
+            }
+        },
+    );
+    let browser_draft = create_rw_signal::<Option<Draft>>(None);
+    create_effect(move |_| browser_draft.set(local_draft::load(&draft_key())));
+    let draft_dismissed = create_rw_signal(false);
+
+    // the newest draft that holds changes the post doesn't have
+    let restorable_draft = move || -> Option<Draft> {
+        if draft_dismissed.get() {
+            return None;
+        }
+        let post = post_resource.get()?.ok()?;
+        let server = server_draft.get().and_then(|res| res.ok()).flatten();
+        [server, browser_draft.get()]
+            .into_iter()
+            .flatten()
+            .filter(|draft| draft.differs_from(&post))
+            .max_by_key(|draft| draft.saved_at)
+    };
+
+    let discard_drafts = move |_: ev::MouseEvent| {
+        local_draft::clear(&draft_key());
+        discard_draft.dispatch(DiscardDraft {
+            post_id: draft_key(),
+            _csrf: auth.csrf_token(),
+        });
+        draft_dismissed.set(true);
+    };
+
+    let draft_banner = move || {
+        restorable_draft().map(|draft| {
+            let saved_at = format!("{}", draft.saved_at.format("%b %e, %Y %I:%M%P"));
+            view! {
+                <div class="flex items-center justify-between bg-yellow-100 text-yellow-900 p-4 rounded mb-4">
+                    <span>{format!("You have an unsaved draft from {}.", saved_at)}</span>
+                    <div class="flex space-x-2">
+                        <button class="bg-blue-500 hover:bg-blue-700 text-white font-bold py-1 px-3 rounded"
+                            on:click=move |_| {
+                                post_resource.update(|curr| {
+                                    if let Some(Ok(post)) = curr {
+                                        draft.apply_to(post);
+                                    }
+                                });
+                                dirty.set(true);
+                                draft_dismissed.set(true);
+                            }>
+                            "Restore unsaved draft"
+                        </button>
+                        <button class="bg-gray-500 hover:bg-gray-700 text-white font-bold py-1 px-3 rounded"
+                            on:click=discard_drafts>
+                            "Discard"
+                        </button>
+                    </div>
+                </div>
+            }
+        })
+    };
+
+    // the saved copy of the post, loaded when a save is rejected because it is stale
+    let load_theirs = create_action(|id: &String| {
+        let id = id.clone();
+        async move { get_post(id).await }
+    });
+
+    let toasts = use_toasts();
     // take them to the new or updated post once they create or edit it
     create_effect(move |_| {
         let id = upsert_post.value().get();
-        if let Some(Ok(id)) = id {
-            set_toast.set(ToastMessage {
-                message: String::from("Post submitted."),
-                toast_type: ToastType::Success,
-                visible: true,
-            });
-            let navigate = use_navigate();
-            navigate(format!("/view/{}", id).as_str(), Default::default());
+        match id {
+            Some(Ok(id)) => {
+                // the server dropped its copy of the draft when the post was saved
+                dirty.set(false);
+                selected_status.set(None);
+                local_draft::clear(&draft_key());
+                toasts.show(ToastMessage::success("Post submitted."));
+                let navigate = use_navigate();
+                navigate(format!("/view/{}", id).as_str(), Default::default());
+            }
+            Some(Err(e)) => match AppError::from_server_fn_error(&e) {
+                // someone else saved in the meantime, let the author compare and merge
+                Some(AppError::Conflict(_)) => {
+                    if let Some(Ok(post)) = untrack(move || post_resource.get()) {
+                        load_theirs.dispatch(post.id);
+                    }
+                }
+                app_error => {
+                    toasts.show(ToastMessage::error(
+                        app_error.map(|e| e.to_string()).unwrap_or(e.to_string()),
+                    ));
+                }
+            },
+            None => {}
         }
     });
 
+    // saving on top of the saved version overwrites their changes with ours
+    let keep_mine = move |_: ()| {
+        if let (Some(Ok(mine)), Some(Ok(theirs))) =
+            (post_resource.get(), load_theirs.value().get_untracked())
+        {
+            upsert_post.dispatch(UpsertPost {
+                id: Some(mine.id),
+                version: theirs.version,
+                author_id: mine.author_id,
+                status: selected_status
+                    .get_untracked()
+                    .unwrap_or(mine.status)
+                    .as_str()
+                    .to_string(),
+                dt: format_dt(mine.dt),
+                image_url: mine.image_url,
+                title: mine.title,
+                text: mine.text,
+                excerpt: mine.excerpt.unwrap_or_default(),
+                tags: mine.tags.join(", "),
+                _csrf: auth.csrf_token(),
+            });
+        }
+        load_theirs.value().set(None);
+    };
+
+    let use_theirs = move |_: ()| {
+        if let Some(Ok(theirs)) = load_theirs.value().get_untracked() {
+            post_resource.update(|curr| *curr = Some(Ok(theirs)));
+        }
+        load_theirs.value().set(None);
+    };
+
+    // keep editing our copy, but based on the saved version so the next submit goes through
+    let merge_by_hand = move |_: ()| {
+        if let Some(Ok(theirs)) = load_theirs.value().get_untracked() {
+            post_resource.update(|curr| {
+                if let Some(Ok(post)) = curr {
+                    post.version = theirs.version;
+                }
+            });
+        }
+        load_theirs.value().set(None);
+    };
+
+    let merge_dialog = move || {
+        let theirs = match load_theirs.value().get() {
+            Some(Ok(theirs)) => theirs,
+            _ => return None,
+        };
+        let mine = post_resource.get().and_then(|res| res.ok())?;
+        Some(view! {
+            <MergeDialog mine=mine theirs=theirs
+                on_keep_mine=keep_mine on_use_theirs=use_theirs on_close=merge_by_hand/>
+        })
+    };
+
+    let confirm_delete = create_rw_signal(false);
+
     // take them to the home page if they delete a post
     create_effect(move |_| {
         let id = delete_post.value().get();
         if let Some(Ok(_)) = id {
-            log!("set toast set");
-            set_toast.set(ToastMessage {
-                message: String::from("Post deleted."),
-                toast_type: ToastType::Success,
-                visible: true,
-            });
+            confirm_delete.set(false);
+            let navigate = Rc::new(use_navigate());
+            let deleted_id = untrack(move || post_resource.get())
+                .and_then(|res| res.ok())
+                .map(|post| post.id);
 
-            let navigate = use_navigate();
-            navigate("/", Default::default());
+            let mut toast = ToastMessage::success("Post moved to the trash.")
+                .with_duration(Some(std::time::Duration::from_secs(10)));
+            // the editor is gone by the time "Undo" is clicked, so restore through the server fn
+            // directly
+            if let Some(post_id) = deleted_id {
+                let navigate = navigate.clone();
+                toast = toast.with_action("Undo", move || {
+                    let post_id = post_id.clone();
+                    let navigate = navigate.clone();
+                    spawn_local(async move {
+                        match restore_post(post_id, auth.csrf_token()).await {
+                            Ok(id) => {
+                                toasts.show(ToastMessage::success("Post restored."));
+                                (*navigate)(format!("/view/{}", id).as_str(), Default::default());
+                            }
+                            Err(e) => {
+                                toasts.show(ToastMessage::error(format!(
+                                    "Couldn't restore the post: {}",
+                                    e
+                                )));
+                            }
+                        }
+                    });
+                });
+            }
+            toasts.show(toast);
+
+            (*navigate)("/", Default::default());
         }
     });
 
+    let delete_dialog = move || {
+        confirm_delete.get().then(|| {
+            view! {
+                <ConfirmDialog
+                    title="Delete this post?"
+                    message="The post will be moved to the trash, where it can be restored until it is purged."
+                    on_cancel=move |_: ()| confirm_delete.set(false)>
+                    <ActionForm action=delete_post>
+                        <CsrfField/>
+                        <input type="hidden" name="id"
+                            prop:value={move || post_resource.get().and_then(|res| res.map(|post| post.id).ok())}/>
+                        <input type="submit" value="Delete" class="bg-red-500 hover:bg-red-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+                    </ActionForm>
+                </ConfirmDialog>
+            }
+        })
+    };
+
+    // notes from a review this post went through, and a link to its full history
+    let review_panel = move || {
+        params
+            .with(|params| params.as_ref().ok().and_then(|params| params.post_id.clone()))
+            .map(|post_id| {
+                view! {
+                    <ReviewNotes post_id=post_id.clone()/>
+                    <a href=format!("/review/{}", post_id) class="block pb-4 hover:text-blue-400">"Review history"</a>
+                }
+            })
+    };
+
+    let author_options = move || {
+        let selected = post_resource
+            .get()
+            .and_then(|res| res.ok())
+            .and_then(|post| post.author_id);
+        authors_resource.get().and_then(|res| res.ok()).map(|authors| {
+            authors
+                .into_iter()
+                .map(|author| {
+                    let is_selected = selected.as_deref() == Some(author.id.as_str());
+                    view! { <option value=author.id selected=is_selected>{author.display_name}</option> }
+                })
+                .collect_view()
+        })
+    };
+
+    let no_access = move || match auth.current_user() {
+        None => view! {
+            <p class="min-w-[50%]"><a href="/login" class="hover:text-blue-400">"Log in"</a>" to write posts."</p>
+        },
+        Some(_) => view! { <p class="min-w-[50%]">"You can't edit this post."</p> },
+    };
+
     view! {
         <Transition fallback=move || view! { <p>"Loading..."</p> }>
             <ErrorBoundary fallback={error_fallback()}>
                 <div class="flex h-screen">
-                <div class="min-w-[50%] max-h-[90%] text-gray-200 dark:bg-gray-800 bg-gray-100 p-10 rounded-md">
+                <Show when=move || permitted(CurrentUser::can_edit_post) fallback=no_access>
+                <div class="min-w-[50%] max-h-[90%] text-gray-900 dark:text-gray-200 dark:bg-gray-800 bg-gray-100 p-10 rounded-md">
+                {draft_banner}
+                {review_panel}
+                {merge_dialog}
                 <ActionForm action=upsert_post>
+                    <CsrfField/>
                     <input type="hidden" name="id" prop:value={move || post_resource.get().and_then(|res| res.map(|post| post.id).ok())}/>
+                    <input type="hidden" name="version" prop:value={move || post_resource.get().and_then(|res| res.map(|post| post.version.to_string()).ok())}/>
                     <label class="block mb-4">
                         <span>Date</span>
                         <input class="mt-1 p-2 w-full" type="datetime-local" id="datetime" name="dt"
@@ -95,6 +495,7 @@ pub fn EditPost() -> impl IntoView {
                                         post.dt = utc_dt;
                                     }
                                 });
+                                dirty.set(true);
                             }
                             prop:value={move || {
                                 post_resource
@@ -103,6 +504,53 @@ pub fn EditPost() -> impl IntoView {
                             }}
                     />
                     </label>
+                    <Show when=can_assign_author fallback=|| ()>
+                    <label class="block mb-4">
+                    <span>Author</span>
+                    <select class="mt-1 p-2 w-full" id="author_id" name="author_id"
+                        on:change=move |ev| {
+                            let author_id = event_target_value(&ev);
+                            let author = authors_resource
+                                .get()
+                                .and_then(|res| res.ok())
+                                .and_then(|authors| authors.into_iter().find(|author| author.id == author_id));
+                            post_resource.update(|curr| {
+                                if let Some(Ok(post)) = curr {
+                                    // keep the preview's byline in step with the selection
+                                    post.author_id = author.as_ref().map(|author| author.id.clone());
+                                    post.author_handle = author.as_ref().map(|author| author.handle.clone());
+                                    post.author_name = author.as_ref().map(|author| author.display_name.clone());
+                                    post.author_avatar_url = author.as_ref().map(|author| author.avatar_url.clone());
+                                    post.author_avatar_src = author.as_ref().and_then(|author| author.avatar_src.clone());
+                                }
+                            });
+                            dirty.set(true);
+                        }>
+                        <option value="">"No author"</option>
+                        {author_options}
+                    </select>
+                    </label>
+                    </Show>
+                    <label class="block mb-4">
+                    <span>Status</span>
+                    <select class="mt-1 p-2 w-full" id="status" name="status"
+                        on:change=move |ev| {
+                            selected_status.set(PostStatus::parse(&event_target_value(&ev)));
+                        }
+                        prop:value={move || status_value().map(|status| status.as_str())}>
+                        {[
+                            PostStatus::Draft,
+                            PostStatus::PendingReview,
+                            PostStatus::ChangesRequested,
+                            PostStatus::Published,
+                        ]
+                            .into_iter()
+                            .map(|status| view! {
+                                <option value=status.as_str() disabled=move || !can_set_status(status)>{status.label()}</option>
+                            })
+                            .collect_view()}
+                    </select>
+                    </label>
                     <label class="block mb-4">
                     <span>Image URL</span>
                     <input class="mt-1 p-2 w-full" type="text" id="image_url" name="image_url"
@@ -112,6 +560,7 @@ pub fn EditPost() -> impl IntoView {
                                    post.image_url = event_target_value(&ev);
                                 }
                             });
+                            dirty.set(true);
                         }
                         prop:value={move || post_resource.get().and_then(|res| res.map(|post| post.image_url).ok())}/>
                     </label>
@@ -124,10 +573,24 @@ pub fn EditPost() -> impl IntoView {
                                    post.title = event_target_value(&ev);
                                 }
                             });
+                            dirty.set(true);
                         }
                             prop:value={move || post_resource.get().and_then(|res| res.map(|post| post.title).ok())}/>
                     </label>
                     <label class="block mb-4">
+                    <span>Tags</span>
+                    <input class="mt-1 p-2 w-full" type="text" id="tags" name="tags" placeholder="road trip, hiking"
+                        on:change=move |ev| {
+                            post_resource.update(|curr| {
+                                if let Some(Ok(post)) = curr {
+                                   post.tags = parse_tags(&event_target_value(&ev));
+                                }
+                            });
+                            dirty.set(true);
+                        }
+                        prop:value={move || post_resource.get().and_then(|res| res.map(|post| post.tags.join(", ")).ok())}/>
+                    </label>
+                    <label class="block mb-4">
                     <span>Entry</span>
                     <textarea class="mt-1 p-2 w-full" id="text" name="text"
                         on:input=move |ev| {
@@ -136,25 +599,74 @@ pub fn EditPost() -> impl IntoView {
                                    post.text = event_target_value(&ev);
                                 }
                             });
+                            dirty.set(true);
                         }
                         prop:value={move || post_resource.get().and_then(|res| res.map(|post| post.text).ok())}
                     />
                     </label>
+                    <label class="block mb-4">
+                    <span>Excerpt</span>
+                    <textarea class="mt-1 p-2 w-full" id="excerpt" name="excerpt" rows="2"
+                        placeholder="Leave empty to use the start of the post"
+                        on:input=move |ev| {
+                            post_resource.update(|curr| {
+                                if let Some(Ok(post)) = curr {
+                                   post.excerpt = Some(event_target_value(&ev));
+                                }
+                            });
+                            dirty.set(true);
+                        }
+                        prop:value={move || post_resource.get().and_then(|res| res.map(|post| post.excerpt.unwrap_or_default()).ok())}
+                    />
+                    </label>
                 <div class="flex justify-center pb-4">
                     <input type="submit" value="Submit" class="mx-auto w-1/3 bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
                 </div>
                 </ActionForm>
-                <ActionForm action=delete_post>
-                    <input type="hidden" name="id"
-                        prop:value={move || post_resource.get().and_then(|res| res.map(|post| post.id).ok())}/>
+                {delete_dialog}
+                <Show when=move || permitted(CurrentUser::can_delete_post) fallback=|| ()>
                 <div class="flex justify-center pb-4">
-                    <input type="submit" value="Delete Post" class="mx-auto w-1/3 bg-red-500 hover:bg-red-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+                    <button class="mx-auto w-1/3 bg-red-500 hover:bg-red-700 text-white font-bold py-2 px-4 rounded cursor-pointer"
+                        on:click=move |_| confirm_delete.set(true)>
+                        "Delete Post"
+                    </button>
                 </div>
-                </ActionForm>
+                </Show>
                 </div>
+                </Show>
                 // right side preview
                 <div>
-                    {move || post_resource.and_then( |post| view! {<BlogPost post=post.clone()/>})}
+                    {move || post_resource.and_then(|post| {
+                        // the text has changed since the server counted it
+                        let mut post = post.clone();
+                        post.update_reading_stats();
+                        let mut errors = Vec::new();
+                        match image_src_resource.get() {
+                            Some(Ok(src)) => post.image_src = src,
+                            Some(Err(e)) => errors.push(e),
+                            None => {}
+                        }
+                        // shown as text until the server has caught up with the edits
+                        post.paragraphs_html = match paragraphs_resource.get() {
+                            Some((text, Ok(html))) if text == post.text => html,
+                            Some((_, Err(e))) => {
+                                errors.push(e);
+                                Vec::new()
+                            }
+                            _ => Vec::new(),
+                        };
+                        let error = errors.first().map(|e| {
+                            let message = AppError::from_server_fn_error(e)
+                                .map(|e| e.to_string())
+                                .unwrap_or(e.to_string());
+                            view! {
+                                <p class="bg-red-100 text-red-900 p-2 rounded mb-2">
+                                    {format!("The preview may be out of date: {}", message)}
+                                </p>
+                            }
+                        });
+                        view! { {error} <BlogPost post=post/> }
+                    })}
                 </div>
                 </div>
             </ErrorBoundary>
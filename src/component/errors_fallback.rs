@@ -1,4 +1,7 @@
 use leptos::*;
+
+use crate::error::AppError;
+
 pub fn error_fallback() -> Box<dyn Fn(RwSignal<Errors>) -> View> {
     Box::new(|errors: RwSignal<Errors>| {
         view! {
@@ -7,7 +10,18 @@ pub fn error_fallback() -> Box<dyn Fn(RwSignal<Errors>) -> View> {
                 {
                     move || {errors.with(|errors| {
                         errors.iter()
-                                .map(|(_, e)| view! { <li>{e.to_string()}</li> })
+                                .map(|(_, e)| {
+                                    // show our own errors without the server function wrapping
+                                    let message = match AppError::from_error(e) {
+                                        Some(app_error) => app_error.to_string(),
+                                        None => e.to_string(),
+                                    };
+                                    // lets whoever reports the problem point at it in the logs
+                                    let request_id = AppError::request_id(e).map(|request_id| view! {
+                                        <div class="text-sm opacity-75">"Request ID: " {request_id}</div>
+                                    });
+                                    view! { <li>{message}{request_id}</li> }
+                                })
                             .collect_view()
                     })}
                 }
@@ -0,0 +1,49 @@
+//! Keeps a copy of the editor's unsaved changes in the browser's localStorage,
+//! so they survive a closed tab even when the server can't be reached.
+use cfg_if::cfg_if;
+
+use crate::model::draft::Draft;
+
+const STORAGE_PREFIX: &str = "hotblog.draft.";
+
+cfg_if! {
+if #[cfg(feature = "hydrate")] {
+
+    fn storage() -> Option<web_sys::Storage> {
+        leptos::window().local_storage().ok().flatten()
+    }
+
+    pub fn load(key: &str) -> Option<Draft> {
+        let json = storage()?
+            .get_item(&format!("{}{}", STORAGE_PREFIX, key))
+            .ok()
+            .flatten()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    pub fn store(draft: &Draft) {
+        if let (Some(storage), Ok(json)) = (storage(), serde_json::to_string(draft)) {
+            let _ = storage.set_item(&format!("{}{}", STORAGE_PREFIX, draft.post_id), &json);
+        }
+    }
+
+    pub fn clear(key: &str) {
+        if let Some(storage) = storage() {
+            let _ = storage.remove_item(&format!("{}{}", STORAGE_PREFIX, key));
+        }
+    }
+
+} else {
+
+    // there is no localStorage while rendering on the server
+
+    pub fn load(_key: &str) -> Option<Draft> {
+        None
+    }
+
+    pub fn store(_draft: &Draft) {}
+
+    pub fn clear(_key: &str) {}
+
+}
+}
@@ -0,0 +1,56 @@
+use leptos::*;
+
+use crate::model::blog_post::Post;
+
+#[component]
+fn PostVersion(heading: &'static str, post: Post) -> impl IntoView {
+    view! {
+        <div class="flex-1 min-w-0">
+            <div class="text-lg font-semibold pb-2">{heading}</div>
+            <div class="text-sm pb-2">{format!("{}", post.dt.format("%b %e, %Y %I:%M%P"))}</div>
+            <div class="text-sm pb-2 truncate">{post.image_url}</div>
+            <div class="text-xl pb-2">{post.title}</div>
+            <div class="whitespace-pre-wrap overflow-y-auto max-h-96">{post.text}</div>
+        </div>
+    }
+}
+
+/// Shown when a save was rejected because someone else changed the post in the meantime.
+#[component]
+pub fn MergeDialog(
+    mine: Post,
+    theirs: Post,
+    #[prop(into)] on_keep_mine: Callback<()>,
+    #[prop(into)] on_use_theirs: Callback<()>,
+    #[prop(into)] on_close: Callback<()>,
+) -> impl IntoView {
+    view! {
+        <div class="fixed inset-0 bg-black bg-opacity-50 flex items-center justify-center z-30">
+            <div class="dark:bg-gray-800 bg-gray-100 p-8 rounded-lg max-w-5xl w-full">
+                <div class="text-2xl pb-2">"This post was changed while you were editing it"</div>
+                <p class="pb-6">
+                    "Compare your version with the one that was saved, then choose which one to keep. "
+                    "You can also close this dialog and merge the changes by hand."
+                </p>
+                <div class="flex gap-8 pb-6">
+                    <PostVersion heading="Your version" post=mine/>
+                    <PostVersion heading="Saved version" post=theirs/>
+                </div>
+                <div class="flex justify-end gap-4">
+                    <button class="bg-gray-500 hover:bg-gray-700 text-white font-bold py-2 px-4 rounded"
+                        on:click=move |_| on_close.call(())>
+                        "Merge by hand"
+                    </button>
+                    <button class="bg-gray-500 hover:bg-gray-700 text-white font-bold py-2 px-4 rounded"
+                        on:click=move |_| on_use_theirs.call(())>
+                        "Use saved version"
+                    </button>
+                    <button class="bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded"
+                        on:click=move |_| on_keep_mine.call(())>
+                        "Keep my version"
+                    </button>
+                </div>
+            </div>
+        </div>
+    }
+}
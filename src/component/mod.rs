@@ -6,3 +6,20 @@ pub mod blog_previews;
 pub mod blog_preview_card;
 pub mod about;
 pub mod errors_fallback;
+pub mod merge_dialog;
+pub mod confirm_dialog;
+pub mod trash;
+pub mod theme;
+pub mod byline;
+pub mod author_page;
+pub mod authors;
+pub mod local_draft;
+pub mod auth;
+pub mod settings;
+pub mod users;
+pub mod drafts;
+pub mod review;
+pub mod table_of_contents;
+pub mod post_navigation;
+pub mod webhooks;
+pub mod newsletter;
@@ -0,0 +1,152 @@
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use super::auth::CsrfField;
+use crate::error::AppError;
+use crate::repository::newsletter_repository::get_newsletter_enabled;
+use crate::repository::newsletter_repository::ConfirmSubscription;
+use crate::repository::newsletter_repository::Subscribe;
+use crate::repository::newsletter_repository::Unsubscribe;
+
+#[derive(Params, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+struct NewsletterParams {
+    token: Option<String>,
+}
+
+/// What went wrong, for showing under a form.
+fn error_message(e: &ServerFnError) -> String {
+    AppError::from_server_fn_error(e)
+        .map(|e| e.to_string())
+        .unwrap_or(e.to_string())
+}
+
+/// The sign-up form on the home page, shown when the newsletter is set up.
+#[component]
+pub fn SubscribeForm() -> impl IntoView {
+    let enabled_resource = create_resource(
+        || (),
+        |_| async move { get_newsletter_enabled().await.unwrap_or(false) },
+    );
+    let subscribe = create_server_action::<Subscribe>();
+
+    let outcome = move || match subscribe.value().get() {
+        Some(Ok(())) => Some(view! {
+            <p class="pt-2">"Check your inbox: we've sent you a link to confirm your subscription."</p>
+        }),
+        Some(Err(e)) => Some(view! {
+            <p class="pt-2 text-red-600 dark:text-red-300">{error_message(&e)}</p>
+        }),
+        None => None,
+    };
+
+    let form = move || {
+        enabled_resource.get().unwrap_or(false).then(|| view! {
+            <div class="p-5 flex flex-col items-center">
+                <div class="pb-2 text-lg">"Get new posts by email"</div>
+                <ActionForm action=subscribe class="flex flex-wrap justify-center gap-2">
+                    <CsrfField/>
+                    <label>
+                        <span class="sr-only">"Email address"</span>
+                        <input class="p-2 w-64" type="email" name="email" placeholder="you@example.com" autocomplete="email" required/>
+                    </label>
+                    <input type="submit" value="Subscribe" class="bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+                </ActionForm>
+                {outcome}
+            </div>
+        })
+    };
+
+    view! { <Suspense fallback=|| ()>{form}</Suspense> }
+}
+
+/// Where the link in a confirmation email leads. Confirming takes a click, so link
+/// checkers that open every link in an email don't subscribe anyone.
+#[component]
+pub fn NewsletterConfirm() -> impl IntoView {
+    let params = use_params::<NewsletterParams>();
+    let token = move || {
+        params.with(|params| {
+            params
+                .as_ref()
+                .ok()
+                .and_then(|params| params.token.clone())
+                .unwrap_or_default()
+        })
+    };
+    let confirm = create_server_action::<ConfirmSubscription>();
+
+    let content = move || {
+        match confirm.value().get() {
+        Some(Ok(())) => view! {
+            <p>"You're subscribed. New posts will arrive in your inbox."</p>
+            <a href="/" class="hover:text-blue-400">"Back to the blog"</a>
+        }
+        .into_view(),
+        result => view! {
+            <p class="pb-4">"Confirm that you'd like new posts sent to your inbox."</p>
+            <ActionForm action=confirm>
+                <CsrfField/>
+                <input type="hidden" name="token" value=token/>
+                <input type="submit" value="Confirm my subscription" class="bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+            </ActionForm>
+            {result.and_then(Result::err).map(|e| view! {
+                <p class="pt-2 text-red-600 dark:text-red-300">{error_message(&e)}</p>
+            })}
+        }
+        .into_view(),
+    }
+    };
+
+    view! {
+        <div class="max-w-3xl mx-auto">
+            <div class="text-4xl pb-6">"Newsletter"</div>
+            {content}
+        </div>
+    }
+}
+
+/// Where the unsubscribe link at the bottom of every email leads.
+#[component]
+pub fn NewsletterUnsubscribe() -> impl IntoView {
+    let params = use_params::<NewsletterParams>();
+    let token = move || {
+        params.with(|params| {
+            params
+                .as_ref()
+                .ok()
+                .and_then(|params| params.token.clone())
+                .unwrap_or_default()
+        })
+    };
+    let unsubscribe = create_server_action::<Unsubscribe>();
+
+    let content = move || {
+        match unsubscribe.value().get() {
+        Some(Ok(())) => view! {
+            <p>"You're unsubscribed and won't get any more emails from us."</p>
+            <a href="/" class="hover:text-blue-400">"Back to the blog"</a>
+        }
+        .into_view(),
+        result => view! {
+            <p class="pb-4">"Stop getting new posts by email?"</p>
+            <ActionForm action=unsubscribe>
+                <CsrfField/>
+                <input type="hidden" name="token" value=token/>
+                <input type="submit" value="Unsubscribe" class="bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+            </ActionForm>
+            {result.and_then(Result::err).map(|e| view! {
+                <p class="pt-2 text-red-600 dark:text-red-300">{error_message(&e)}</p>
+            })}
+        }
+        .into_view(),
+    }
+    };
+
+    view! {
+        <div class="max-w-3xl mx-auto">
+            <div class="text-4xl pb-6">"Newsletter"</div>
+            {content}
+        </div>
+    }
+}
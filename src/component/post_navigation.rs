@@ -0,0 +1,82 @@
+use leptos::*;
+
+use super::blog_preview_card::BlogPreviewCard;
+use crate::model::blog_post::Post;
+use crate::model::blog_post::PostLink;
+use crate::repository::navigation_repository::get_adjacent_posts;
+use crate::repository::navigation_repository::get_related_posts;
+
+/// How many related posts to suggest.
+const RELATED_POSTS: u8 = 3;
+
+/// Links to the posts published just before and after this one.
+#[component]
+pub fn AdjacentPostLinks(#[prop(into)] post_id: Signal<String>) -> impl IntoView {
+    let adjacent_resource = create_resource(
+        move || post_id.get(),
+        |id| async move { get_adjacent_posts(id).await },
+    );
+
+    let link = |post: Option<PostLink>, label: &'static str, align: &'static str| {
+        post.map(|post| {
+            view! {
+                <a href=format!("/view/{}", post.id) class=format!("block hover:text-blue-400 {}", align)>
+                    <div class="text-sm text-gray-600 dark:text-gray-300">{label}</div>
+                    <div class="text-lg">{post.title}</div>
+                </a>
+            }
+        })
+    };
+
+    view! {
+        <Suspense fallback=|| ()>
+            {move || {
+                adjacent_resource
+                    .get()
+                    .and_then(|res| res.ok())
+                    .filter(|adjacent| adjacent.previous.is_some() || adjacent.next.is_some())
+                    .map(|adjacent| {
+                    view! {
+                        <nav aria-label="More posts" class="flex justify-between px-10 py-6 border-t border-gray-300 dark:border-gray-600">
+                            <div>{link(adjacent.previous, "← Previous", "text-left")}</div>
+                            <div>{link(adjacent.next, "Next →", "text-right")}</div>
+                        </nav>
+                    }
+                })
+            }}
+        </Suspense>
+    }
+}
+
+/// Posts sharing tags or subject matter with this one.
+#[component]
+pub fn RelatedPosts(#[prop(into)] post_id: Signal<String>) -> impl IntoView {
+    let related_resource = create_resource(
+        move || post_id.get(),
+        |id| async move { get_related_posts(id, 40, RELATED_POSTS).await },
+    );
+
+    view! {
+        <Suspense fallback=move || view! { <p class="px-10">"Loading related posts..."</p> }>
+            {move || {
+                related_resource
+                    .get()
+                    .and_then(|res| res.ok())
+                    .filter(|posts: &Vec<Post>| !posts.is_empty())
+                    .map(|posts| {
+                        view! {
+                            <section class="px-10 py-6">
+                                <h2 class="text-2xl pb-4">"Related posts"</h2>
+                                <div class="flex flex-wrap">
+                                    {posts
+                                        .into_iter()
+                                        .map(|post| view! { <BlogPreviewCard blog_preview=post/> })
+                                        .collect_view()}
+                                </div>
+                            </section>
+                        }
+                    })
+            }}
+        </Suspense>
+    }
+}
@@ -0,0 +1,370 @@
+use leptos::*;
+use leptos_router::*;
+use serde::{Deserialize, Serialize};
+
+use super::auth::use_auth;
+use super::auth::CsrfField;
+use super::byline::Byline;
+use super::errors_fallback::error_fallback;
+use super::toast::use_toasts;
+use super::toast::ToastMessage;
+use crate::error::AppError;
+use crate::model::blog_post::Post;
+use crate::model::review::ReviewNote;
+use crate::model::review::StatusTransition;
+use crate::repository::blog_repository::get_post;
+use crate::repository::review_repository::get_post_history;
+use crate::repository::review_repository::get_review_notes;
+use crate::repository::review_repository::get_review_queue;
+use crate::repository::review_repository::AddReviewNote;
+use crate::repository::review_repository::ApprovePost;
+use crate::repository::review_repository::RequestChanges;
+use crate::repository::review_repository::ResolveReviewNote;
+
+fn format_dt(datetime: chrono::NaiveDateTime) -> String {
+    format!("{}", datetime.format("%b %e, %Y %I:%M%P"))
+}
+
+fn error_message(e: &ServerFnError) -> String {
+    AppError::from_server_fn_error(e)
+        .map(|e| e.to_string())
+        .unwrap_or(e.to_string())
+}
+
+/// Posts waiting for a reviewer.
+#[component]
+pub fn ReviewQueue() -> impl IntoView {
+    let queue_resource = create_resource(|| (), |_| async move { get_review_queue().await });
+
+    let queue_view = move || {
+        queue_resource.and_then(|posts: &Vec<Post>| {
+            if posts.is_empty() {
+                return view! { <p>"Nothing is waiting for review."</p> }.into_view();
+            }
+            posts
+                .iter()
+                .map(|post| {
+                    view! {
+                        <a href=format!("/review/{}", post.id)
+                            class="block bg-gray-100 dark:bg-gray-600 hover:bg-gray-200 dark:hover:bg-gray-500 p-4 rounded-lg mb-4">
+                            <div class="text-xl font-semibold">{post.title.clone()}</div>
+                            <Byline handle=post.author_handle.clone() name=post.author_name.clone()
+                                avatar_src=post.author_avatar_src.clone() link=false/>
+                        </a>
+                    }
+                })
+                .collect_view()
+        })
+    };
+
+    view! {
+        <div class="max-w-3xl mx-auto">
+            <div class="text-4xl pb-6">"Review queue"</div>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback={error_fallback()}>
+                    {queue_view}
+                </ErrorBoundary>
+            </Transition>
+        </div>
+    }
+}
+
+#[component]
+fn NoteCard(
+    note: ReviewNote,
+    resolve_note: Action<ResolveReviewNote, Result<(), ServerFnError>>,
+    /// Show the paragraph the note was left on, for when it isn't displayed alongside.
+    #[prop(default = false)]
+    show_quote: bool,
+) -> impl IntoView {
+    let meta = format!(
+        "{} · {}",
+        note.author_name.clone().unwrap_or("Someone".to_string()),
+        format_dt(note.created_at)
+    );
+    let card_class = if note.resolved {
+        "border-l-4 border-gray-400 bg-gray-50 dark:bg-gray-800 opacity-60 p-3 rounded mb-2"
+    } else {
+        "border-l-4 border-yellow-500 bg-yellow-50 dark:bg-gray-800 p-3 rounded mb-2"
+    };
+
+    view! {
+        <div class=card_class>
+            {show_quote.then(|| view! {
+                <blockquote class="italic text-gray-600 dark:text-gray-300 truncate pb-1">{note.quote.clone()}</blockquote>
+            })}
+            <div class="whitespace-pre-wrap">{note.body}</div>
+            <div class="flex items-center justify-between text-sm text-gray-600 dark:text-gray-300 pt-1">
+                <span>{meta}</span>
+                {(!note.resolved).then(|| view! {
+                    <ActionForm action=resolve_note>
+                        <CsrfField/>
+                        <input type="hidden" name="id" value=note.id.to_string()/>
+                        <input type="submit" value="Resolve" class="hover:text-blue-400 cursor-pointer bg-transparent"/>
+                    </ActionForm>
+                })}
+            </div>
+        </div>
+    }
+}
+
+/// Unresolved review notes, for the writer working through them in the editor.
+#[component]
+pub fn ReviewNotes(post_id: String) -> impl IntoView {
+    let resolve_note = create_server_action::<ResolveReviewNote>();
+    let notes_resource = create_resource(
+        move || resolve_note.version().get(),
+        move |_| {
+            let post_id = post_id.clone();
+            async move { get_review_notes(post_id).await }
+        },
+    );
+
+    let notes_view = move || {
+        notes_resource.get().and_then(|res| res.ok()).map(|notes| {
+            let open: Vec<ReviewNote> = notes.into_iter().filter(|note| !note.resolved).collect();
+            (!open.is_empty()).then(|| {
+                view! {
+                    <div class="pb-4">
+                        <div class="text-xl pb-2">"Review notes"</div>
+                        {open
+                            .into_iter()
+                            .map(|note| view! { <NoteCard note=note resolve_note=resolve_note show_quote=true/> })
+                            .collect_view()}
+                    </div>
+                }
+            })
+        })
+    };
+
+    view! { <Transition fallback=|| ()>{notes_view}</Transition> }
+}
+
+/// Who moved a post between statuses, and when.
+#[component]
+pub fn PostHistory(
+    post_id: String,
+    /// Reload whenever this changes.
+    #[prop(into)]
+    version: Signal<usize>,
+) -> impl IntoView {
+    let history_resource = create_resource(
+        move || version.get(),
+        move |_| {
+            let post_id = post_id.clone();
+            async move { get_post_history(post_id).await }
+        },
+    );
+
+    let history_view = move || {
+        history_resource
+            .get()
+            .and_then(|res| res.ok())
+            .map(|history| {
+                history
+                    .into_iter()
+                    .map(|transition: StatusTransition| {
+                        let change = match transition.from_status {
+                            Some(from) => {
+                                format!("{} → {}", from.label(), transition.to_status.label())
+                            }
+                            None => format!(
+                                "Created as {}",
+                                transition.to_status.label().to_lowercase()
+                            ),
+                        };
+                        let meta = format!(
+                            "{} · {}",
+                            transition.actor_name.unwrap_or("Someone".to_string()),
+                            format_dt(transition.created_at)
+                        );
+                        view! {
+                            <li class="pb-2">
+                                <div>{change}</div>
+                                <div class="text-sm text-gray-600 dark:text-gray-300">{meta}</div>
+                                {(!transition.note.is_empty()).then(|| view! {
+                                    <div class="text-sm whitespace-pre-wrap">{transition.note}</div>
+                                })}
+                            </li>
+                        }
+                    })
+                    .collect_view()
+            })
+    };
+
+    view! {
+        <div class="text-xl pb-2">"History"</div>
+        <Transition fallback=|| ()>
+            <ul>{history_view}</ul>
+        </Transition>
+    }
+}
+
+#[derive(Params, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+struct ReviewPostParams {
+    post_id: Option<String>,
+}
+
+/// A post pending review, with notes inline next to each paragraph and the reviewer's decision.
+#[component]
+pub fn ReviewPost() -> impl IntoView {
+    let params = use_params::<ReviewPostParams>();
+    let post_id = move || {
+        params.with(|params| {
+            params
+                .as_ref()
+                .ok()
+                .and_then(|params| params.post_id.clone())
+                .unwrap_or_default()
+        })
+    };
+
+    let add_note = create_server_action::<AddReviewNote>();
+    let resolve_note = create_server_action::<ResolveReviewNote>();
+    let approve = create_server_action::<ApprovePost>();
+    let request_changes = create_server_action::<RequestChanges>();
+
+    let post_resource = create_resource(post_id, |id| async move { get_post(id).await });
+    let notes_resource = create_resource(
+        move || {
+            (
+                post_id(),
+                add_note.version().get(),
+                resolve_note.version().get(),
+            )
+        },
+        |(id, _, _)| async move { get_review_notes(id).await },
+    );
+    let decisions =
+        Signal::derive(move || approve.version().get() + request_changes.version().get());
+
+    let toasts = use_toasts();
+    create_effect(move |_| {
+        if let Some(Err(e)) = add_note.value().get() {
+            toasts.show(ToastMessage::error(error_message(&e)));
+        }
+    });
+    let conclude =
+        move |result: Option<Result<(), ServerFnError>>, success: &'static str| match result {
+            Some(Ok(())) => {
+                toasts.show(ToastMessage::success(success));
+                let navigate = use_navigate();
+                navigate("/review", Default::default());
+            }
+            Some(Err(e)) => {
+                toasts.show(ToastMessage::error(error_message(&e)));
+            }
+            None => {}
+        };
+    create_effect(move |_| conclude(approve.value().get(), "Post approved and published."));
+    create_effect(move |_| conclude(request_changes.value().get(), "Changes requested."));
+
+    let auth = use_auth();
+
+    let paragraphs_view = move || {
+        let post = post_resource.get()?.ok()?;
+        let notes = notes_resource
+            .get()
+            .and_then(|res| res.ok())
+            .unwrap_or_default();
+        let can_review = auth
+            .current_user()
+            .map_or(false, |user| user.can_review_post(&post));
+        let paragraphs = post.paragraphs();
+        let paragraph_count = paragraphs.len();
+
+        let paragraphs = paragraphs
+            .into_iter()
+            .enumerate()
+            .map(|(index, paragraph)| {
+                let index = index as i64;
+                let paragraph_notes = notes
+                    .iter()
+                    .filter(|note| note.paragraph == index)
+                    .cloned()
+                    .map(|note| view! { <NoteCard note=note resolve_note=resolve_note/> })
+                    .collect_view();
+                let post_id = post.id.clone();
+                view! {
+                    <div class="grid grid-cols-2 gap-6 pb-4">
+                        <p class="whitespace-pre-wrap">{paragraph}</p>
+                        <div>
+                            {paragraph_notes}
+                            {can_review.then(|| view! {
+                                <ActionForm action=add_note>
+                                    <CsrfField/>
+                                    <input type="hidden" name="post_id" value=post_id/>
+                                    <input type="hidden" name="paragraph" value=index.to_string()/>
+                                    <label>
+                                        <span class="sr-only">"Note on this paragraph"</span>
+                                        <textarea class="p-2 w-full" name="body" rows="2" placeholder="Add a note"></textarea>
+                                    </label>
+                                    <input type="submit" value="Add note" class="text-sm hover:text-blue-400 cursor-pointer bg-transparent"/>
+                                </ActionForm>
+                            })}
+                        </div>
+                    </div>
+                }
+            })
+            .collect_view();
+
+        // notes left on paragraphs that have since been removed
+        let orphaned = notes
+            .into_iter()
+            .filter(|note| note.paragraph < 0 || note.paragraph as usize >= paragraph_count)
+            .map(|note| view! { <NoteCard note=note resolve_note=resolve_note show_quote=true/> })
+            .collect_view();
+
+        let decision = can_review.then(|| {
+            let approve_id = post.id.clone();
+            let changes_id = post.id.clone();
+            view! {
+                <div class="grid grid-cols-2 gap-6 bg-gray-100 dark:bg-gray-800 p-6 rounded-md mt-6">
+                    <ActionForm action=approve>
+                        <CsrfField/>
+                        <input type="hidden" name="id" value=approve_id/>
+                        <label class="block mb-2">
+                            <span>"Note for the writer (optional)"</span>
+                            <textarea class="mt-1 p-2 w-full" name="note" rows="2"></textarea>
+                        </label>
+                        <input type="submit" value="Approve and publish" class="bg-green-600 hover:bg-green-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+                    </ActionForm>
+                    <ActionForm action=request_changes>
+                        <CsrfField/>
+                        <input type="hidden" name="id" value=changes_id/>
+                        <label class="block mb-2">
+                            <span>"What needs to change"</span>
+                            <textarea class="mt-1 p-2 w-full" name="note" rows="2"></textarea>
+                        </label>
+                        <input type="submit" value="Request changes" class="bg-yellow-600 hover:bg-yellow-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+                    </ActionForm>
+                </div>
+            }
+        });
+
+        Some(view! {
+            <div class="text-sm text-gray-600 dark:text-gray-300">{post.status.label()}</div>
+            <div class="text-4xl pb-2">{post.title.clone()}</div>
+            <div class="pb-6">
+                <Byline handle=post.author_handle.clone() name=post.author_name.clone() avatar_src=post.author_avatar_src.clone()/>
+            </div>
+            {paragraphs}
+            {orphaned}
+            {decision}
+        })
+    };
+
+    view! {
+        <div class="max-w-5xl mx-auto">
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback={error_fallback()}>
+                    {move || post_resource.get().map(|res| res.map(|_| ()))}
+                    {paragraphs_view}
+                </ErrorBoundary>
+            </Transition>
+            <div class="pt-8">
+                {move || view! { <PostHistory post_id=post_id() version=decisions/> }}
+            </div>
+        </div>
+    }
+}
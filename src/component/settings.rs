@@ -0,0 +1,94 @@
+use leptos::*;
+
+use super::auth::use_auth;
+use super::auth::CsrfField;
+use super::toast::use_toasts;
+use super::toast::ToastMessage;
+use crate::error::AppError;
+use crate::model::setting::Settings;
+use crate::repository::settings_repository::get_settings;
+use crate::repository::settings_repository::UpdateSettings;
+
+/// Blog-wide settings, reloaded whenever an admin saves them. Available in context.
+#[derive(Clone, Copy)]
+pub struct SettingsContext {
+    pub update: Action<UpdateSettings, Result<(), ServerFnError>>,
+    pub settings: Resource<usize, Settings>,
+}
+
+impl SettingsContext {
+    /// The current settings, or the defaults while they are loading.
+    pub fn get(&self) -> Settings {
+        self.settings.get().unwrap_or_default()
+    }
+}
+
+pub fn provide_settings() -> SettingsContext {
+    let update = create_server_action::<UpdateSettings>();
+    let settings = create_resource(
+        move || update.version().get(),
+        |_| async move { get_settings().await.unwrap_or_default() },
+    );
+
+    let context = SettingsContext { update, settings };
+    provide_context(context);
+    context
+}
+
+pub fn use_settings() -> SettingsContext {
+    expect_context::<SettingsContext>()
+}
+
+#[component]
+pub fn AdminSettings() -> impl IntoView {
+    let auth = use_auth();
+    let settings = use_settings();
+
+    let toasts = use_toasts();
+    create_effect(move |_| match settings.update.value().get() {
+        Some(Ok(())) => {
+            toasts.show(ToastMessage::success("Settings saved."));
+        }
+        Some(Err(e)) => {
+            let message = AppError::from_server_fn_error(&e)
+                .map(|e| e.to_string())
+                .unwrap_or(e.to_string());
+            toasts.show(ToastMessage::error(message));
+        }
+        None => {}
+    });
+
+    let settings_form = move || {
+        let can_manage_settings = auth
+            .current_user()
+            .map_or(false, |user| user.can_manage_settings());
+        if !can_manage_settings {
+            return view! { <p>"Only admins can change the blog's settings."</p> }.into_view();
+        }
+        let current = settings.get();
+        view! {
+            <ActionForm action=settings.update>
+                <CsrfField/>
+                <label class="block mb-4">
+                    <span>"Blog title"</span>
+                    <input class="mt-1 p-2 w-full" type="text" name="blog_title" value=current.blog_title/>
+                </label>
+                <label class="block mb-4">
+                    <span>"Tagline"</span>
+                    <input class="mt-1 p-2 w-full" type="text" name="blog_tagline" value=current.blog_tagline/>
+                </label>
+                <input type="submit" value="Save" class="bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+            </ActionForm>
+        }
+        .into_view()
+    };
+
+    view! {
+        <div class="max-w-3xl mx-auto">
+            <div class="text-4xl pb-6">"Settings"</div>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                {settings_form}
+            </Transition>
+        </div>
+    }
+}
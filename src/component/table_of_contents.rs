@@ -0,0 +1,76 @@
+use leptos::*;
+
+use crate::model::post_body::Heading;
+
+/// How far below the top of the viewport a heading may be and still count as the current section.
+const SECTION_OFFSET: f64 = 80.0;
+
+/// The heading of the section being read: the last one scrolled up to near the top of the screen.
+fn current_anchor(anchors: &[String]) -> Option<String> {
+    anchors
+        .iter()
+        .rev()
+        .find(|anchor| {
+            document()
+                .get_element_by_id(anchor)
+                .map_or(false, |heading| {
+                    heading.get_bounding_client_rect().top() <= SECTION_OFFSET
+                })
+        })
+        .or(anchors.first())
+        .cloned()
+}
+
+/// Links to each heading of a post, highlighting the section currently on screen.
+#[component]
+pub fn TableOfContents(headings: Vec<Heading>) -> impl IntoView {
+    let active = create_rw_signal::<Option<String>>(None);
+    let anchors: Vec<String> = headings
+        .iter()
+        .map(|heading| heading.anchor.clone())
+        .collect();
+
+    // effects only run in the browser, where there is something to scroll
+    create_effect(move |_| {
+        let anchors = anchors.clone();
+        active.set(current_anchor(&anchors));
+        let handle = window_event_listener(ev::scroll, move |_| {
+            let current = current_anchor(&anchors);
+            if active.get_untracked() != current {
+                active.set(current);
+            }
+        });
+        on_cleanup(move || handle.remove());
+    });
+
+    let links = headings
+        .into_iter()
+        .map(|heading| {
+            let anchor = heading.anchor.clone();
+            let is_active = move || active.get().as_deref() == Some(anchor.as_str());
+            let indent = match heading.level {
+                1 => "",
+                2 => "pl-4",
+                _ => "pl-8",
+            };
+            view! {
+                <li class=indent>
+                    <a href=format!("#{}", heading.anchor)
+                        class="block py-1 hover:text-blue-400"
+                        class:text-blue-500=is_active.clone()
+                        class:font-bold=is_active.clone()
+                        aria-current=move || is_active().then_some("location")>
+                        {heading.text}
+                    </a>
+                </li>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <nav aria-label="Table of contents" class="sticky top-4 text-sm">
+            <div class="font-semibold pb-2">"Contents"</div>
+            <ul>{links}</ul>
+        </nav>
+    }
+}
@@ -0,0 +1,107 @@
+use leptos::*;
+
+/// Name of the cookie holding the reader's theme choice, so the server can render it without
+/// a flash.
+pub const THEME_COOKIE: &str = "theme";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Follow the operating system's preference.
+    System,
+}
+
+impl Theme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::System => "system",
+        }
+    }
+
+    pub fn parse(value: &str) -> Theme {
+        match value {
+            "light" => Theme::Light,
+            "dark" => Theme::Dark,
+            _ => Theme::System,
+        }
+    }
+
+    /// Class for the `<html>` element. Without one, tailwind falls back to `prefers-color-scheme`.
+    pub fn html_class(&self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::System => "",
+        }
+    }
+}
+
+/// The theme this page was rendered with: the cookie on the server, the `<html>` class in the browser.
+fn initial_theme() -> Theme {
+    #[cfg(feature = "ssr")]
+    {
+        use_context::<actix_web::HttpRequest>()
+            .and_then(|req| req.cookie(THEME_COOKIE))
+            .map(|cookie| Theme::parse(cookie.value()))
+            .unwrap_or(Theme::System)
+    }
+    #[cfg(not(feature = "ssr"))]
+    {
+        document()
+            .document_element()
+            .map(|html| Theme::parse(&html.class_name()))
+            .unwrap_or(Theme::System)
+    }
+}
+
+fn persist_theme(theme: Theme) {
+    #[cfg(feature = "hydrate")]
+    {
+        use wasm_bindgen::JsCast;
+
+        let cookie = format!(
+            "{}={}; path=/; max-age=31536000; samesite=lax",
+            THEME_COOKIE,
+            theme.as_str()
+        );
+        let _ = document()
+            .unchecked_into::<web_sys::HtmlDocument>()
+            .set_cookie(&cookie);
+    }
+    #[cfg(not(feature = "hydrate"))]
+    {
+        let _ = theme;
+    }
+}
+
+/// Provides the current theme in context.
+pub fn provide_theme() -> RwSignal<Theme> {
+    let theme = create_rw_signal(initial_theme());
+    provide_context(theme);
+    theme
+}
+
+#[component]
+pub fn ThemeSwitcher() -> impl IntoView {
+    let theme = expect_context::<RwSignal<Theme>>();
+
+    view! {
+        <label class="flex items-center space-x-2">
+            <span class="sr-only">"Theme"</span>
+            <select class="bg-transparent border border-gray-400 rounded px-1"
+                on:change=move |ev| {
+                    let selected = Theme::parse(&event_target_value(&ev));
+                    persist_theme(selected);
+                    theme.set(selected);
+                }
+                prop:value=move || theme.get().as_str()>
+                <option value="system">"System"</option>
+                <option value="light">"Light"</option>
+                <option value="dark">"Dark"</option>
+            </select>
+        </label>
+    }
+}
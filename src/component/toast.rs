@@ -1,64 +1,181 @@
+use std::rc::Rc;
 use std::time::Duration;
 
 use leptos::*;
 
-#[derive(Clone)]
+/// How long a toast stays up unless it says otherwise.
+const DEFAULT_TOAST_DURATION: Duration = Duration::from_secs(4);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ToastType {
     Success,
+    Info,
+    Warning,
     Error,
 }
 
+/// A button shown next to the toast message, e.g. "Undo" or "View post".
+///
+/// The handler may run after the component that created it is gone,
+/// so it shouldn't rely on that component's signals or actions.
+#[derive(Clone)]
+pub struct ToastAction {
+    pub label: String,
+    pub on_click: Rc<dyn Fn()>,
+}
+
 #[derive(Clone)]
 pub struct ToastMessage {
     pub message: String,
     pub toast_type: ToastType,
-    pub visible: bool,
+    /// How long the toast stays up; `None` keeps it until it is dismissed.
+    pub duration: Option<Duration>,
+    pub actions: Vec<ToastAction>,
+}
+
+impl ToastMessage {
+    pub fn new(toast_type: ToastType, message: impl Into<String>) -> ToastMessage {
+        ToastMessage {
+            message: message.into(),
+            toast_type,
+            duration: Some(DEFAULT_TOAST_DURATION),
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn success(message: impl Into<String>) -> ToastMessage {
+        Self::new(ToastType::Success, message)
+    }
+
+    pub fn info(message: impl Into<String>) -> ToastMessage {
+        Self::new(ToastType::Info, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> ToastMessage {
+        Self::new(ToastType::Warning, message)
+    }
+
+    pub fn error(message: impl Into<String>) -> ToastMessage {
+        Self::new(ToastType::Error, message)
+    }
+
+    pub fn with_duration(mut self, duration: Option<Duration>) -> ToastMessage {
+        self.duration = duration;
+        self
+    }
+
+    pub fn with_action(mut self, label: impl Into<String>, on_click: impl Fn() + 'static) -> ToastMessage {
+        self.actions.push(ToastAction {
+            label: label.into(),
+            on_click: Rc::new(on_click),
+        });
+        self
+    }
+}
+
+#[derive(Clone)]
+struct QueuedToast {
+    id: u64,
+    message: ToastMessage,
+}
+
+/// Queues toasts for the [`Toast`] component to display. Available in context.
+#[derive(Clone, Copy)]
+pub struct ToastService {
+    queue: RwSignal<Vec<QueuedToast>>,
+    next_id: StoredValue<u64>,
+}
+
+impl ToastService {
+    fn new() -> ToastService {
+        ToastService {
+            queue: create_rw_signal(Vec::new()),
+            next_id: store_value(0),
+        }
+    }
+
+    /// Shows a toast and returns an id that can be passed to [`ToastService::dismiss`].
+    pub fn show(&self, message: ToastMessage) -> u64 {
+        let id = self.next_id.get_value();
+        self.next_id.set_value(id + 1);
+
+        let duration = message.duration;
+        self.queue.update(|queue| queue.push(QueuedToast { id, message }));
+
+        if let Some(duration) = duration {
+            let service = *self;
+            set_timeout(move || service.dismiss(id), duration);
+        }
+        id
+    }
+
+    pub fn dismiss(&self, id: u64) {
+        self.queue.update(|queue| queue.retain(|toast| toast.id != id));
+    }
+}
+
+pub fn use_toasts() -> ToastService {
+    expect_context::<ToastService>()
 }
 
 #[component]
-pub fn Toast() -> impl IntoView {
-    let (toast, set_toast) = create_signal::<ToastMessage>(ToastMessage {
-        message: String::new(),
-        toast_type: ToastType::Success,
-        visible: false,
-    });
-    provide_context::<WriteSignal<ToastMessage>>(set_toast);
-
-    let base_toast_classes = "fixed bottom-10 left-1/2 transform -translate-x-1/2 text-white px-4 py-2 rounded shadow-lg transition-opacity duration-600 z-40";
-
-    let toast_classes = move || -> String {
-        let t = toast.get();
-        let background_class = match t.toast_type {
-            ToastType::Success => "bg-green-600",
-            ToastType::Error => "bg-red-600",
-        };
-
-        let opacity_class = if t.visible == true {
-            "opacity-1".to_string()
-        } else {
-            "opacity-0".to_string()
-        };
-
-        format!("{} {} {}", base_toast_classes, background_class, opacity_class)
+fn ToastCard(toast: QueuedToast, service: ToastService) -> impl IntoView {
+    let id = toast.id;
+    let background_class = match toast.message.toast_type {
+        ToastType::Success => "bg-green-600",
+        ToastType::Info => "bg-blue-600",
+        ToastType::Warning => "bg-yellow-600",
+        ToastType::Error => "bg-red-600",
+    };
+    // errors and warnings interrupt the screen reader, everything else waits its turn
+    let role = match toast.message.toast_type {
+        ToastType::Error | ToastType::Warning => "alert",
+        ToastType::Success | ToastType::Info => "status",
     };
 
-    create_effect(move |_| {
-        let t = toast.get();
-        if t.visible {
-            set_timeout(
-                move || {
-                    set_toast.update(|msg| {
-                        msg.visible = false;
-                    });
-                },
-                Duration::new(4, 0),
-            )
-        }
-    });
+    let actions = toast
+        .message
+        .actions
+        .into_iter()
+        .map(|action| {
+            let on_click = action.on_click.clone();
+            view! {
+                <button class="ml-4 font-bold underline"
+                    on:click=move |_| {
+                        on_click();
+                        service.dismiss(id);
+                    }>
+                    {action.label}
+                </button>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <div role=role class=format!("flex items-center text-white px-4 py-2 rounded shadow-lg mt-2 {}", background_class)>
+            <span>{toast.message.message}</span>
+            {actions}
+            <button class="ml-4 font-bold" aria-label="Dismiss notification"
+                on:click=move |_| service.dismiss(id)>
+                "×"
+            </button>
+        </div>
+    }
+}
+
+#[component]
+pub fn Toast() -> impl IntoView {
+    let service = ToastService::new();
+    provide_context::<ToastService>(service);
 
     view! {
-        <div id="toast" class={toast_classes}>
-            {move || toast.get().message}
+        <div id="toast" aria-live="polite"
+            class="fixed bottom-10 left-1/2 transform -translate-x-1/2 flex flex-col items-center z-40">
+            <For
+                each=move || service.queue.get()
+                key=|toast| toast.id
+                children=move |toast| view! { <ToastCard toast=toast service=service/> }
+            />
         </div>
     }
 }
@@ -0,0 +1,136 @@
+use leptos::*;
+use leptos_router::*;
+
+use super::auth::CsrfField;
+use super::confirm_dialog::ConfirmDialog;
+use super::errors_fallback::error_fallback;
+use super::toast::use_toasts;
+use super::toast::ToastMessage;
+use crate::model::blog_post::Post;
+use crate::repository::blog_repository::get_deleted_posts;
+use crate::repository::blog_repository::PurgePost;
+use crate::repository::blog_repository::RestorePost;
+
+#[component]
+fn TrashedPost(
+    post: Post,
+    restore_post: Action<RestorePost, Result<String, ServerFnError>>,
+    purge_post: Action<PurgePost, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let confirm_purge = create_rw_signal(false);
+    let deleted_at = post
+        .deleted_at
+        .map(|dt| format!("{}", dt.format("%b %e, %Y %I:%M%P")))
+        .unwrap_or_default();
+    let id = post.id.clone();
+
+    let purge_dialog = move || {
+        let id = id.clone();
+        confirm_purge.get().then(|| {
+            view! {
+                <ConfirmDialog
+                    title="Delete this post for good?"
+                    message="This can't be undone."
+                    on_cancel=move |_: ()| confirm_purge.set(false)>
+                    <ActionForm action=purge_post>
+                        <CsrfField/>
+                        <input type="hidden" name="id" value=id/>
+                        <input type="submit" value="Delete forever" class="bg-red-500 hover:bg-red-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+                    </ActionForm>
+                </ConfirmDialog>
+            }
+        })
+    };
+
+    view! {
+        <div class="flex items-center justify-between bg-gray-100 dark:bg-gray-600 p-4 rounded-lg mb-4">
+            <div>
+                <div class="text-xl font-semibold">{post.title}</div>
+                <div class="text-gray-600 dark:text-gray-200 text-sm">{format!("Deleted {}", deleted_at)}</div>
+            </div>
+            <div class="flex items-center space-x-2">
+                <ActionForm action=restore_post>
+                    <CsrfField/>
+                    <input type="hidden" name="id" value=post.id/>
+                    <input type="submit" value="Restore" class="bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+                </ActionForm>
+                <button class="bg-red-500 hover:bg-red-700 text-white font-bold py-2 px-4 rounded"
+                    on:click=move |_| confirm_purge.set(true)>
+                    "Delete forever"
+                </button>
+            </div>
+            {purge_dialog}
+        </div>
+    }
+}
+
+#[component]
+pub fn Trash() -> impl IntoView {
+    let restore_post = create_server_action::<RestorePost>();
+    let purge_post = create_server_action::<PurgePost>();
+
+    let toasts = use_toasts();
+    create_effect(move |_| match restore_post.value().get() {
+        Some(Ok(id)) => {
+            let navigate = use_navigate();
+            toasts.show(
+                ToastMessage::success("Post restored.").with_action("View post", move || {
+                    navigate(format!("/view/{}", id).as_str(), Default::default())
+                }),
+            );
+        }
+        Some(Err(e)) => {
+            toasts.show(ToastMessage::error(format!(
+                "Couldn't restore the post: {}",
+                e
+            )));
+        }
+        None => {}
+    });
+    create_effect(move |_| match purge_post.value().get() {
+        Some(Ok(())) => {
+            toasts.show(ToastMessage::info("Post deleted for good."));
+        }
+        Some(Err(e)) => {
+            toasts.show(ToastMessage::error(format!(
+                "Couldn't delete the post: {}",
+                e
+            )));
+        }
+        None => {}
+    });
+
+    // reload the list whenever a post is restored or purged
+    let trash_resource = create_resource(
+        move || (restore_post.version().get(), purge_post.version().get()),
+        |_| async move { get_deleted_posts().await },
+    );
+
+    let trash_view = move || {
+        trash_resource.and_then(|posts: &Vec<Post>| {
+            if posts.is_empty() {
+                return view! { <p>"The trash is empty."</p> }.into_view();
+            }
+            posts
+                .iter()
+                .map(|post| {
+                    view! {
+                        <TrashedPost post={post.clone()} restore_post=restore_post purge_post=purge_post/>
+                    }
+                })
+                .collect_view()
+        })
+    };
+
+    view! {
+        <div class="max-w-3xl mx-auto">
+            <div class="text-4xl pb-2">"Trash"</div>
+            <p class="pb-6">"Deleted posts are kept here for a while before they are purged for good."</p>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback={error_fallback()}>
+                    {trash_view}
+                </ErrorBoundary>
+            </Transition>
+        </div>
+    }
+}
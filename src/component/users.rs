@@ -0,0 +1,117 @@
+use leptos::*;
+
+use super::auth::use_auth;
+use super::auth::CsrfField;
+use super::errors_fallback::error_fallback;
+use super::toast::use_toasts;
+use super::toast::ToastMessage;
+use crate::error::AppError;
+use crate::model::user::Role;
+use crate::model::user::UserSummary;
+use crate::repository::user_repository::get_users;
+use crate::repository::user_repository::SetUserPassword;
+use crate::repository::user_repository::SetUserRole;
+
+#[component]
+fn UserRow(
+    user: UserSummary,
+    set_role: Action<SetUserRole, Result<(), ServerFnError>>,
+    set_password: Action<SetUserPassword, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let current_role = user.role;
+    let role_options = Role::ALL
+        .into_iter()
+        .map(|role| {
+            view! { <option value=role.as_str() selected={role == current_role}>{role.label()}</option> }
+        })
+        .collect_view();
+
+    view! {
+        <div class="bg-gray-100 dark:bg-gray-600 p-4 rounded-lg mb-4">
+            <div class="text-xl font-semibold">{user.display_name}</div>
+            <div class="text-gray-600 dark:text-gray-200 text-sm pb-2">
+                {format!("@{}", user.handle)}
+                {(!user.has_password).then_some(" · can't log in yet")}
+            </div>
+            <div class="flex flex-wrap items-end gap-4">
+                <ActionForm action=set_role>
+                    <CsrfField/>
+                    <input type="hidden" name="id" value=user.id.clone()/>
+                    <label>
+                        <span class="sr-only">"Role"</span>
+                        <select class="p-2" name="role">{role_options}</select>
+                    </label>
+                    <input type="submit" value="Set role" class="ml-2 bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+                </ActionForm>
+                <ActionForm action=set_password>
+                    <CsrfField/>
+                    <input type="hidden" name="id" value=user.id/>
+                    <label>
+                        <span class="sr-only">"New password"</span>
+                        <input class="p-2" type="password" name="password" placeholder="New password" autocomplete="new-password"/>
+                    </label>
+                    <input type="submit" value="Set password" class="ml-2 bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+                </ActionForm>
+            </div>
+        </div>
+    }
+}
+
+/// Lets admins change users' roles and passwords. New users are added on the authors page.
+#[component]
+pub fn AdminUsers() -> impl IntoView {
+    let auth = use_auth();
+    let set_role = create_server_action::<SetUserRole>();
+    let set_password = create_server_action::<SetUserPassword>();
+
+    let users_resource = create_resource(
+        move || {
+            (
+                set_role.version().get(),
+                set_password.version().get(),
+                auth.user.get(),
+            )
+        },
+        |_| async move { get_users().await },
+    );
+
+    let toasts = use_toasts();
+    let show_result =
+        move |result: Option<Result<(), ServerFnError>>, success: &'static str| match result {
+            Some(Ok(())) => {
+                toasts.show(ToastMessage::success(success));
+            }
+            Some(Err(e)) => {
+                let message = AppError::from_server_fn_error(&e)
+                    .map(|e| e.to_string())
+                    .unwrap_or(e.to_string());
+                toasts.show(ToastMessage::error(message));
+            }
+            None => {}
+        };
+    create_effect(move |_| show_result(set_role.value().get(), "Role updated."));
+    create_effect(move |_| show_result(set_password.value().get(), "Password updated."));
+
+    let users_view = move || {
+        users_resource.and_then(|users: &Vec<UserSummary>| {
+            users
+                .iter()
+                .map(|user| {
+                    view! { <UserRow user={user.clone()} set_role=set_role set_password=set_password/> }
+                })
+                .collect_view()
+        })
+    };
+
+    view! {
+        <div class="max-w-3xl mx-auto">
+            <div class="text-4xl pb-2">"Users"</div>
+            <p class="pb-6">"Contributors submit drafts for review, authors publish their own posts, editors edit and review everyone's posts, and admins manage users and settings."</p>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback={error_fallback()}>
+                    {users_view}
+                </ErrorBoundary>
+            </Transition>
+        </div>
+    }
+}
@@ -3,7 +3,13 @@ use leptos::*;
 use leptos_router::*;
 use serde::{Deserialize, Serialize};
 use crate::model::blog_post::Post;
+use crate::app::NotFound;
+use crate::component::auth::use_auth;
 use crate::component::blog_post::BlogPost;
+use crate::component::post_navigation::{AdjacentPostLinks, RelatedPosts};
+use crate::component::table_of_contents::TableOfContents;
+use crate::error::AppError;
+use crate::model::post_body::headings;
 use crate::repository::blog_repository::get_post;
 
 #[derive(Params, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
@@ -14,30 +20,64 @@ struct ViewPostParams {
 #[component]
 pub fn ViewPost() -> impl IntoView {
     let params: Memo<Result<_, _>> = use_params::<ViewPostParams>();
-    let post_resource: Resource<_, Result<Post, ServerFnError>> = create_resource(
+    // blocking, so the status code is known before the page starts streaming;
+    // the links to other posts stream in after it
+    let post_resource: Resource<_, Result<Post, ServerFnError>> = create_blocking_resource(
         move || params.get(),
         |params| async move {
             match params {
                 Ok(ViewPostParams { post_id: Some(s) }) => get_post(s).await,
-                // if no id is in the URL path parameter, assume we are making a new post
-                _ => Ok(Post::new_empty()),
+                // there is nothing to view without an id in the URL path parameter
+                _ => Err(AppError::NotFound("no post id given".to_string()).into()),
             }
         },
     );
 
+    let auth = use_auth();
+    let post_id = Signal::derive(move || {
+        params.with(|params| {
+            params
+                .as_ref()
+                .ok()
+                .and_then(|params| params.post_id.clone())
+                .unwrap_or_default()
+        })
+    });
+
     let post_view = move || {
-        post_resource.and_then(|post| {
-            let post_saved = post.clone();
-            view! {
-                <div class="w-full flex justify-center">
-                    <div class="max-w-[800]">
-                        <div class="flex justify-center pt-10">
-                            <a href={format!("/edit/{}", &post.id)}>Edit</a>
+        post_resource.get().map(|res| match res {
+            Ok(post) => {
+                let post_id = post.id.clone();
+                let can_edit = auth
+                    .current_user()
+                    .map_or(false, |user| user.can_edit_post(&post));
+                let headings = headings(&post.text);
+                // long trip reports get a sidebar for jumping between sections
+                let toc = (!headings.is_empty()).then(|| {
+                    view! {
+                        <aside class="hidden lg:block w-64 pt-10 pl-8">
+                            <TableOfContents headings=headings/>
+                        </aside>
+                    }
+                });
+                Ok(view! {
+                    <div class="w-full flex justify-center">
+                        <div class="max-w-[800]">
+                            <div class="flex justify-center pt-10">
+                                {can_edit.then(|| view! { <a href={format!("/edit/{}", post_id)}>Edit</a> })}
+                            </div>
+                            <BlogPost post=post/>
                         </div>
-                        <BlogPost post=post_saved/>
+                        {toc}
                     </div>
-                </div>
+                }
+                .into_view())
             }
+            Err(e) => match AppError::from_server_fn_error(&e) {
+                // a missing post is an expected outcome, not an error
+                Some(AppError::NotFound(_)) => Ok(view! { <NotFound/> }.into_view()),
+                _ => Err(e),
+            },
         })
     };
 
@@ -47,5 +87,9 @@ pub fn ViewPost() -> impl IntoView {
                 {post_view}
             </ErrorBoundary>
         </Suspense>
+        <div class="max-w-4xl mx-auto">
+            <AdjacentPostLinks post_id=post_id/>
+            <RelatedPosts post_id=post_id/>
+        </div>
     }
 }
@@ -0,0 +1,114 @@
+use leptos::*;
+
+use super::auth::use_auth;
+use super::auth::CsrfField;
+use super::errors_fallback::error_fallback;
+use super::toast::use_toasts;
+use super::toast::ToastMessage;
+use crate::error::AppError;
+use crate::model::webhook::DeliveryStatus;
+use crate::model::webhook::WebhookDelivery;
+use crate::repository::webhook_repository::get_webhook_deliveries;
+use crate::repository::webhook_repository::RetryWebhookDelivery;
+
+#[component]
+fn DeliveryRow(
+    delivery: WebhookDelivery,
+    retry: Action<RetryWebhookDelivery, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let created_at = format!("{}", delivery.created_at.format("%b %e, %Y %I:%M%P"));
+    let outcome = match delivery.status {
+        DeliveryStatus::Delivered => delivery
+            .delivered_at
+            .map(|at| format!("delivered {}", at.format("%b %e, %Y %I:%M%P")))
+            .unwrap_or_default(),
+        DeliveryStatus::Pending if delivery.attempts == 0 => "not sent yet".to_string(),
+        DeliveryStatus::Pending => format!(
+            "next attempt {}",
+            delivery.next_attempt_at.format("%b %e, %Y %I:%M%P")
+        ),
+        DeliveryStatus::Failed => "gave up".to_string(),
+    };
+    let last_response = delivery
+        .last_response_status
+        .map(|status| format!("HTTP {}", status));
+
+    view! {
+        <div class="bg-gray-100 dark:bg-gray-600 p-4 rounded-lg mb-4">
+            <div class="flex justify-between items-baseline">
+                <div class="text-xl font-semibold">{delivery.event}</div>
+                <div class="text-sm">{delivery.status.label()}</div>
+            </div>
+            <div class="text-gray-600 dark:text-gray-200 text-sm pb-2 break-all">
+                {delivery.url}
+            </div>
+            <div class="text-sm">
+                <a href=format!("/view/{}", delivery.post_id) class="hover:text-blue-400">"Post"</a>
+                {format!(" · {} · {} attempt(s) · {}", created_at, delivery.attempts, outcome)}
+                {last_response.map(|status| format!(" · {}", status))}
+            </div>
+            {delivery.last_error.map(|error| view! {
+                <div class="text-sm text-red-600 dark:text-red-300 pt-1 break-all">{error}</div>
+            })}
+            {(delivery.status != DeliveryStatus::Delivered).then(|| view! {
+                <ActionForm action=retry class="pt-2">
+                    <CsrfField/>
+                    <input type="hidden" name="id" value=delivery.id/>
+                    <input type="submit" value="Retry now" class="bg-blue-500 hover:bg-blue-700 text-white font-bold py-2 px-4 rounded cursor-pointer"/>
+                </ActionForm>
+            })}
+        </div>
+    }
+}
+
+/// The webhook delivery log, for admins to see whether the receivers are getting events.
+#[component]
+pub fn AdminWebhooks() -> impl IntoView {
+    let auth = use_auth();
+    let retry = create_server_action::<RetryWebhookDelivery>();
+
+    let deliveries_resource = create_resource(
+        move || (retry.version().get(), auth.user.get()),
+        |_| async move { get_webhook_deliveries().await },
+    );
+
+    let toasts = use_toasts();
+    create_effect(move |_| match retry.value().get() {
+        Some(Ok(())) => {
+            toasts.show(ToastMessage::success(
+                "The delivery will be retried shortly.",
+            ));
+        }
+        Some(Err(e)) => {
+            let message = AppError::from_server_fn_error(&e)
+                .map(|e| e.to_string())
+                .unwrap_or(e.to_string());
+            toasts.show(ToastMessage::error(message));
+        }
+        None => {}
+    });
+
+    let deliveries_view = move || {
+        deliveries_resource.and_then(|deliveries: &Vec<WebhookDelivery>| {
+            if deliveries.is_empty() {
+                return view! { <p>"No webhooks have been sent."</p> }.into_view();
+            }
+            deliveries
+                .iter()
+                .map(|delivery| view! { <DeliveryRow delivery={delivery.clone()} retry=retry/> })
+                .collect_view()
+        })
+    };
+
+    view! {
+        <div class="max-w-3xl mx-auto">
+            <div class="text-4xl pb-2">"Webhooks"</div>
+            <p class="pb-6">"The latest calls of the configured webhook URLs. Failed calls are retried with growing delays until they succeed or run out of attempts."</p>
+            <Transition fallback=move || view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback={error_fallback()}>
+                    {deliveries_view}
+                </ErrorBoundary>
+            </Transition>
+        </div>
+    }
+}
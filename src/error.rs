@@ -0,0 +1,145 @@
+use std::fmt;
+
+use leptos::{ServerFnError, ServerFnErrorErr};
+use serde::{Deserialize, Serialize};
+
+/// Prefix marking a `ServerFnError::ServerError` payload as a serialized [`Payload`].
+const APP_ERROR_PREFIX: &str = "app_error:";
+
+/// What actually travels: the error, and the id of the request that failed with it.
+#[derive(Serialize, Deserialize)]
+struct Payload {
+    error: AppError,
+    request_id: Option<String>,
+}
+
+/// Errors returned by the blog's server functions.
+///
+/// Leptos 0.5 can only carry a string across the server function boundary,
+/// so an `AppError` travels as JSON inside `ServerFnError::ServerError` and is
+/// recovered on the other side with [`AppError::from_server_fn_error`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AppError {
+    NotFound(String),
+    Conflict(String),
+    Validation(String),
+    Unauthorized(String),
+    Internal(String),
+}
+
+impl AppError {
+    /// The HTTP status code this error should be reported with.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            AppError::NotFound(_) => 404,
+            AppError::Conflict(_) => 409,
+            AppError::Validation(_) => 422,
+            AppError::Unauthorized(_) => 401,
+            AppError::Internal(_) => 500,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Conflict(message)
+            | AppError::Validation(message)
+            | AppError::Unauthorized(message)
+            | AppError::Internal(message) => message,
+        }
+    }
+
+    /// Recovers an `AppError` from the result of a server function call.
+    pub fn from_server_fn_error(error: &ServerFnError) -> Option<AppError> {
+        match error {
+            ServerFnError::ServerError(payload) => {
+                Self::decode(payload).map(|payload| payload.error)
+            }
+            _ => None,
+        }
+    }
+
+    /// Recovers an `AppError` from an error caught by an `ErrorBoundary`.
+    pub fn from_error(error: &leptos::error::Error) -> Option<AppError> {
+        Self::decode_error(error).map(|payload| payload.error)
+    }
+
+    /// The id of the request that failed with an error caught by an `ErrorBoundary`,
+    /// to match it up with the server's logs.
+    pub fn request_id(error: &leptos::error::Error) -> Option<String> {
+        Self::decode_error(error).and_then(|payload| payload.request_id)
+    }
+
+    fn decode_error(error: &leptos::error::Error) -> Option<Payload> {
+        match error.downcast_ref::<ServerFnErrorErr>() {
+            Some(ServerFnErrorErr::ServerError(payload)) => Self::decode(payload),
+            _ => None,
+        }
+    }
+
+    fn decode(payload: &str) -> Option<Payload> {
+        payload
+            .strip_prefix(APP_ERROR_PREFIX)
+            .and_then(|json| serde_json::from_str(json).ok())
+    }
+
+    /// Sets the status of the current response to match this error.
+    ///
+    /// This only has an effect during server-side rendering or while handling
+    /// a server function request; on the client it does nothing.
+    pub fn set_response_status(&self) {
+        #[cfg(feature = "ssr")]
+        {
+            if let Some(resp) = leptos::use_context::<leptos_actix::ResponseOptions>() {
+                if let Ok(status) = actix_web::http::StatusCode::from_u16(self.status_code()) {
+                    resp.set_status(status);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NotFound(message) => write!(f, "Not found: {}", message),
+            AppError::Conflict(message) => write!(f, "Conflict: {}", message),
+            AppError::Validation(message) => write!(f, "Invalid input: {}", message),
+            AppError::Unauthorized(message) => write!(f, "Unauthorized: {}", message),
+            AppError::Internal(message) => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+// AppError deliberately does not implement std::error::Error: ServerFnError has a
+// blanket From impl for those, which would flatten the error into its Display string.
+impl From<AppError> for ServerFnError {
+    fn from(error: AppError) -> Self {
+        error.set_response_status();
+        #[cfg(feature = "ssr")]
+        let request_id = crate::server::telemetry::request_id();
+        #[cfg(not(feature = "ssr"))]
+        let request_id = None;
+        let json = serde_json::to_string(&Payload { error, request_id }).unwrap_or_default();
+        ServerFnError::ServerError(format!("{}{}", APP_ERROR_PREFIX, json))
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::RowNotFound => AppError::NotFound("no matching record".to_string()),
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+                AppError::Conflict(db_error.message().to_string())
+            }
+            sqlx::Error::Database(db_error) if db_error.is_check_violation() => {
+                AppError::Validation(db_error.message().to_string())
+            }
+            other => {
+                tracing::error!(error = %other, "database error");
+                AppError::Internal("database error".to_string())
+            }
+        }
+    }
+}
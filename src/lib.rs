@@ -1,7 +1,10 @@
 pub mod app;
 pub mod component;
+pub mod error;
 pub mod model;
 pub mod repository;
+#[cfg(feature = "ssr")]
+pub mod server;
 
 use cfg_if::cfg_if;
 
@@ -1,53 +1,142 @@
 #[cfg(feature = "ssr")]
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    use std::io;
-
-    use actix_files::Files;
+    use actix_web::dev::Service;
     use actix_web::*;
     use hot_blog::app::*;
+    use hot_blog::server::assets::{asset_file, fingerprint_pkg, pkg_file, set_html_cache_control};
+    use hot_blog::server::auth::ensure_admin_from_env;
+    use hot_blog::server::cache::{PageCache, ResponseCache};
+    use hot_blog::server::csrf::{Csrf, MAX_BODY_BYTES};
+    use hot_blog::server::database::{self, DatabaseConfig};
+    use hot_blog::server::health::{healthz, media_dir, readyz, MIGRATOR};
+    use hot_blog::server::images::{image, ImagePolicy};
+    use hot_blog::server::metrics::{metrics_endpoint, RequestMetrics};
+    use hot_blog::server::newsletter::{spawn_newsletter_task, NewsletterConfig};
+    use hot_blog::server::rate_limit::{RateLimit, RateLimitConfig, RateLimiter};
+    use hot_blog::server::security::{SecurityConfig, SecurityHeaders};
+    use hot_blog::server::telemetry::{init_tracing, set_request_id_header};
+    use hot_blog::server::trash::{spawn_purge_task, TrashConfig};
+    use hot_blog::server::webhooks::{spawn_delivery_task, WebhookConfig};
     use leptos::*;
     use leptos_actix::{generate_route_list, LeptosRoutes};
-    use sqlx::{migrate, sqlite::SqlitePoolOptions};
+    use tracing_actix_web::TracingLogger;
+
+    // first, so everything below can report what went wrong
+    init_tracing();
 
-    let conf = get_configuration(None).await.expect("couldn't load configuration!");
+    let mut conf = get_configuration(None)
+        .await
+        .map_err(|e| startup_error("could not load the Leptos configuration", e))?;
     let addr = conf.leptos_options.site_addr;
     // Generate the list of routes in your Leptos App
     let routes = generate_route_list(App);
-    println!("listening on http://{}", &addr);
 
-    env_logger::init();
+    // before any page is rendered, so every page links to the fingerprinted files
+    fingerprint_pkg(&mut conf.leptos_options);
 
-    let db_pool = SqlitePoolOptions::new()
-        .connect("sqlite:post.db")
+    let db_config = DatabaseConfig::from_env();
+    let db_pool = database::connect(&db_config)
         .await
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        .map_err(|e| startup_error(&format!("could not open the database {}", db_config.url), e))?;
 
-    migrate!("./migrations")
+    MIGRATOR
         .run(&db_pool)
         .await
-        .expect(format!("could not run sqlx migration {}", whoami::username()).as_str());
+        .map_err(|e| startup_error("could not run the database migrations", e))?;
+
+    let media_dir = media_dir();
+    std::fs::create_dir_all(&media_dir).map_err(|e| {
+        startup_error(&format!("could not create the media directory {}", media_dir.display()), e)
+    })?;
+
+    ensure_admin_from_env(&db_pool)
+        .await
+        .map_err(|e| startup_error("could not set up the admin account", e))?;
+
+    spawn_purge_task(db_pool.clone(), TrashConfig::from_env());
+    let webhook_config = web::Data::new(WebhookConfig::from_env());
+    spawn_delivery_task(db_pool.clone(), webhook_config.clone());
+    let newsletter_config = web::Data::new(NewsletterConfig::from_env());
+    spawn_newsletter_task(db_pool.clone(), newsletter_config.clone());
 
-    HttpServer::new(move || {
+    // shared by all workers, so a save evicts a page everywhere
+    let response_cache = web::Data::new(ResponseCache::from_env());
+    let rate_limiter = web::Data::new(RateLimiter::new(RateLimitConfig::from_env()));
+    // created once, so every worker checks signatures with the same secret
+    let image_policy = web::Data::new(ImagePolicy::from_env());
+    let security_config = SecurityConfig::from_env(&image_policy);
+
+    // how long requests still in flight at SIGTERM or Ctrl-C get to finish
+    let shutdown_timeout = std::env::var("SHUTDOWN_TIMEOUT_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(30);
+
+    let server_pool = db_pool.clone();
+    let server = HttpServer::new(move || {
         let leptos_options = &conf.leptos_options;
-        let site_root = &leptos_options.site_root;
 
         App::new()
-            .app_data(web::Data::new(db_pool.clone()))
+            .app_data(web::Data::new(server_pool.clone()))
+            .app_data(response_cache.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(image_policy.clone())
+            .app_data(webhook_config.clone())
+            .app_data(newsletter_config.clone())
+            .app_data(web::PayloadConfig::new(MAX_BODY_BYTES))
             .route("/api/{tail:.*}", leptos_actix::handle_server_fns())
+            .service(metrics_endpoint)
+            .service(healthz)
+            .service(readyz)
+            // images hosted elsewhere, as the image policy allows
+            .service(image)
             // serve JS/WASM/CSS from `pkg`
-            .service(Files::new("/pkg", format!("{site_root}/pkg")))
+            .service(pkg_file)
             // serve other assets from the `assets` directory
-            .service(Files::new("/assets", site_root))
+            .service(asset_file)
             // serve the favicon from /favicon.ico
             .service(favicon)
             .leptos_routes(leptos_options.to_owned(), routes.to_owned(), App)
             .app_data(web::Data::new(leptos_options.to_owned()))
-        //.wrap(middleware::Compress::default())
+            .wrap(PageCache)
+            // outside the page cache, so cached pages get a fresh nonce
+            .wrap(SecurityHeaders(security_config.clone()))
+            .wrap(Csrf)
+            // in front of the cache, so cached pages count too
+            .wrap(RateLimit)
+            .wrap_fn(|req, srv| {
+                let res = srv.call(req);
+                async move {
+                    let mut res = res.await?;
+                    set_html_cache_control(&mut res);
+                    set_request_id_header(&mut res);
+                    Ok(res)
+                }
+            })
+            // outside the rest, so it compresses whatever the rest produce
+            .wrap(middleware::Compress::default())
+            .wrap(RequestMetrics)
+            // a span and request id for every request, around everything else
+            .wrap(TracingLogger::default())
     })
-    .bind(&addr)?
-    .run()
-    .await
+    .shutdown_timeout(shutdown_timeout)
+    .bind(&addr)
+    .map_err(|e| startup_error(&format!("could not listen on {}", addr), e))?;
+
+    tracing::info!("listening on http://{}", &addr);
+    // resolves once the server has stopped taking requests and drained the ones in flight
+    let result = server.run().await;
+
+    database::close(db_pool).await;
+    result
+}
+
+/// Logs why the server couldn't start, and turns it into the error `main` exits with.
+#[cfg(feature = "ssr")]
+fn startup_error(context: &str, error: impl std::fmt::Display) -> std::io::Error {
+    tracing::error!(error = %error, "{}", context);
+    std::io::Error::new(std::io::ErrorKind::Other, format!("{}: {}", context, error))
 }
 
 #[cfg(feature = "ssr")]
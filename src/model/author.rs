@@ -0,0 +1,19 @@
+use serde::Deserialize;
+use serde::Serialize;
+#[cfg(feature = "ssr")]
+use sqlx::FromRow;
+
+/// A writer on the blog, stored in the `user` table.
+#[cfg_attr(feature = "ssr", derive(Serialize, Deserialize, Debug, Clone, FromRow))]
+#[cfg_attr(feature = "hydrate", derive(Serialize, Deserialize, Debug, Clone))]
+pub struct Author {
+    pub id: String,
+    /// Unique, URL-safe name used in `/author/:handle`.
+    pub handle: String,
+    pub display_name: String,
+    pub bio: String,
+    pub avatar_url: String,
+    /// What `avatar_url` is shown with, as the server's image policy allows.
+    #[cfg_attr(feature = "ssr", sqlx(skip))]
+    pub avatar_src: Option<String>,
+}
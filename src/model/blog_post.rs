@@ -12,6 +12,51 @@ use chrono::NaiveDateTime;
 #[cfg(feature = "hydrate")]
 use chrono::Local;
 
+use super::post_body::ReadingStats;
+
+/// Whether readers can see a post.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+#[cfg_attr(feature = "ssr", sqlx(rename_all = "snake_case"))]
+pub enum PostStatus {
+    /// Only visible to the people who can edit it.
+    Draft,
+    /// Submitted for an editor to approve or send back.
+    PendingReview,
+    /// Sent back to the writer with review notes.
+    ChangesRequested,
+    Published,
+}
+
+impl PostStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostStatus::Draft => "draft",
+            PostStatus::PendingReview => "pending_review",
+            PostStatus::ChangesRequested => "changes_requested",
+            PostStatus::Published => "published",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PostStatus::Draft => "Draft",
+            PostStatus::PendingReview => "Pending review",
+            PostStatus::ChangesRequested => "Changes requested",
+            PostStatus::Published => "Published",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<PostStatus> {
+        match value {
+            "draft" => Some(PostStatus::Draft),
+            "pending_review" => Some(PostStatus::PendingReview),
+            "changes_requested" => Some(PostStatus::ChangesRequested),
+            "published" => Some(PostStatus::Published),
+            _ => None,
+        }
+    }
+}
 
 #[cfg_attr(feature = "ssr", derive(Serialize, Deserialize, Debug, Clone, FromRow))]
 #[cfg_attr(feature = "hydrate", derive(Serialize, Deserialize, Debug, Clone))]
@@ -21,6 +66,37 @@ pub struct Post {
     pub image_url: String,
     pub title: String,
     pub text: String,
+    /// Written by hand for preview cards; see [`excerpt`](super::post_body::excerpt) for the fallback.
+    pub excerpt: Option<String>,
+    /// Incremented on every save; an update must name the version it was based on.
+    pub version: i64,
+    /// Set while the post is in the trash.
+    pub deleted_at: Option<NaiveDateTime>,
+    pub author_id: Option<String>,
+    pub status: PostStatus,
+    // byline, joined in from the author's `user` row
+    #[cfg_attr(feature = "ssr", sqlx(default))]
+    pub author_handle: Option<String>,
+    #[cfg_attr(feature = "ssr", sqlx(default))]
+    pub author_name: Option<String>,
+    #[cfg_attr(feature = "ssr", sqlx(default))]
+    pub author_avatar_url: Option<String>,
+    /// What `image_url` is shown with, as the server's image policy allows; `None` to show nothing.
+    #[cfg_attr(feature = "ssr", sqlx(skip))]
+    pub image_src: Option<String>,
+    #[cfg_attr(feature = "ssr", sqlx(skip))]
+    pub author_avatar_src: Option<String>,
+    /// The paragraphs of the text as sanitized HTML, in the order
+    /// [`parse_blocks`](super::post_body::parse_blocks) gives them. Filled in by the server,
+    /// which is the only place HTML can be sanitized.
+    #[cfg_attr(feature = "ssr", sqlx(skip))]
+    pub paragraphs_html: Vec<String>,
+    /// Lowercase, stored in `post_tag`.
+    #[cfg_attr(feature = "ssr", sqlx(skip))]
+    pub tags: Vec<String>,
+    /// Computed from the full text on the server, see [`Post::update_reading_stats`].
+    #[cfg_attr(feature = "ssr", sqlx(skip))]
+    pub reading_stats: ReadingStats,
 }
 
 impl Post {
@@ -31,6 +107,67 @@ impl Post {
             image_url: "".to_string(),
             title: "".to_string(),
             text: "".to_string(),
+            excerpt: None,
+            version: 0,
+            deleted_at: None,
+            author_id: None,
+            status: PostStatus::Draft,
+            author_handle: None,
+            author_name: None,
+            author_avatar_url: None,
+            image_src: None,
+            author_avatar_src: None,
+            paragraphs_html: Vec::new(),
+            tags: Vec::new(),
+            reading_stats: ReadingStats::default(),
         }
     }
+
+    pub fn update_reading_stats(&mut self) {
+        self.reading_stats = ReadingStats::of(&self.text);
+    }
+
+    /// The text split on blank lines. Review notes refer to paragraphs by their index here.
+    pub fn paragraphs(&self) -> Vec<String> {
+        // `lines` also copes with the \r\n line endings browsers submit
+        self.text
+            .lines()
+            .collect::<Vec<_>>()
+            .split(|line| line.trim().is_empty())
+            .filter(|lines| !lines.is_empty())
+            .map(|lines| lines.join("\n"))
+            .collect()
+    }
+}
+
+/// Turns "Road Trip, hiking ,road trip" into `["road-trip", "hiking"]`.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split(',') {
+        let tag = tag
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-")
+            .to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Just enough of a post to link to it.
+#[cfg_attr(feature = "ssr", derive(Serialize, Deserialize, Debug, Clone, FromRow))]
+#[cfg_attr(feature = "hydrate", derive(Serialize, Deserialize, Debug, Clone))]
+pub struct PostLink {
+    pub id: String,
+    pub title: String,
+}
+
+/// The published posts either side of a post, by date.
+#[cfg_attr(feature = "ssr", derive(Serialize, Deserialize, Debug, Clone))]
+#[cfg_attr(feature = "hydrate", derive(Serialize, Deserialize, Debug, Clone))]
+pub struct AdjacentPosts {
+    pub previous: Option<PostLink>,
+    pub next: Option<PostLink>,
 }
@@ -0,0 +1,72 @@
+use serde::Deserialize;
+use serde::Serialize;
+#[cfg(feature = "ssr")]
+use sqlx::types::chrono::NaiveDateTime;
+#[cfg(feature = "ssr")]
+use sqlx::FromRow;
+
+#[cfg(feature = "hydrate")]
+use chrono::NaiveDateTime;
+
+use super::blog_post::Post;
+
+/// Draft key used for a post that hasn't been saved yet. Drafts are kept per user, so
+/// everyone has their own.
+pub const NEW_POST_DRAFT_KEY: &str = "new";
+
+/// Unsaved edits to a post, autosaved by the editor. Only the user who made them sees them.
+#[cfg_attr(feature = "ssr", derive(Serialize, Deserialize, Debug, Clone, FromRow))]
+#[cfg_attr(feature = "hydrate", derive(Serialize, Deserialize, Debug, Clone))]
+pub struct Draft {
+    /// The id of the post being edited, or [`NEW_POST_DRAFT_KEY`] for a new post.
+    pub post_id: String,
+    /// The version of the post the edits started from.
+    pub base_version: i64,
+    pub dt: NaiveDateTime,
+    pub image_url: String,
+    pub title: String,
+    pub text: String,
+    pub saved_at: NaiveDateTime,
+}
+
+impl Draft {
+    pub fn key_for(post_id: &str) -> String {
+        if post_id.is_empty() {
+            NEW_POST_DRAFT_KEY.to_string()
+        } else {
+            post_id.to_string()
+        }
+    }
+
+    pub fn from_post(post: &Post, saved_at: NaiveDateTime) -> Draft {
+        Draft {
+            post_id: Self::key_for(&post.id),
+            base_version: post.version,
+            dt: post.dt,
+            image_url: post.image_url.clone(),
+            title: post.title.clone(),
+            text: post.text.clone(),
+            saved_at,
+        }
+    }
+
+    /// Whether the draft holds anything that isn't already in `post`.
+    pub fn differs_from(&self, post: &Post) -> bool {
+        self.dt != post.dt
+            || self.image_url != post.image_url
+            || self.title != post.title
+            || self.text != post.text
+    }
+
+    /// Copies the drafted content onto `post`.
+    ///
+    /// The post keeps the version the draft was based on, so saving it after
+    /// someone else's edit is caught as a conflict instead of overwriting them.
+    pub fn apply_to(&self, post: &mut Post) {
+        post.dt = self.dt;
+        post.image_url = self.image_url.clone();
+        post.title = self.title.clone();
+        post.text = self.text.clone();
+        post.version = self.base_version;
+    }
+}
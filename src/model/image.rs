@@ -0,0 +1,46 @@
+/// Where the server serves images it fetches or decodes, by the URL they were given as.
+pub const IMAGE_PATH: &str = "/image";
+
+/// URL schemes an image may be given with. `data:` images are only shown if the server
+/// is configured to allow them.
+const IMAGE_SCHEMES: &[&str] = &["http", "https", "data"];
+
+/// The scheme of `url`, lowercased, or `None` for relative URLs.
+fn scheme_of(url: &str) -> Option<String> {
+    let (scheme, _) = url.split_once(':')?;
+    let is_scheme = scheme
+        .chars()
+        .next()
+        .map_or(false, |first| first.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    is_scheme.then(|| scheme.to_ascii_lowercase())
+}
+
+/// Whether `url` may be saved as a post image or avatar: empty, a path on this site, or an
+/// http(s) or `data:` URL. Rules out `javascript:` and the like.
+pub fn is_allowed_image_url(url: &str) -> bool {
+    let url = url.trim();
+    if url.is_empty() || (url.starts_with('/') && !url.starts_with("//")) {
+        return true;
+    }
+    match scheme_of(url) {
+        Some(scheme) => IMAGE_SCHEMES.contains(&scheme.as_str()),
+        None => false,
+    }
+}
+
+/// Percent-encodes everything but the characters RFC 3986 leaves unreserved.
+pub fn encode_query_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
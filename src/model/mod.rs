@@ -1 +1,9 @@
+pub mod author;
 pub mod blog_post;
+pub mod draft;
+pub mod image;
+pub mod post_body;
+pub mod review;
+pub mod setting;
+pub mod user;
+pub mod webhook;
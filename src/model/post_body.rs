@@ -0,0 +1,291 @@
+//! Structure inside a post's plain text: paragraphs separated by blank lines,
+//! and headings written as paragraphs starting with `#`, `##` or `###`.
+
+use serde::Deserialize;
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Average adult silent reading speed, in words per minute.
+const WORDS_PER_MINUTE: u32 = 200;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// 1 for `#`, 2 for `##`, 3 for `###`.
+    pub level: u8,
+    pub text: String,
+    /// Unique within the post, used as the element id and in `#anchor` links.
+    pub anchor: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    Heading(Heading),
+    Paragraph(String),
+}
+
+/// Splits text into headings and paragraphs.
+pub fn parse_blocks(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut anchors: Vec<String> = Vec::new();
+
+    // `lines` also copes with the \r\n line endings browsers submit
+    let lines: Vec<&str> = text.lines().collect();
+    for paragraph in lines.split(|line| line.trim().is_empty()) {
+        let Some((first, rest)) = paragraph.split_first() else {
+            continue;
+        };
+
+        let mut rest = rest;
+        match parse_heading(first) {
+            Some((level, heading)) => {
+                let anchor = unique_anchor(&slugify(heading), &anchors);
+                anchors.push(anchor.clone());
+                blocks.push(Block::Heading(Heading {
+                    level,
+                    text: heading.to_string(),
+                    anchor,
+                }));
+            }
+            None => rest = paragraph,
+        }
+
+        if !rest.is_empty() {
+            blocks.push(Block::Paragraph(rest.join("\n")));
+        }
+    }
+
+    blocks
+}
+
+pub fn headings(text: &str) -> Vec<Heading> {
+    parse_blocks(text)
+        .into_iter()
+        .filter_map(|block| match block {
+            Block::Heading(heading) => Some(heading),
+            Block::Paragraph(_) => None,
+        })
+        .collect()
+}
+
+fn parse_heading(line: &str) -> Option<(u8, &str)> {
+    let line = line.trim();
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if !(1..=3).contains(&level) {
+        return None;
+    }
+    let text = line[level..].strip_prefix(' ')?.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some((level as u8, text))
+}
+
+fn slugify(text: &str) -> String {
+    let slug = text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+fn unique_anchor(slug: &str, taken: &[String]) -> String {
+    let mut anchor = slug.to_string();
+    let mut suffix = 2;
+    while taken.contains(&anchor) {
+        anchor = format!("{}-{}", slug, suffix);
+        suffix += 1;
+    }
+    anchor
+}
+
+/// The text without markup: heading markers, HTML tags, emphasis and link targets
+/// are dropped, and all whitespace is collapsed to single spaces.
+pub fn plain_text(text: &str) -> String {
+    let blocks: Vec<String> = parse_blocks(text)
+        .into_iter()
+        .map(|block| match block {
+            Block::Heading(heading) => heading.text,
+            Block::Paragraph(paragraph) => paragraph,
+        })
+        .collect();
+    let text = strip_links(&strip_tags(&blocks.join(" ")));
+
+    text.replace(['*', '`'], "")
+        .replace("~~", "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Drops anything that looks like an HTML tag or comment. A `<` with no `>` after it is
+/// text, like in "a <b", and is kept.
+fn strip_tags(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        let starts_tag = rest[open + 1..].chars().next().map_or(false, |next| {
+            next.is_ascii_alphabetic() || next == '/' || next == '!'
+        });
+        let close = rest[open..].find('>').filter(|_| starts_tag);
+        let Some(close) = close else {
+            out.push_str(&rest[..=open]);
+            rest = &rest[open + 1..];
+            continue;
+        };
+        out.push_str(&rest[..open]);
+        // a tag separates words, so leave a space in its place
+        out.push(' ');
+        rest = &rest[open + close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Replaces markdown links `[text](url)` with their text, and drops images `![alt](url)`.
+fn strip_links(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('[') {
+        let link = rest[open + 1..].find("](").and_then(|label_end| {
+            let label_end = open + 1 + label_end;
+            let url_end = label_end + 2 + rest[label_end + 2..].find(')')?;
+            Some((label_end, url_end))
+        });
+        let Some((label_end, url_end)) = link else {
+            break;
+        };
+        let is_image = rest[..open].ends_with('!');
+        out.push_str(&rest[..if is_image { open - 1 } else { open }]);
+        if !is_image {
+            out.push_str(&rest[open + 1..label_end]);
+        }
+        rest = &rest[url_end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A plain-text excerpt of at most `max_graphemes` user-visible characters, plus an ellipsis
+/// if anything was cut. Cuts between words, unless the first word alone is too long.
+pub fn excerpt(text: &str, max_graphemes: usize) -> String {
+    let plain = plain_text(text);
+    let Some((cut, _)) = plain.grapheme_indices(true).nth(max_graphemes) else {
+        return plain;
+    };
+
+    let head = &plain[..cut];
+    let ends_at_word = plain[cut..].starts_with(char::is_whitespace);
+    let head = match head.rfind(char::is_whitespace) {
+        Some(space) if !ends_at_word && space > 0 => &head[..space],
+        _ => head,
+    };
+    let head = head.trim_end_matches(|c: char| c.is_whitespace() || ",;:-–—".contains(c));
+
+    format!("{}…", head)
+}
+
+/// How long a post is to read.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReadingStats {
+    pub word_count: u32,
+    /// Rounded up, and at least one minute for any post with words in it.
+    pub minutes: u32,
+}
+
+impl ReadingStats {
+    pub fn of(text: &str) -> ReadingStats {
+        let word_count = text
+            .split_whitespace()
+            // heading markers aren't words
+            .filter(|word| !word.chars().all(|c| c == '#'))
+            .count() as u32;
+        ReadingStats {
+            word_count,
+            minutes: word_count.div_ceil(WORDS_PER_MINUTE),
+        }
+    }
+
+    /// e.g. "1,234 words · 7 min read"
+    pub fn label(&self) -> String {
+        let digits = self.word_count.to_string();
+        let mut words = String::new();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                words.push(',');
+            }
+            words.push(digit);
+        }
+        let unit = if self.word_count == 1 {
+            "word"
+        } else {
+            "words"
+        };
+        format!("{} {} · {} min read", words, unit, self.minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_tags() {
+        assert_eq!(strip_tags("a <b>bold</b> word"), "a  bold  word");
+        assert_eq!(strip_tags("<!-- note -->text"), " text");
+        assert_eq!(strip_tags("1 < 2 and 3 <4"), "1 < 2 and 3 <4");
+    }
+
+    #[test]
+    fn keeps_a_lone_angle_bracket_and_what_follows() {
+        assert_eq!(strip_tags("if a <b then"), "if a <b then");
+        assert_eq!(excerpt("so x <y and the rest", 100), "so x <y and the rest");
+    }
+
+    #[test]
+    fn excerpt_removes_markup() {
+        let text = "# Heading\n\nSome *bold* and `code`, a [link](https://example.com), \
+                    ![an image](/a.png)<em>emphasis</em> and ~~struck~~ text.";
+        assert_eq!(
+            excerpt(text, 200),
+            "Heading Some bold and code, a link, emphasis and struck text."
+        );
+    }
+
+    #[test]
+    fn excerpt_keeps_text_that_fits() {
+        assert_eq!(excerpt("exactly ten", 11), "exactly ten");
+        assert_eq!(excerpt("exactly ten", 10), "exactly…");
+        assert_eq!(excerpt("", 10), "");
+    }
+
+    #[test]
+    fn excerpt_cuts_between_words() {
+        assert_eq!(excerpt("one two three four", 9), "one two…");
+        // cut right before a space, so the last word is whole
+        assert_eq!(excerpt("one two three four", 7), "one two…");
+        // trailing punctuation goes with the cut
+        assert_eq!(excerpt("one, two", 6), "one…");
+        // a first word that is too long is cut inside
+        assert_eq!(excerpt("incomprehensibilities", 5), "incom…");
+    }
+
+    #[test]
+    fn excerpt_cuts_between_characters_as_users_see_them() {
+        assert_eq!(excerpt("héllo wörld", 8), "héllo…");
+        assert_eq!(excerpt("日本語のテキスト", 3), "日本語…");
+        // a family emoji is several code points joined, and is never split
+        let family = "👨‍👩‍👧";
+        assert_eq!(
+            excerpt(&family.repeat(3), 2),
+            format!("{}…", family.repeat(2))
+        );
+        // nor is a letter with a combining accent
+        assert_eq!(excerpt("e\u{301}e\u{301}e\u{301}", 2), "e\u{301}e\u{301}…");
+    }
+}
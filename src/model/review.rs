@@ -0,0 +1,43 @@
+use serde::Deserialize;
+use serde::Serialize;
+#[cfg(feature = "ssr")]
+use sqlx::types::chrono::NaiveDateTime;
+#[cfg(feature = "ssr")]
+use sqlx::FromRow;
+
+#[cfg(feature = "hydrate")]
+use chrono::NaiveDateTime;
+
+use super::blog_post::PostStatus;
+
+/// A reviewer's comment on one paragraph of a post.
+#[cfg_attr(feature = "ssr", derive(Serialize, Deserialize, Debug, Clone, FromRow))]
+#[cfg_attr(feature = "hydrate", derive(Serialize, Deserialize, Debug, Clone))]
+pub struct ReviewNote {
+    pub id: i64,
+    pub post_id: String,
+    /// Index into [`Post::paragraphs`](super::blog_post::Post::paragraphs).
+    pub paragraph: i64,
+    /// The paragraph as it read when the note was left, in case it has changed since.
+    pub quote: String,
+    pub body: String,
+    #[cfg_attr(feature = "ssr", sqlx(default))]
+    pub author_name: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub resolved: bool,
+}
+
+/// One change of a post's status: who made it, when, and why.
+#[cfg_attr(feature = "ssr", derive(Serialize, Deserialize, Debug, Clone, FromRow))]
+#[cfg_attr(feature = "hydrate", derive(Serialize, Deserialize, Debug, Clone))]
+pub struct StatusTransition {
+    pub id: i64,
+    pub post_id: String,
+    /// `None` when the post was created.
+    pub from_status: Option<PostStatus>,
+    pub to_status: PostStatus,
+    #[cfg_attr(feature = "ssr", sqlx(default))]
+    pub actor_name: Option<String>,
+    pub note: String,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,18 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Blog-wide settings that admins can change without a redeploy.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub blog_title: String,
+    pub blog_tagline: String,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            blog_title: "Moonbound".to_string(),
+            blog_tagline: "A travel blog about fun places".to_string(),
+        }
+    }
+}
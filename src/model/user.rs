@@ -0,0 +1,151 @@
+use serde::Deserialize;
+use serde::Serialize;
+#[cfg(feature = "ssr")]
+use sqlx::FromRow;
+
+use super::blog_post::Post;
+use super::blog_post::PostStatus;
+
+/// What a user is allowed to do, from least to most privileged.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+#[cfg_attr(feature = "ssr", sqlx(rename_all = "snake_case"))]
+pub enum Role {
+    /// Writes drafts and submits them for review, but can't publish them.
+    Contributor,
+    /// Publishes and edits their own posts.
+    Author,
+    /// Edits and publishes anyone's posts, and reviews submissions.
+    Editor,
+    /// Everything an editor can do, plus managing users and settings.
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Contributor, Role::Author, Role::Editor, Role::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Contributor => "contributor",
+            Role::Author => "author",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Role> {
+        Role::ALL.into_iter().find(|role| role.as_str() == value)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Role::Contributor => "Contributor",
+            Role::Author => "Author",
+            Role::Editor => "Editor",
+            Role::Admin => "Admin",
+        }
+    }
+}
+
+/// The logged-in user. All permission checks go through here, so the server
+/// and the UI agree on who can do what.
+#[cfg_attr(
+    feature = "ssr",
+    derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromRow)
+)]
+#[cfg_attr(
+    feature = "hydrate",
+    derive(Serialize, Deserialize, Debug, Clone, PartialEq)
+)]
+pub struct CurrentUser {
+    pub id: String,
+    pub handle: String,
+    pub display_name: String,
+    pub role: Role,
+}
+
+impl CurrentUser {
+    fn owns(&self, post: &Post) -> bool {
+        post.author_id.as_deref() == Some(self.id.as_str())
+    }
+
+    /// Whether this user may save changes to `post`. An empty id means a new post.
+    pub fn can_edit_post(&self, post: &Post) -> bool {
+        match self.role {
+            Role::Admin | Role::Editor => true,
+            Role::Author => post.id.is_empty() || self.owns(post),
+            // once submitted, a post is out of a contributor's hands until it comes back
+            Role::Contributor => {
+                post.id.is_empty()
+                    || (self.owns(post)
+                        && matches!(
+                            post.status,
+                            PostStatus::Draft | PostStatus::ChangesRequested
+                        ))
+            }
+        }
+    }
+
+    /// Whether this user may make `post` public.
+    pub fn can_publish_post(&self, post: &Post) -> bool {
+        match self.role {
+            Role::Admin | Role::Editor => true,
+            Role::Author => post.id.is_empty() || self.owns(post),
+            Role::Contributor => false,
+        }
+    }
+
+    /// Whether this user may approve or send back a post that is pending review.
+    pub fn can_review_post(&self, post: &Post) -> bool {
+        self.role >= Role::Editor && post.status == PostStatus::PendingReview
+    }
+
+    /// Whether this user may save `post` with the status `to`, given they can edit it.
+    pub fn can_set_status(&self, post: &Post, to: PostStatus) -> bool {
+        match to {
+            _ if to == post.status => true,
+            PostStatus::Draft | PostStatus::PendingReview => true,
+            PostStatus::ChangesRequested => self.can_review_post(post),
+            PostStatus::Published => self.can_publish_post(post),
+        }
+    }
+
+    pub fn can_delete_post(&self, post: &Post) -> bool {
+        !post.id.is_empty() && self.can_edit_post(post)
+    }
+
+    /// Whether this user may credit a post to someone else.
+    pub fn can_assign_author(&self) -> bool {
+        self.role >= Role::Editor
+    }
+
+    /// Whether this user may see the review queue.
+    pub fn is_reviewer(&self) -> bool {
+        self.role >= Role::Editor
+    }
+
+    /// Whether this user may see the trash and purge posts from it.
+    pub fn can_manage_trash(&self) -> bool {
+        self.role >= Role::Editor
+    }
+
+    pub fn can_manage_users(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    pub fn can_manage_settings(&self) -> bool {
+        self.role == Role::Admin
+    }
+}
+
+/// A user as listed on the admin page.
+#[cfg_attr(feature = "ssr", derive(Serialize, Deserialize, Debug, Clone, FromRow))]
+#[cfg_attr(feature = "hydrate", derive(Serialize, Deserialize, Debug, Clone))]
+pub struct UserSummary {
+    pub id: String,
+    pub handle: String,
+    pub display_name: String,
+    pub role: Role,
+    /// Users without a password can be credited as authors but can't log in.
+    pub has_password: bool,
+}
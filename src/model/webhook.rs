@@ -0,0 +1,50 @@
+use serde::Deserialize;
+use serde::Serialize;
+#[cfg(feature = "ssr")]
+use sqlx::types::chrono::NaiveDateTime;
+#[cfg(feature = "ssr")]
+use sqlx::FromRow;
+
+#[cfg(feature = "hydrate")]
+use chrono::NaiveDateTime;
+
+/// Where a webhook delivery stands.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+#[cfg_attr(feature = "ssr", sqlx(rename_all = "snake_case"))]
+pub enum DeliveryStatus {
+    /// Not sent yet, or waiting to be retried.
+    Pending,
+    Delivered,
+    /// Given up on after too many attempts.
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "Pending",
+            DeliveryStatus::Delivered => "Delivered",
+            DeliveryStatus::Failed => "Failed",
+        }
+    }
+}
+
+/// One call of a webhook for a post event, and how it went.
+#[cfg_attr(feature = "ssr", derive(Serialize, Deserialize, Debug, Clone, FromRow))]
+#[cfg_attr(feature = "hydrate", derive(Serialize, Deserialize, Debug, Clone))]
+pub struct WebhookDelivery {
+    pub id: String,
+    /// Like `post.published`.
+    pub event: String,
+    pub post_id: String,
+    pub url: String,
+    pub status: DeliveryStatus,
+    pub attempts: i64,
+    /// The HTTP status of the last response, if the receiver answered at all.
+    pub last_response_status: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub next_attempt_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+}
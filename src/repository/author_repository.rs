@@ -0,0 +1,119 @@
+use crate::error::AppError;
+use crate::model::author::Author;
+#[cfg(feature = "ssr")]
+use crate::model::image::is_allowed_image_url;
+use std::sync::Arc;
+
+#[cfg(feature = "ssr")]
+use crate::server::auth;
+#[cfg(feature = "ssr")]
+use crate::server::cache::response_cache;
+#[cfg(feature = "ssr")]
+use crate::server::images::image_policy;
+#[cfg(feature = "ssr")]
+use crate::server::telemetry::traced;
+#[cfg(feature = "ssr")]
+use actix_web::web::Data;
+#[cfg(feature = "ssr")]
+use sqlx::{Pool, Sqlite};
+
+use leptos::*;
+#[cfg(feature = "ssr")]
+use leptos_actix::extract;
+#[cfg(feature = "ssr")]
+use uuid::Uuid;
+
+#[server(GetAuthors, "/api")]
+pub async fn get_authors() -> Result<Vec<Author>, ServerFnError> {
+    traced("GetAuthors", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        let mut res: Vec<Author> = sqlx::query_as(
+            "SELECT id, handle, display_name, bio, avatar_url FROM user ORDER BY display_name",
+        )
+        .fetch_all(&*pool)
+        .await
+        .map_err(AppError::from)?;
+
+        let images = image_policy().await?;
+        for author in res.iter_mut() {
+            images.set_author_srcs(author);
+        }
+        Ok(res)
+    })
+    .await
+}
+
+#[server(GetAuthor, "/api")]
+pub async fn get_author(handle: String) -> Result<Author, ServerFnError> {
+    traced("GetAuthor", async move {
+        tracing::debug!(?handle);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        let res: Option<Author> = sqlx::query_as(
+            "SELECT id, handle, display_name, bio, avatar_url FROM user WHERE handle = ?",
+        )
+        .bind(&handle)
+        .fetch_optional(&*pool)
+        .await
+        .map_err(AppError::from)?;
+
+        let mut author =
+            res.ok_or_else(|| AppError::NotFound(format!("no author with handle {}", handle)))?;
+        image_policy().await?.set_author_srcs(&mut author);
+        Ok(author)
+    })
+    .await
+}
+
+/// Creates an author, or updates the profile of the author with the same handle.
+#[server(UpsertAuthor, "/api")]
+pub async fn upsert_author(
+    handle: String,
+    display_name: String,
+    bio: String,
+    avatar_url: String,
+) -> Result<String, ServerFnError> {
+    traced("UpsertAuthor", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        if !auth::require_user().await?.can_manage_users() {
+            return Err(AppError::Unauthorized("only admins can manage authors".to_string()).into());
+        }
+
+        let handle = handle.trim().to_lowercase();
+        if handle.is_empty()
+            || !handle
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(AppError::Validation(
+                "a handle may only contain letters, digits, '-' and '_'".to_string(),
+            )
+            .into());
+        }
+        if display_name.trim().is_empty() {
+            return Err(AppError::Validation("an author needs a display name".to_string()).into());
+        }
+        if !is_allowed_image_url(&avatar_url) {
+            return Err(AppError::Validation("an avatar needs an http(s) or data: URL, or a path on this site".to_string()).into());
+        }
+
+        sqlx::query("INSERT INTO user (id, handle, display_name, bio, avatar_url) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (handle) DO UPDATE SET display_name=excluded.display_name, bio=excluded.bio, avatar_url=excluded.avatar_url")
+            .bind(Uuid::new_v4().to_string())
+            .bind(&handle)
+            .bind(display_name.trim())
+            .bind(&bio)
+            .bind(&avatar_url)
+            .execute(&*pool)
+            .await
+            .map_err(AppError::from)?;
+        // bylines show on every post and preview
+        response_cache().await?.clear();
+
+        Ok(handle)
+    })
+    .await
+}
@@ -1,104 +1,550 @@
+use crate::error::AppError;
 use crate::model::blog_post::Post;
+use crate::model::draft::Draft;
 use std::{sync::Arc, thread::sleep, time::Duration};
 
+#[cfg(feature = "ssr")]
+use crate::model::blog_post::parse_tags;
+#[cfg(feature = "ssr")]
+use crate::model::blog_post::PostStatus;
+#[cfg(feature = "ssr")]
+use crate::model::image::is_allowed_image_url;
+#[cfg(feature = "ssr")]
+use crate::model::post_body::excerpt;
+#[cfg(feature = "ssr")]
+use crate::model::user::CurrentUser;
+#[cfg(feature = "ssr")]
+use crate::repository::review_repository::record_transition;
+#[cfg(feature = "ssr")]
+use crate::server::auth;
+#[cfg(feature = "ssr")]
+use crate::server::cache::{depends_on, response_cache, Dependency};
+#[cfg(feature = "ssr")]
+use crate::server::images::image_policy;
+#[cfg(feature = "ssr")]
+use crate::server::metrics::timed_query;
+#[cfg(feature = "ssr")]
+use crate::server::newsletter::post_published;
+#[cfg(feature = "ssr")]
+use crate::server::sanitize::paragraphs_html;
+#[cfg(feature = "ssr")]
+use crate::server::telemetry::traced;
+#[cfg(feature = "ssr")]
+use crate::server::webhooks::{post_event, PostEvent};
 #[cfg(feature = "ssr")]
 use actix_web::web::Data;
 #[cfg(feature = "ssr")]
 use sqlx::{Pool, Sqlite};
 
-use leptos::{logging::log, *};
+use leptos::*;
 #[cfg(feature = "ssr")]
 use leptos_actix::extract;
 #[cfg(feature = "ssr")]
 use uuid::Uuid;
 
-pub struct DBError {}
+/// Selects posts along with their author's byline.
+#[cfg(feature = "ssr")]
+pub(crate) const POST_SELECT: &str = "SELECT post.*, user.handle AS author_handle, user.display_name AS author_name, user.avatar_url AS author_avatar_url FROM post LEFT JOIN user ON user.id = post.author_id";
+
+/// Looks up a post whether or not it is in the trash, for permission checks.
+#[cfg(feature = "ssr")]
+pub(crate) async fn find_post(pool: &Pool<Sqlite>, id: &str) -> Result<Option<Post>, AppError> {
+    let post = sqlx::query_as(&format!("{} WHERE post.id = ?", POST_SELECT))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(post)
+}
+
+/// The current user, provided they may edit the post with the given id.
+/// An empty id stands for a post that hasn't been saved yet.
+#[cfg(feature = "ssr")]
+pub(crate) async fn require_can_edit(
+    pool: &Pool<Sqlite>,
+    post_id: &str,
+) -> Result<CurrentUser, ServerFnError> {
+    let user = auth::require_user().await?;
+    let post = match post_id {
+        "" => None,
+        id => find_post(pool, id).await?,
+    };
+    if !user.can_edit_post(&post.unwrap_or_else(Post::new_empty)) {
+        return Err(AppError::Unauthorized("you can't edit this post".to_string()).into());
+    }
+    Ok(user)
+}
 
 #[server(UpsertPost, "/api")]
 pub async fn upsert_post(
     id: Option<String>,
+    version: i64,
+    author_id: Option<String>,
+    status: String,
     dt: String,
     image_url: String,
     title: String,
     text: String,
+    excerpt: String,
+    tags: String,
+    _csrf: String,
 ) -> Result<String, ServerFnError> {
-    let pool: Arc<Pool<Sqlite>> =
-        extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+    traced("UpsertPost", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        let user = auth::require_user().await?;
 
-    let id = id.unwrap_or(Uuid::new_v4().to_string());
-    sqlx::query("INSERT INTO post VALUES ($1, $2, $3, $4, $5) ON CONFLICT (id) DO UPDATE SET dt=excluded.dt, image_url=excluded.image_url, title=excluded.title, text=excluded.text")
-        .bind(&id)
-        .bind(&dt)
-        .bind(&image_url)
-        .bind(&title)
-        .bind(&text)
-        .execute(&*pool)
-        .await?;
+        if title.trim().is_empty() {
+            return Err(AppError::Validation("a post needs a title".to_string()).into());
+        }
+        if chrono::NaiveDateTime::parse_from_str(&dt, "%Y-%m-%dT%H:%M").is_err() {
+            return Err(AppError::Validation(format!("invalid date {:?}", dt)).into());
+        }
+        if !is_allowed_image_url(&image_url) {
+            return Err(AppError::Validation("a post image needs an http(s) or data: URL, or a path on this site".to_string()).into());
+        }
+
+        let status = PostStatus::parse(&status)
+            .ok_or_else(|| AppError::Validation(format!("unknown status {:?}", status)))?;
+
+        // every permission check on saving a post happens here
+        let existing = match id.as_deref() {
+            Some(id) if !id.is_empty() => find_post(&pool, id).await?,
+            _ => None,
+        };
+        let target = existing.clone().unwrap_or_else(Post::new_empty);
+        if !user.can_edit_post(&target) {
+            return Err(AppError::Unauthorized("you can't edit this post".to_string()).into());
+        }
+        if !user.can_set_status(&target, status) {
+            return Err(AppError::Unauthorized(format!(
+                "you can't mark this post as {}",
+                status.label().to_lowercase()
+            ))
+            .into());
+        }
+        let previous_status = existing.as_ref().map(|post| post.status);
+        // only editors may credit a post to someone else
+        let author_id = if user.can_assign_author() {
+            author_id.filter(|author_id| !author_id.is_empty())
+        } else {
+            existing
+                .map(|post| post.author_id)
+                .unwrap_or_else(|| Some(user.id.clone()))
+        };
+
+        let draft_key = Draft::key_for(id.as_deref().unwrap_or(""));
+        let id = id
+            .filter(|id| !id.is_empty())
+            .unwrap_or(Uuid::new_v4().to_string());
+        // the post, its tags, its history and the draft are saved together or not at all
+        let mut tx = pool.begin().await.map_err(AppError::from)?;
+        // only overwrite the post if nobody else has saved it since this edit started
+        let excerpt = Some(excerpt.trim().to_string()).filter(|excerpt| !excerpt.is_empty());
+        let updated = timed_query("update_post", sqlx::query("UPDATE post SET dt = $1, image_url = $2, title = $3, text = $4, excerpt = $5, author_id = $6, status = $7, version = version + 1 WHERE id = $8 AND version = $9 AND deleted_at IS NULL")
+            .bind(&dt)
+            .bind(&image_url)
+            .bind(&title)
+            .bind(&text)
+            .bind(&excerpt)
+            .bind(&author_id)
+            .bind(status)
+            .bind(&id)
+            .bind(version)
+            .execute(&mut *tx))
+            .await
+            .map_err(AppError::from)?;
+
+        if updated.rows_affected() == 0 {
+            let current: Option<(i64, Option<String>)> =
+                sqlx::query_as("SELECT version, deleted_at FROM post WHERE id = ?")
+                    .bind(&id)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .map_err(AppError::from)?;
+
+            match current {
+                Some((_, Some(_))) => {
+                    return Err(AppError::NotFound("this post has been deleted".to_string()).into());
+                }
+                Some((current, None)) => {
+                    return Err(AppError::Conflict(format!(
+                        "this post was changed by someone else (version {} is newer than {})",
+                        current, version
+                    ))
+                    .into());
+                }
+                None => {}
+            }
+
+            // a concurrent insert of the same id surfaces as a unique violation, i.e. a conflict
+            timed_query("insert_post", sqlx::query("INSERT INTO post (id, dt, image_url, title, text, excerpt, author_id, status, version) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 1)")
+                .bind(&id)
+                .bind(&dt)
+                .bind(&image_url)
+                .bind(&title)
+                .bind(&text)
+                .bind(&excerpt)
+                .bind(&author_id)
+                .bind(status)
+                .execute(&mut *tx))
+                .await
+                .map_err(AppError::from)?;
+        }
+
+        sqlx::query("DELETE FROM post_tag WHERE post_id = ?")
+            .bind(&id)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::from)?;
+        for tag in parse_tags(&tags) {
+            sqlx::query("INSERT INTO post_tag (post_id, tag) VALUES (?, ?)")
+                .bind(&id)
+                .bind(&tag)
+                .execute(&mut *tx)
+                .await
+                .map_err(AppError::from)?;
+        }
+
+        if previous_status != Some(status) {
+            record_transition(&mut *tx, &id, previous_status, status, &user.id, "").await?;
+        }
+
+        // the edits are saved now, so the user's autosaved draft is no longer needed;
+        // anyone else's still holds edits that aren't in the post
+        sqlx::query("DELETE FROM post_draft WHERE post_id = ? AND user_id = ?")
+            .bind(&draft_key)
+            .bind(&user.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::from)?;
+
+        tx.commit().await.map_err(AppError::from)?;
 
-    Ok(id)
+        let listed = previous_status == Some(PostStatus::Published) || status == PostStatus::Published;
+        response_cache().await?.invalidate_post(&id, listed);
+
+        let event = if previous_status.is_none() { PostEvent::Created } else { PostEvent::Updated };
+        post_event(&pool, event, &id).await;
+        if status == PostStatus::Published && previous_status != Some(PostStatus::Published) {
+            post_event(&pool, PostEvent::Published, &id).await;
+            post_published(&pool, &id).await;
+        }
+
+        Ok(id)
+    })
+    .await
 }
 
 #[server(GetPost, "/api")]
 pub async fn get_post(id: String) -> Result<Post, ServerFnError> {
-    log!("get_post {:?}", &id);
-    let pool: Arc<Pool<Sqlite>> =
-        extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
-    let res: Post = sqlx::query_as("SELECT * FROM post WHERE id = ?")
-        .bind(id)
-        .fetch_one(&*pool)
+    traced("GetPost", async move {
+        tracing::debug!(?id);
+        let cache = response_cache().await?;
+        depends_on([Dependency::Post(id.clone())]);
+        if let Some(post) = cache.posts.get(&id) {
+            return Ok(post);
+        }
+        let generation = cache.posts.generation();
+
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        let res: Option<Post> = timed_query(
+            "get_post",
+            sqlx::query_as(&format!(
+                "{} WHERE post.id = ? AND post.deleted_at IS NULL",
+                POST_SELECT
+            ))
+            .bind(&id)
+            .fetch_optional(&*pool),
+        )
         .await
-        .map_err(|_| ServerFnError::ServerError("error getting post".to_owned()))?;
+        .map_err(AppError::from)?;
+
+        // unpublished posts only exist for the people who can edit them
+        let visible = match &res {
+            Some(post) if post.status != PostStatus::Published => auth::current_user()
+                .await?
+                .map_or(false, |user| user.can_edit_post(post)),
+            _ => true,
+        };
+
+        let Some(mut post) = res.filter(|_| visible) else {
+            return Err(AppError::NotFound(format!("no post with id {}", id)).into());
+        };
+        post.tags = sqlx::query_scalar("SELECT tag FROM post_tag WHERE post_id = ? ORDER BY tag")
+            .bind(&id)
+            .fetch_all(&*pool)
+            .await
+            .map_err(AppError::from)?;
+        post.update_reading_stats();
+        let images = image_policy().await?;
+        images.set_post_srcs(&mut post);
+        post.paragraphs_html = paragraphs_html(&post.text, &images);
 
-    Ok(res)
+        // unpublished posts depend on who is asking, so only published ones are shared
+        if post.status == PostStatus::Published {
+            let dependencies = [Dependency::Post(id.clone())].into();
+            cache
+                .posts
+                .insert(id, post.clone(), dependencies, generation);
+        }
+
+        Ok(post)
+    })
+    .await
+}
+
+/// What [`Post::paragraphs_html`] would be for `text`, for the editor's preview.
+#[server(RenderParagraphs, "/api")]
+pub async fn render_paragraphs(text: String) -> Result<Vec<String>, ServerFnError> {
+    traced("RenderParagraphs", async move {
+        auth::require_user().await?;
+        Ok(paragraphs_html(&text, &image_policy().await?))
+    })
+    .await
 }
 
+/// Moves a post to the trash. It can be restored until it is purged.
 #[server(DeletePost, "/api")]
 pub async fn delete_post(id: String) -> Result<(), ServerFnError> {
-    log!("delete_post {:?}", &id);
-    let pool: Arc<Pool<Sqlite>> =
-        extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+    traced("DeletePost", async move {
+        tracing::debug!(?id);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
 
-    sqlx::query("DELETE FROM post WHERE ID = ?")
-        .bind(id)
+        let user = auth::require_user().await?;
+        let existing = find_post(&pool, &id).await?;
+        match &existing {
+            Some(post) if !user.can_delete_post(post) => {
+                return Err(
+                    AppError::Unauthorized("you can't delete this post".to_string()).into(),
+                );
+            }
+            _ => {}
+        }
+
+        let result = timed_query(
+            "delete_post",
+            sqlx::query("UPDATE post SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL")
+                .bind(chrono::Local::now().naive_local())
+                .bind(&id)
+                .execute(&*pool),
+        )
+        .await
+        .map_err(AppError::from)?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("no post with id {}", id)).into());
+        }
+
+        let listed = existing.map_or(false, |post| post.status == PostStatus::Published);
+        response_cache().await?.invalidate_post(&id, listed);
+
+        post_event(&pool, PostEvent::Deleted, &id).await;
+
+        Ok(())
+    })
+    .await
+}
+
+#[server(RestorePost, "/api")]
+pub async fn restore_post(id: String, _csrf: String) -> Result<String, ServerFnError> {
+    traced("RestorePost", async move {
+        tracing::debug!(?id);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        // whoever could delete a post can undo that
+        let user = auth::require_user().await?;
+        let existing = find_post(&pool, &id).await?;
+        match &existing {
+            Some(post) if !user.can_manage_trash() && !user.can_delete_post(post) => {
+                return Err(
+                    AppError::Unauthorized("you can't restore this post".to_string()).into(),
+                );
+            }
+            _ => {}
+        }
+
+        let result = sqlx::query(
+            "UPDATE post SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL",
+        )
+        .bind(&id)
         .execute(&*pool)
         .await
-        .map_err(|_| ServerFnError::ServerError("error deleting post".to_owned()))?;
+        .map_err(AppError::from)?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("no deleted post with id {}", id)).into());
+        }
+
+        let listed = existing.map_or(false, |post| post.status == PostStatus::Published);
+        response_cache().await?.invalidate_post(&id, listed);
+
+        Ok(id)
+    })
+    .await
+}
+
+/// Permanently removes a post that is in the trash.
+#[server(PurgePost, "/api")]
+pub async fn purge_post(id: String) -> Result<(), ServerFnError> {
+    traced("PurgePost", async move {
+        tracing::debug!(?id);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        require_trash_manager().await?;
+
+        let result = sqlx::query("DELETE FROM post WHERE id = ? AND deleted_at IS NOT NULL")
+            .bind(&id)
+            .execute(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("no deleted post with id {}", id)).into());
+        }
+
+        sqlx::query("DELETE FROM post_draft WHERE post_id = ?")
+            .bind(&id)
+            .execute(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[server(GetDeletedPosts, "/api")]
+pub async fn get_deleted_posts() -> Result<Vec<Post>, ServerFnError> {
+    traced("GetDeletedPosts", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        require_trash_manager().await?;
+
+        let res: Vec<Post> = sqlx::query_as(&format!(
+            "{} WHERE post.deleted_at IS NOT NULL ORDER BY post.deleted_at DESC",
+            POST_SELECT
+        ))
+        .fetch_all(&*pool)
+        .await
+        .map_err(AppError::from)?;
 
-    Ok(())
+        Ok(res)
+    })
+    .await
 }
 
+/// Unpublished posts the current user can work on: their own, or everyone's for editors.
+#[server(GetUnpublishedPosts, "/api")]
+pub async fn get_unpublished_posts() -> Result<Vec<Post>, ServerFnError> {
+    traced("GetUnpublishedPosts", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        let user = auth::require_user().await?;
+
+        let posts: Vec<Post> = sqlx::query_as(&format!(
+            "{} WHERE post.deleted_at IS NULL AND post.status != 'published' ORDER BY post.dt DESC",
+            POST_SELECT
+        ))
+        .fetch_all(&*pool)
+        .await
+        .map_err(AppError::from)?;
+
+        Ok(posts
+            .into_iter()
+            .filter(|post| user.can_edit_post(post))
+            .collect())
+    })
+    .await
+}
+
+#[cfg(feature = "ssr")]
+async fn require_trash_manager() -> Result<CurrentUser, ServerFnError> {
+    let user = auth::require_user().await?;
+    if !user.can_manage_trash() {
+        return Err(AppError::Unauthorized("only editors can manage the trash".to_string()).into());
+    }
+    Ok(user)
+}
+
+/// Permanently removes every post that was moved to the trash before `cutoff`.
+#[cfg(feature = "ssr")]
+pub async fn purge_deleted_before(
+    pool: &Pool<Sqlite>,
+    cutoff: chrono::NaiveDateTime,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM post WHERE deleted_at IS NOT NULL AND deleted_at < ?")
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        "DELETE FROM post_draft WHERE post_id != ? AND post_id NOT IN (SELECT id FROM post)",
+    )
+    .bind(crate::model::draft::NEW_POST_DRAFT_KEY)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Replaces the text of a post with what a preview card shows: its own excerpt,
+/// or else the start of the text without markup, at most `preview_length` characters long.
+/// The reading time needs the whole text, so it is worked out first.
+#[cfg(feature = "ssr")]
+pub(crate) fn make_preview(post: &mut Post, preview_length: u8) {
+    post.update_reading_stats();
+    post.text = match post.excerpt.as_deref().map(str::trim) {
+        Some(own) if !own.is_empty() => own.to_string(),
+        _ => excerpt(&post.text, preview_length as usize),
+    };
+}
+
+/// Previews of the newest published posts, optionally only those by the author with the given
+/// handle.
 #[server(GetPreviews, "/api")]
 pub async fn get_previews(
     oldest: Option<String>,
     newest: Option<String>,
+    author_handle: Option<String>,
     preview_length: u8,
     page_size: u8,
 ) -> Result<Vec<Post>, ServerFnError> {
-    log!(
-        "get_previews {:?}, {:?}, {}, {}",
-        oldest,
-        newest,
-        preview_length,
-        page_size
-    );
-    let pool: Arc<Pool<Sqlite>> =
-        extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
-    let res: Vec<Post> = sqlx::query_as(
-        "SELECT
-            id, dt, image_url, title,
-            CASE
-                WHEN LENGTH(text) > $1 THEN SUBSTR(text, 0, $1) || '...'
-                ELSE text
-            END AS text
-        FROM post
-        ORDER BY dt DESC
-        LIMIT $2",
-    )
-    .bind(preview_length)
-    .bind(page_size)
-    .fetch_all(&*pool)
-    .await?;
+    traced("GetPreviews", async move {
+        tracing::debug!(?oldest, ?newest, ?author_handle, preview_length, page_size);
+        let cache = response_cache().await?;
+        depends_on([Dependency::PublishedPosts]);
+        let key = format!("{:?} {:?} {:?} {} {}", oldest, newest, author_handle, preview_length, page_size);
+        if let Some(previews) = cache.previews.get(&key) {
+            return Ok(previews);
+        }
+        let generation = cache.previews.generation();
+
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        let mut res: Vec<Post> = timed_query("get_previews", sqlx::query_as(&format!(
+            "{} WHERE post.deleted_at IS NULL AND post.status = 'published' AND ($1 IS NULL OR user.handle = $1) ORDER BY post.dt DESC LIMIT $2",
+            POST_SELECT
+        ))
+        .bind(author_handle)
+        .bind(page_size)
+        .fetch_all(&*pool))
+        .await
+        .map_err(AppError::from)?;
+
+        let images = image_policy().await?;
+        for post in res.iter_mut() {
+            make_preview(post, preview_length);
+            images.set_post_srcs(post);
+        }
+        cache
+            .previews
+            .insert(key, res.clone(), [Dependency::PublishedPosts].into(), generation);
 
-    // Err(ServerFnError::ServerError("forced error".to_string()))
-    Ok(res)
+        // Err(ServerFnError::ServerError("forced error".to_string()))
+        Ok(res)
+    })
+    .await
 }
@@ -0,0 +1,95 @@
+use crate::error::AppError;
+use crate::model::draft::Draft;
+use std::sync::Arc;
+
+#[cfg(feature = "ssr")]
+use crate::repository::blog_repository::require_can_edit;
+#[cfg(feature = "ssr")]
+use crate::server::telemetry::traced;
+#[cfg(feature = "ssr")]
+use actix_web::web::Data;
+#[cfg(feature = "ssr")]
+use sqlx::{Pool, Sqlite};
+
+use leptos::*;
+#[cfg(feature = "ssr")]
+use leptos_actix::extract;
+
+#[server(SaveDraft, "/api")]
+pub async fn save_draft(
+    post_id: String,
+    base_version: i64,
+    dt: String,
+    image_url: String,
+    title: String,
+    text: String,
+    _csrf: String,
+) -> Result<(), ServerFnError> {
+    traced("SaveDraft", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        let user = require_can_edit(&pool, &post_id).await?;
+
+        let dt = chrono::NaiveDateTime::parse_from_str(&dt, "%Y-%m-%dT%H:%M")
+            .map_err(|_| AppError::Validation(format!("invalid date {:?}", dt)))?;
+        let saved_at = chrono::Local::now().naive_local();
+
+        sqlx::query("INSERT INTO post_draft (post_id, user_id, base_version, dt, image_url, title, text, saved_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (post_id, user_id) DO UPDATE SET base_version=excluded.base_version, dt=excluded.dt, image_url=excluded.image_url, title=excluded.title, text=excluded.text, saved_at=excluded.saved_at")
+            .bind(Draft::key_for(&post_id))
+            .bind(&user.id)
+            .bind(base_version)
+            .bind(dt)
+            .bind(&image_url)
+            .bind(&title)
+            .bind(&text)
+            .bind(saved_at)
+            .execute(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[server(GetDraft, "/api")]
+pub async fn get_draft(post_id: String) -> Result<Option<Draft>, ServerFnError> {
+    traced("GetDraft", async move {
+        tracing::debug!(?post_id);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        let user = require_can_edit(&pool, &post_id).await?;
+
+        // only ever the user's own edits, which nobody else has seen yet
+        let res: Option<Draft> =
+            sqlx::query_as("SELECT * FROM post_draft WHERE post_id = ? AND user_id = ?")
+                .bind(Draft::key_for(&post_id))
+                .bind(&user.id)
+                .fetch_optional(&*pool)
+                .await
+                .map_err(AppError::from)?;
+
+        Ok(res)
+    })
+    .await
+}
+
+#[server(DiscardDraft, "/api")]
+pub async fn discard_draft(post_id: String, _csrf: String) -> Result<(), ServerFnError> {
+    traced("DiscardDraft", async move {
+        tracing::debug!(?post_id);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        let user = require_can_edit(&pool, &post_id).await?;
+
+        sqlx::query("DELETE FROM post_draft WHERE post_id = ? AND user_id = ?")
+            .bind(Draft::key_for(&post_id))
+            .bind(&user.id)
+            .execute(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+        Ok(())
+    })
+    .await
+}
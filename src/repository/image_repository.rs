@@ -0,0 +1,19 @@
+#[cfg(feature = "ssr")]
+use crate::server::auth;
+#[cfg(feature = "ssr")]
+use crate::server::images::image_policy;
+#[cfg(feature = "ssr")]
+use crate::server::telemetry::traced;
+
+use leptos::*;
+
+/// The `src` an image URL that hasn't been saved yet would be shown with, for the editor's
+/// preview. Only for people who can log in, as it signs URLs for the image proxy.
+#[server(GetImageSrc, "/api")]
+pub async fn get_image_src(url: String) -> Result<Option<String>, ServerFnError> {
+    traced("GetImageSrc", async move {
+        auth::require_user().await?;
+        Ok(image_policy().await?.src(&url))
+    })
+    .await
+}
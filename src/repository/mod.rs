@@ -1 +1,10 @@
+pub mod author_repository;
 pub mod blog_repository;
+pub mod draft_repository;
+pub mod image_repository;
+pub mod navigation_repository;
+pub mod newsletter_repository;
+pub mod review_repository;
+pub mod settings_repository;
+pub mod user_repository;
+pub mod webhook_repository;
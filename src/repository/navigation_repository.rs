@@ -0,0 +1,185 @@
+use crate::error::AppError;
+use crate::model::blog_post::AdjacentPosts;
+use crate::model::blog_post::Post;
+use std::sync::Arc;
+
+#[cfg(feature = "ssr")]
+use crate::model::blog_post::{PostLink, PostStatus};
+#[cfg(feature = "ssr")]
+use crate::repository::blog_repository::{find_post, make_preview};
+#[cfg(feature = "ssr")]
+use crate::server::cache::{depends_on, Dependency};
+#[cfg(feature = "ssr")]
+use crate::server::images::image_policy;
+#[cfg(feature = "ssr")]
+use crate::server::telemetry::traced;
+#[cfg(feature = "ssr")]
+use actix_web::web::Data;
+#[cfg(feature = "ssr")]
+use sqlx::{Pool, Sqlite};
+#[cfg(feature = "ssr")]
+use std::collections::HashMap;
+
+use leptos::*;
+#[cfg(feature = "ssr")]
+use leptos_actix::extract;
+
+/// How many distinct words of a post go into the full-text query for similar posts.
+#[cfg(feature = "ssr")]
+const SIMILARITY_TERMS: usize = 12;
+
+#[cfg(feature = "ssr")]
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "again", "also", "been", "before", "being", "could", "from", "have", "here",
+    "into", "just", "like", "more", "most", "much", "only", "other", "over", "some", "such",
+    "than", "that", "their", "them", "then", "there", "these", "they", "this", "very", "were",
+    "what", "when", "where", "which", "while", "will", "with", "would", "your",
+];
+
+/// An FTS5 query matching any of the words that best describe the post:
+/// its most frequent words, with words in the title counting extra.
+#[cfg(feature = "ssr")]
+fn similarity_query(post: &Post) -> Option<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for (text, weight) in [(&post.title, 3), (&post.text, 1)] {
+        for word in text.split(|c: char| !c.is_alphanumeric()) {
+            let word = word.to_lowercase();
+            if word.chars().count() >= 4 && !STOP_WORDS.contains(&word.as_str()) {
+                *counts.entry(word).or_default() += weight;
+            }
+        }
+    }
+
+    let mut words: Vec<(String, usize)> = counts.into_iter().collect();
+    words.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    let terms: Vec<String> = words
+        .into_iter()
+        .take(SIMILARITY_TERMS)
+        .map(|(word, _)| format!("\"{}\"", word))
+        .collect();
+
+    (!terms.is_empty()).then(|| terms.join(" OR "))
+}
+
+/// The published posts dated just before and just after the given post.
+#[server(GetAdjacentPosts, "/api")]
+pub async fn get_adjacent_posts(id: String) -> Result<AdjacentPosts, ServerFnError> {
+    traced("GetAdjacentPosts", async move {
+        tracing::debug!(?id);
+        depends_on([Dependency::PublishedPosts]);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        // posts on the same date are ordered by id, so none are skipped
+        let previous: Option<PostLink> = sqlx::query_as(
+            "SELECT id, title FROM post
+            WHERE status = 'published' AND deleted_at IS NULL
+                AND (dt < (SELECT dt FROM post WHERE id = $1) OR (dt = (SELECT dt FROM post WHERE id = $1) AND id < $1))
+            ORDER BY dt DESC, id DESC
+            LIMIT 1",
+        )
+        .bind(&id)
+        .fetch_optional(&*pool)
+        .await
+        .map_err(AppError::from)?;
+
+        let next: Option<PostLink> = sqlx::query_as(
+            "SELECT id, title FROM post
+            WHERE status = 'published' AND deleted_at IS NULL
+                AND (dt > (SELECT dt FROM post WHERE id = $1) OR (dt = (SELECT dt FROM post WHERE id = $1) AND id > $1))
+            ORDER BY dt ASC, id ASC
+            LIMIT 1",
+        )
+        .bind(&id)
+        .fetch_optional(&*pool)
+        .await
+        .map_err(AppError::from)?;
+
+        Ok(AdjacentPosts { previous, next })
+    })
+    .await
+}
+
+/// Previews of published posts like the given one.
+///
+/// Each shared tag counts one point, and full-text similarity of title and text
+/// adds up to one more, so tags decide and the text breaks ties.
+#[server(GetRelatedPosts, "/api")]
+pub async fn get_related_posts(
+    id: String,
+    preview_length: u8,
+    limit: u8,
+) -> Result<Vec<Post>, ServerFnError> {
+    traced("GetRelatedPosts", async move {
+        tracing::debug!(?id);
+        depends_on([Dependency::PublishedPosts]);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        let Some(post) = find_post(&pool, &id).await? else {
+            return Err(AppError::NotFound(format!("no post with id {}", id)).into());
+        };
+
+        let mut scores: HashMap<String, f64> = HashMap::new();
+
+        let shared_tags: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT other.post_id, COUNT(*) FROM post_tag AS mine
+            JOIN post_tag AS other ON other.tag = mine.tag AND other.post_id != mine.post_id
+            WHERE mine.post_id = ?
+            GROUP BY other.post_id",
+        )
+        .bind(&id)
+        .fetch_all(&*pool)
+        .await
+        .map_err(AppError::from)?;
+        for (other_id, shared) in shared_tags {
+            *scores.entry(other_id).or_default() += shared as f64;
+        }
+
+        if let Some(query) = similarity_query(&post) {
+            // bm25 is negative, and more negative for better matches; the title weighs more than
+            // the text
+            let matches: Vec<(String, f64)> = sqlx::query_as(
+                "SELECT post.id, bm25(post_fts, 5.0, 1.0) AS score FROM post_fts
+                JOIN post ON post.rowid = post_fts.rowid
+                WHERE post_fts MATCH ? AND post.id != ?
+                ORDER BY score
+                LIMIT 50",
+            )
+            .bind(&query)
+            .bind(&id)
+            .fetch_all(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+            let best = matches.iter().map(|(_, rank)| -rank).fold(0.0, f64::max);
+            if best > 0.0 {
+                for (other_id, rank) in matches {
+                    *scores.entry(other_id).or_default() += -rank / best;
+                }
+            }
+        }
+
+        let mut ranked: Vec<(String, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|(a, a_score), (b, b_score)| b_score.total_cmp(a_score).then(a.cmp(b)));
+
+        // candidates may be unpublished or in the trash, so keep going until there are enough
+        let images = image_policy().await?;
+        let mut related = Vec::new();
+        for (other_id, _) in ranked {
+            if related.len() >= limit as usize {
+                break;
+            }
+            if let Some(mut other) = find_post(&pool, &other_id).await? {
+                if other.status == PostStatus::Published && other.deleted_at.is_none() {
+                    make_preview(&mut other, preview_length);
+                    images.set_post_srcs(&mut other);
+                    related.push(other);
+                }
+            }
+        }
+
+        Ok(related)
+    })
+    .await
+}
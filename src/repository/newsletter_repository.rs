@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+#[cfg(feature = "ssr")]
+use crate::server::newsletter;
+#[cfg(feature = "ssr")]
+use crate::server::telemetry::traced;
+#[cfg(feature = "ssr")]
+use actix_web::web::Data;
+#[cfg(feature = "ssr")]
+use sqlx::{Pool, Sqlite};
+
+use leptos::*;
+#[cfg(feature = "ssr")]
+use leptos_actix::extract;
+
+/// Sends a confirmation email to `email`; it is only subscribed once that is followed.
+#[server(Subscribe, "/api")]
+pub async fn subscribe(email: String) -> Result<(), ServerFnError> {
+    traced("Subscribe", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        let config = newsletter::config().await?;
+
+        newsletter::subscribe(&pool, &config, &email).await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Whether readers can subscribe, so the form is only shown when they can.
+#[server(GetNewsletterEnabled, "/api")]
+pub async fn get_newsletter_enabled() -> Result<bool, ServerFnError> {
+    traced("GetNewsletterEnabled", async move {
+        Ok(newsletter::config().await?.is_enabled())
+    })
+    .await
+}
+
+#[server(ConfirmSubscription, "/api")]
+pub async fn confirm_subscription(token: String) -> Result<(), ServerFnError> {
+    traced("ConfirmSubscription", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        newsletter::confirm(&pool, &token).await?;
+        Ok(())
+    })
+    .await
+}
+
+#[server(Unsubscribe, "/api")]
+pub async fn unsubscribe(token: String) -> Result<(), ServerFnError> {
+    traced("Unsubscribe", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        newsletter::unsubscribe(&pool, &token).await?;
+        Ok(())
+    })
+    .await
+}
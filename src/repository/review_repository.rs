@@ -0,0 +1,283 @@
+use crate::error::AppError;
+use crate::model::blog_post::Post;
+use crate::model::review::ReviewNote;
+use crate::model::review::StatusTransition;
+use std::sync::Arc;
+
+#[cfg(feature = "ssr")]
+use crate::model::blog_post::PostStatus;
+#[cfg(feature = "ssr")]
+use crate::model::user::CurrentUser;
+#[cfg(feature = "ssr")]
+use crate::repository::blog_repository::{find_post, POST_SELECT};
+#[cfg(feature = "ssr")]
+use crate::server::auth;
+#[cfg(feature = "ssr")]
+use crate::server::cache::response_cache;
+#[cfg(feature = "ssr")]
+use crate::server::images::image_policy;
+#[cfg(feature = "ssr")]
+use crate::server::newsletter::post_published;
+#[cfg(feature = "ssr")]
+use crate::server::telemetry::traced;
+#[cfg(feature = "ssr")]
+use crate::server::webhooks::{post_event, PostEvent};
+#[cfg(feature = "ssr")]
+use actix_web::web::Data;
+#[cfg(feature = "ssr")]
+use sqlx::{Pool, Sqlite, SqliteExecutor};
+
+use leptos::*;
+#[cfg(feature = "ssr")]
+use leptos_actix::extract;
+
+/// Records a change of a post's status. `from` is `None` for a new post.
+#[cfg(feature = "ssr")]
+pub(crate) async fn record_transition(
+    conn: impl SqliteExecutor<'_>,
+    post_id: &str,
+    from: Option<PostStatus>,
+    to: PostStatus,
+    actor_id: &str,
+    note: &str,
+) -> Result<(), AppError> {
+    sqlx::query("INSERT INTO post_transition (post_id, from_status, to_status, actor_id, note, created_at) VALUES ($1, $2, $3, $4, $5, $6)")
+        .bind(post_id)
+        .bind(from)
+        .bind(to)
+        .bind(actor_id)
+        .bind(note.trim())
+        .bind(chrono::Local::now().naive_local())
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// The current user and the post, provided the user may edit or review it.
+#[cfg(feature = "ssr")]
+async fn require_participant(
+    pool: &Pool<Sqlite>,
+    post_id: &str,
+) -> Result<(CurrentUser, Post), ServerFnError> {
+    let user = auth::require_user().await?;
+    let post = find_post(pool, post_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("no post with id {}", post_id)))?;
+    if !user.can_edit_post(&post) && !user.is_reviewer() {
+        return Err(
+            AppError::Unauthorized("you can't see the review of this post".to_string()).into(),
+        );
+    }
+    Ok((user, post))
+}
+
+/// Moves a post that is pending review on to `to`, on behalf of a reviewer.
+#[cfg(feature = "ssr")]
+async fn conclude_review(id: String, to: PostStatus, note: String) -> Result<(), ServerFnError> {
+    let pool: Arc<Pool<Sqlite>> =
+        extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+    let user = auth::require_user().await?;
+    if !user.is_reviewer() {
+        return Err(AppError::Unauthorized("only editors can review posts".to_string()).into());
+    }
+
+    // bumping the version makes any edit started before the review conflict instead of undoing it
+    let result = sqlx::query("UPDATE post SET status = ?, version = version + 1 WHERE id = ? AND status = ? AND deleted_at IS NULL")
+        .bind(to)
+        .bind(&id)
+        .bind(PostStatus::PendingReview)
+        .execute(&*pool)
+        .await
+        .map_err(AppError::from)?;
+
+    if result.rows_affected() == 0 {
+        return Err(match find_post(&pool, &id).await? {
+            Some(post) if post.deleted_at.is_none() => AppError::Conflict(format!(
+                "this post is no longer pending review ({})",
+                post.status.label()
+            )),
+            _ => AppError::NotFound(format!("no post with id {}", id)),
+        }
+        .into());
+    }
+
+    record_transition(
+        &*pool,
+        &id,
+        Some(PostStatus::PendingReview),
+        to,
+        &user.id,
+        &note,
+    )
+    .await?;
+    response_cache()
+        .await?
+        .invalidate_post(&id, to == PostStatus::Published);
+    if to == PostStatus::Published {
+        post_event(&pool, PostEvent::Published, &id).await;
+        post_published(&pool, &id).await;
+    }
+    Ok(())
+}
+
+/// Posts waiting for a reviewer, oldest submission first.
+#[server(GetReviewQueue, "/api")]
+pub async fn get_review_queue() -> Result<Vec<Post>, ServerFnError> {
+    traced("GetReviewQueue", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        if !auth::require_user().await?.is_reviewer() {
+            return Err(AppError::Unauthorized("only editors can review posts".to_string()).into());
+        }
+
+        let mut res: Vec<Post> = sqlx::query_as(&format!(
+            "{} WHERE post.status = 'pending_review' AND post.deleted_at IS NULL ORDER BY (SELECT MAX(created_at) FROM post_transition WHERE post_transition.post_id = post.id)",
+            POST_SELECT
+        ))
+        .fetch_all(&*pool)
+        .await
+        .map_err(AppError::from)?;
+
+        let images = image_policy().await?;
+        for post in res.iter_mut() {
+            images.set_post_srcs(post);
+        }
+        Ok(res)
+    })
+    .await
+}
+
+/// Publishes a post that is pending review.
+#[server(ApprovePost, "/api")]
+pub async fn approve_post(id: String, note: String) -> Result<(), ServerFnError> {
+    traced("ApprovePost", async move {
+        tracing::debug!(?id);
+        conclude_review(id, PostStatus::Published, note).await
+    })
+    .await
+}
+
+/// Sends a post that is pending review back to its writer.
+#[server(RequestChanges, "/api")]
+pub async fn request_changes(id: String, note: String) -> Result<(), ServerFnError> {
+    traced("RequestChanges", async move {
+        tracing::debug!(?id);
+        if note.trim().is_empty() {
+            return Err(AppError::Validation("say what needs to change".to_string()).into());
+        }
+        conclude_review(id, PostStatus::ChangesRequested, note).await
+    })
+    .await
+}
+
+#[server(GetReviewNotes, "/api")]
+pub async fn get_review_notes(post_id: String) -> Result<Vec<ReviewNote>, ServerFnError> {
+    traced("GetReviewNotes", async move {
+        tracing::debug!(?post_id);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        require_participant(&pool, &post_id).await?;
+
+        let res: Vec<ReviewNote> = sqlx::query_as(
+            "SELECT review_note.*, user.display_name AS author_name FROM review_note LEFT JOIN user ON user.id = review_note.author_id WHERE review_note.post_id = ? ORDER BY review_note.paragraph, review_note.created_at",
+        )
+        .bind(&post_id)
+        .fetch_all(&*pool)
+        .await
+        .map_err(AppError::from)?;
+
+        Ok(res)
+    })
+    .await
+}
+
+#[server(AddReviewNote, "/api")]
+pub async fn add_review_note(
+    post_id: String,
+    paragraph: i64,
+    body: String,
+) -> Result<(), ServerFnError> {
+    traced("AddReviewNote", async move {
+        tracing::debug!(?post_id, paragraph);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        let (user, post) = require_participant(&pool, &post_id).await?;
+        if !user.is_reviewer() {
+            return Err(AppError::Unauthorized("only editors can leave review notes".to_string()).into());
+        }
+
+        if body.trim().is_empty() {
+            return Err(AppError::Validation("a note can't be empty".to_string()).into());
+        }
+        let quote = usize::try_from(paragraph)
+            .ok()
+            .and_then(|index| post.paragraphs().into_iter().nth(index))
+            .ok_or_else(|| AppError::Validation(format!("the post has no paragraph {}", paragraph)))?;
+
+        sqlx::query("INSERT INTO review_note (post_id, paragraph, quote, body, author_id, created_at) VALUES ($1, $2, $3, $4, $5, $6)")
+            .bind(&post_id)
+            .bind(paragraph)
+            .bind(&quote)
+            .bind(body.trim())
+            .bind(&user.id)
+            .bind(chrono::Local::now().naive_local())
+            .execute(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Marks a note as dealt with. Either the writer or a reviewer can do this.
+#[server(ResolveReviewNote, "/api")]
+pub async fn resolve_review_note(id: i64) -> Result<(), ServerFnError> {
+    traced("ResolveReviewNote", async move {
+        tracing::debug!(id);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        let post_id: Option<(String,)> =
+            sqlx::query_as("SELECT post_id FROM review_note WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&*pool)
+                .await
+                .map_err(AppError::from)?;
+        let Some((post_id,)) = post_id else {
+            return Err(AppError::NotFound(format!("no review note with id {}", id)).into());
+        };
+        require_participant(&pool, &post_id).await?;
+
+        sqlx::query("UPDATE review_note SET resolved = 1 WHERE id = ?")
+            .bind(id)
+            .execute(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+        Ok(())
+    })
+    .await
+}
+
+/// Every status change of a post, newest first.
+#[server(GetPostHistory, "/api")]
+pub async fn get_post_history(post_id: String) -> Result<Vec<StatusTransition>, ServerFnError> {
+    traced("GetPostHistory", async move {
+        tracing::debug!(?post_id);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+        require_participant(&pool, &post_id).await?;
+
+        let res: Vec<StatusTransition> = sqlx::query_as(
+            "SELECT post_transition.*, user.display_name AS actor_name FROM post_transition LEFT JOIN user ON user.id = post_transition.actor_id WHERE post_transition.post_id = ? ORDER BY post_transition.id DESC",
+        )
+        .bind(&post_id)
+        .fetch_all(&*pool)
+        .await
+        .map_err(AppError::from)?;
+
+        Ok(res)
+    })
+    .await
+}
@@ -0,0 +1,78 @@
+use crate::error::AppError;
+use crate::model::setting::Settings;
+use std::sync::Arc;
+
+#[cfg(feature = "ssr")]
+use crate::server::auth;
+#[cfg(feature = "ssr")]
+use crate::server::cache::response_cache;
+#[cfg(feature = "ssr")]
+use crate::server::telemetry::traced;
+#[cfg(feature = "ssr")]
+use actix_web::web::Data;
+#[cfg(feature = "ssr")]
+use sqlx::{Pool, Sqlite};
+
+use leptos::*;
+#[cfg(feature = "ssr")]
+use leptos_actix::extract;
+
+#[server(GetSettings, "/api")]
+pub async fn get_settings() -> Result<Settings, ServerFnError> {
+    traced("GetSettings", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        let rows: Vec<(String, String)> = sqlx::query_as("SELECT key, value FROM setting")
+            .fetch_all(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+        // anything that was never set keeps its default
+        let mut settings = Settings::default();
+        for (key, value) in rows {
+            match key.as_str() {
+                "blog_title" => settings.blog_title = value,
+                "blog_tagline" => settings.blog_tagline = value,
+                _ => {}
+            }
+        }
+
+        Ok(settings)
+    })
+    .await
+}
+
+#[server(UpdateSettings, "/api")]
+pub async fn update_settings(
+    blog_title: String,
+    blog_tagline: String,
+) -> Result<(), ServerFnError> {
+    traced("UpdateSettings", async move {
+        tracing::debug!(?blog_title, ?blog_tagline);
+        let user = auth::require_user().await?;
+        if !user.can_manage_settings() {
+            return Err(AppError::Unauthorized("only admins can change settings".to_string()).into());
+        }
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        if blog_title.trim().is_empty() {
+            return Err(AppError::Validation("the blog needs a title".to_string()).into());
+        }
+
+        for (key, value) in [("blog_title", blog_title.trim()), ("blog_tagline", blog_tagline.trim())] {
+            sqlx::query("INSERT INTO setting (key, value) VALUES ($1, $2) ON CONFLICT (key) DO UPDATE SET value=excluded.value")
+                .bind(key)
+                .bind(value)
+                .execute(&*pool)
+                .await
+                .map_err(AppError::from)?;
+        }
+        // the title and tagline are on every page
+        response_cache().await?.clear();
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,175 @@
+use crate::error::AppError;
+use crate::model::user::CurrentUser;
+#[cfg(feature = "ssr")]
+use crate::model::user::Role;
+use crate::model::user::UserSummary;
+use std::sync::Arc;
+
+#[cfg(feature = "ssr")]
+use crate::server::auth;
+#[cfg(feature = "ssr")]
+use crate::server::telemetry::traced;
+#[cfg(feature = "ssr")]
+use actix_web::web::Data;
+#[cfg(feature = "ssr")]
+use sqlx::{Pool, Sqlite};
+
+use leptos::*;
+#[cfg(feature = "ssr")]
+use leptos_actix::extract;
+
+#[server(Login, "/api")]
+pub async fn login(handle: String, password: String) -> Result<CurrentUser, ServerFnError> {
+    traced("Login", async move {
+        tracing::debug!(?handle);
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        let row: Option<(String, String, String, Role, Option<String>)> = sqlx::query_as(
+            "SELECT id, handle, display_name, role, password_hash FROM user WHERE handle = ?",
+        )
+        .bind(handle.trim().to_lowercase())
+        .fetch_optional(&*pool)
+        .await
+        .map_err(AppError::from)?;
+
+        // the same error whether the handle or the password is wrong
+        let Some((id, handle, display_name, role, Some(password_hash))) = row else {
+            auth::verify_dummy_password(&password);
+            return Err(AppError::Unauthorized("wrong handle or password".to_string()).into());
+        };
+        if !auth::verify_password(&password, &password_hash) {
+            return Err(AppError::Unauthorized("wrong handle or password".to_string()).into());
+        }
+
+        auth::start_session(&pool, &id).await?;
+
+        Ok(CurrentUser {
+            id,
+            handle,
+            display_name,
+            role,
+        })
+    })
+    .await
+}
+
+#[server(Logout, "/api")]
+pub async fn logout() -> Result<(), ServerFnError> {
+    traced("Logout", async move {
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        auth::end_session(&pool).await?;
+        Ok(())
+    })
+    .await
+}
+
+#[server(GetCurrentUser, "/api")]
+pub async fn get_current_user() -> Result<Option<CurrentUser>, ServerFnError> {
+    traced("GetCurrentUser", async move { auth::current_user().await }).await
+}
+
+#[server(GetCsrfToken, "/api")]
+pub async fn get_csrf_token() -> Result<Option<String>, ServerFnError> {
+    traced("GetCsrfToken", async move { auth::csrf_token().await }).await
+}
+
+#[server(GetUsers, "/api")]
+pub async fn get_users() -> Result<Vec<UserSummary>, ServerFnError> {
+    traced("GetUsers", async move {
+        require_user_admin().await?;
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        let res: Vec<UserSummary> = sqlx::query_as(
+            "SELECT id, handle, display_name, role, password_hash IS NOT NULL AS has_password FROM user ORDER BY display_name",
+        )
+        .fetch_all(&*pool)
+        .await
+        .map_err(AppError::from)?;
+
+        Ok(res)
+    })
+    .await
+}
+
+#[server(SetUserRole, "/api")]
+pub async fn set_user_role(id: String, role: String) -> Result<(), ServerFnError> {
+    traced("SetUserRole", async move {
+        tracing::debug!(?id, ?role);
+        let admin = require_user_admin().await?;
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        let role = Role::parse(&role)
+            .ok_or_else(|| AppError::Validation(format!("unknown role {:?}", role)))?;
+        if admin.id == id && role != Role::Admin {
+            return Err(AppError::Validation(
+                "you can't take away your own admin role".to_string(),
+            )
+            .into());
+        }
+
+        let result = sqlx::query("UPDATE user SET role = ? WHERE id = ?")
+            .bind(role)
+            .bind(&id)
+            .execute(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("no user with id {}", id)).into());
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Sets a user's password. Existing sessions are ended, so a leaked password can be locked out.
+#[server(SetUserPassword, "/api")]
+pub async fn set_user_password(id: String, password: String) -> Result<(), ServerFnError> {
+    traced("SetUserPassword", async move {
+        tracing::debug!(?id);
+        require_user_admin().await?;
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        if password.chars().count() < 8 {
+            return Err(
+                AppError::Validation("a password needs at least 8 characters".to_string()).into(),
+            );
+        }
+
+        let result = sqlx::query("UPDATE user SET password_hash = ? WHERE id = ?")
+            .bind(auth::hash_password(&password)?)
+            .bind(&id)
+            .execute(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("no user with id {}", id)).into());
+        }
+
+        sqlx::query("DELETE FROM session WHERE user_id = ?")
+            .bind(&id)
+            .execute(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(feature = "ssr")]
+async fn require_user_admin() -> Result<CurrentUser, ServerFnError> {
+    let user = auth::require_user().await?;
+    if !user.can_manage_users() {
+        return Err(AppError::Unauthorized("only admins can manage users".to_string()).into());
+    }
+    Ok(user)
+}
@@ -0,0 +1,81 @@
+use crate::error::AppError;
+use crate::model::webhook::WebhookDelivery;
+use std::sync::Arc;
+
+#[cfg(feature = "ssr")]
+use crate::model::user::CurrentUser;
+#[cfg(feature = "ssr")]
+use crate::model::webhook::DeliveryStatus;
+#[cfg(feature = "ssr")]
+use crate::server::auth;
+#[cfg(feature = "ssr")]
+use crate::server::telemetry::traced;
+#[cfg(feature = "ssr")]
+use actix_web::web::Data;
+#[cfg(feature = "ssr")]
+use sqlx::{Pool, Sqlite};
+
+use leptos::*;
+#[cfg(feature = "ssr")]
+use leptos_actix::extract;
+
+/// How many deliveries the delivery log shows.
+#[cfg(feature = "ssr")]
+const LOG_LENGTH: i64 = 100;
+
+/// The most recent webhook deliveries, newest first.
+#[server(GetWebhookDeliveries, "/api")]
+pub async fn get_webhook_deliveries() -> Result<Vec<WebhookDelivery>, ServerFnError> {
+    traced("GetWebhookDeliveries", async move {
+        require_webhook_admin().await?;
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        let res: Vec<WebhookDelivery> =
+            sqlx::query_as("SELECT * FROM webhook_delivery ORDER BY created_at DESC LIMIT ?")
+                .bind(LOG_LENGTH)
+                .fetch_all(&*pool)
+                .await
+                .map_err(AppError::from)?;
+
+        Ok(res)
+    })
+    .await
+}
+
+/// Sends a delivery again on the next round, with a fresh set of attempts.
+#[server(RetryWebhookDelivery, "/api")]
+pub async fn retry_webhook_delivery(id: String) -> Result<(), ServerFnError> {
+    traced("RetryWebhookDelivery", async move {
+        tracing::debug!(?id);
+        require_webhook_admin().await?;
+        let pool: Arc<Pool<Sqlite>> =
+            extract(|conn: Data<Pool<Sqlite>>| async move { conn.into_inner() }).await?;
+
+        let result = sqlx::query("UPDATE webhook_delivery SET status = ?, attempts = 0, next_attempt_at = ? WHERE id = ?")
+            .bind(DeliveryStatus::Pending)
+            .bind(chrono::Local::now().naive_local())
+            .bind(&id)
+            .execute(&*pool)
+            .await
+            .map_err(AppError::from)?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("no webhook delivery with id {}", id)).into());
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(feature = "ssr")]
+async fn require_webhook_admin() -> Result<CurrentUser, ServerFnError> {
+    let user = auth::require_user().await?;
+    if !user.can_manage_users() {
+        return Err(
+            AppError::Unauthorized("only admins can see webhook deliveries".to_string()).into(),
+        );
+    }
+    Ok(user)
+}
@@ -0,0 +1,179 @@
+//! Serving the compiled JS, WASM and CSS, and the files in `assets`, with cache headers.
+//!
+//! The compiled files are fingerprinted: their URLs carry a hash of their contents,
+//! so browsers can keep them forever and still pick up a new build straight away.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use actix_files::NamedFile;
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{
+    ContentEncoding, HeaderValue, ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_TYPE, VARY,
+};
+use actix_web::web::{Data, Path as UrlPath};
+use actix_web::{get, HttpRequest, HttpResponse};
+use leptos::LeptosOptions;
+
+/// For URLs that change whenever the file does.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// For everything else: browsers have to check with the server before using their copy.
+const REVALIDATE: &str = "no-cache";
+/// For pages anyone can see. Short, since cached pages don't know when a post is saved.
+pub const PUBLIC_HTML: &str = "public, max-age=60, must-revalidate";
+/// For pages that depend on who is logged in.
+const PRIVATE_HTML: &str = "private, no-cache";
+
+/// The fingerprint of the compiled files, once [`fingerprint_pkg`] has worked it out.
+static PKG_VERSION: OnceLock<String> = OnceLock::new();
+
+/// Hashes the compiled files in `site_root/pkg`, and points `options` at a path with the hash
+/// in it, so the scripts Leptos adds to every page load from there. Without a build to hash,
+/// as in development, the files keep their plain URLs.
+pub fn fingerprint_pkg(options: &mut LeptosOptions) {
+    let dir = Path::new(options.site_root.as_str()).join("pkg");
+    match hash_dir(&dir) {
+        Ok(version) => {
+            tracing::info!("serving {} as version {}", dir.display(), version);
+            options.site_pkg_dir = format!("pkg/{}", version);
+            let _ = PKG_VERSION.set(version);
+        }
+        Err(e) => tracing::warn!("not fingerprinting {}: {}", dir.display(), e),
+    }
+}
+
+fn hash_dir(dir: &Path) -> std::io::Result<String> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+
+    let mut hasher = DefaultHasher::new();
+    for file in files {
+        file.file_name().hash(&mut hasher);
+        std::fs::read(&file)?.hash(&mut hasher);
+    }
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+/// Where the compiled files are served from, e.g. `/pkg/3f2a...`.
+pub fn pkg_url() -> String {
+    match PKG_VERSION.get() {
+        Some(version) => format!("/pkg/{}", version),
+        None => "/pkg".to_string(),
+    }
+}
+
+/// Whether an `Accept-Encoding` header allows `encoding`.
+fn accepts(accept_encoding: &str, encoding: &str) -> bool {
+    accept_encoding.split(',').any(|part| {
+        let mut params = part.split(';').map(str::trim);
+        params.next() == Some(encoding)
+            && params.all(|param| {
+                param
+                    .strip_prefix("q=")
+                    .map_or(true, |q| q.parse::<f32>().map_or(true, |q| q > 0.0))
+            })
+    })
+}
+
+/// Serves `file` from `dir`, or a copy compressed ahead of time next to it
+/// (`app.wasm.br`, `app.wasm.gz`) if there is one the browser accepts.
+async fn serve_file(
+    req: &HttpRequest,
+    dir: &Path,
+    file: &str,
+    cache_control: &'static str,
+) -> actix_web::Result<HttpResponse> {
+    // the path comes from the URL, so it mustn't climb out of the directory
+    let relative = Path::new(file);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let path = dir.join(relative);
+    let content_type = actix_files::file_extension_to_mime(
+        path.extension().and_then(|ext| ext.to_str()).unwrap_or(""),
+    );
+    let accept_encoding = req
+        .headers()
+        .get(ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+
+    let mut named_file = None;
+    for (encoding, suffix) in [
+        (ContentEncoding::Brotli, "br"),
+        (ContentEncoding::Gzip, "gz"),
+    ] {
+        if !accepts(accept_encoding, encoding.as_str()) {
+            continue;
+        }
+        let mut compressed = path.clone().into_os_string();
+        compressed.push(".");
+        compressed.push(suffix);
+        if let Ok(file) = NamedFile::open_async(&compressed).await {
+            named_file = Some(file.set_content_encoding(encoding));
+            break;
+        }
+    }
+    let named_file = match named_file {
+        Some(file) => file,
+        None => NamedFile::open_async(&path).await?,
+    };
+
+    let mut res = named_file.set_content_type(content_type).into_response(req);
+    let headers = res.headers_mut();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+    headers.insert(VARY, HeaderValue::from_static("accept-encoding"));
+    Ok(res)
+}
+
+/// The compiled files. Under `/pkg/<fingerprint>/` they never change; plain `/pkg/` URLs
+/// still work, for pages rendered before a deploy and for development builds.
+#[get("/pkg/{file:.*}")]
+pub async fn pkg_file(
+    req: HttpRequest,
+    file: UrlPath<String>,
+    leptos_options: Data<LeptosOptions>,
+) -> actix_web::Result<HttpResponse> {
+    let dir = Path::new(leptos_options.site_root.as_str()).join("pkg");
+    let fingerprinted = PKG_VERSION
+        .get()
+        .and_then(|version| file.strip_prefix(version.as_str()))
+        .and_then(|rest| rest.strip_prefix('/'));
+
+    match fingerprinted {
+        Some(file) => serve_file(&req, &dir, file, IMMUTABLE).await,
+        None => serve_file(&req, &dir, &file, REVALIDATE).await,
+    }
+}
+
+/// Files from the `assets` directory, which keep their names from one build to the next.
+#[get("/assets/{file:.*}")]
+pub async fn asset_file(
+    req: HttpRequest,
+    file: UrlPath<String>,
+    leptos_options: Data<LeptosOptions>,
+) -> actix_web::Result<HttpResponse> {
+    let dir = Path::new(leptos_options.site_root.as_str());
+    serve_file(&req, dir, &file, REVALIDATE).await
+}
+
+/// Gives HTML responses that didn't choose their own caching a private, revalidating policy.
+pub fn set_html_cache_control<B>(res: &mut ServiceResponse<B>) {
+    let is_html = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.starts_with("text/html"));
+    if is_html && !res.headers().contains_key(CACHE_CONTROL) {
+        res.headers_mut()
+            .insert(CACHE_CONTROL, HeaderValue::from_static(PRIVATE_HTML));
+    }
+}
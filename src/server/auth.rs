@@ -0,0 +1,204 @@
+use std::sync::OnceLock;
+
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::http::header::{HeaderValue, SET_COOKIE};
+use actix_web::web::Data;
+use actix_web::HttpRequest;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use leptos::{use_context, ServerFnError};
+use leptos_actix::ResponseOptions;
+use sqlx::{Pool, Sqlite};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::model::user::{CurrentUser, Role};
+
+/// Name of the cookie holding the session token.
+pub const SESSION_COOKIE: &str = "session";
+
+/// How long a login lasts.
+const SESSION_DAYS: i64 = 30;
+
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| {
+            tracing::error!("could not hash password: {}", e);
+            AppError::Internal("could not hash password".to_string())
+        })
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Checks `password` against a throwaway hash, so a login for a handle that doesn't exist takes
+/// as long as one with a wrong password and doesn't give away which handles do.
+pub fn verify_dummy_password(password: &str) {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    let hash =
+        DUMMY_HASH.get_or_init(|| hash_password("not anyone's password").unwrap_or_default());
+    verify_password(password, hash);
+}
+
+/// Whether the session cookie is only sent over HTTPS. Reads `SESSION_COOKIE_SECURE` (`true` or
+/// `false`, default `true`); turn it off to log in over plain HTTP during local development.
+fn secure_cookies() -> bool {
+    std::env::var("SESSION_COOKIE_SECURE")
+        .map_or(true, |value| !matches!(value.as_str(), "false" | "0"))
+}
+
+/// Starts a session for `user_id` and sets the session cookie on the current response.
+pub async fn start_session(pool: &Pool<Sqlite>, user_id: &str) -> Result<(), AppError> {
+    let token = Uuid::new_v4().simple().to_string();
+    let csrf_token = Uuid::new_v4().simple().to_string();
+    let now = chrono::Local::now().naive_local();
+
+    // tidy up while we're here, so old sessions don't pile up
+    sqlx::query("DELETE FROM session WHERE expires_at <= ?")
+        .bind(now)
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO session (token, user_id, expires_at, csrf_token) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(&token)
+    .bind(user_id)
+    .bind(now + chrono::Duration::days(SESSION_DAYS))
+    .bind(csrf_token)
+    .execute(pool)
+    .await?;
+
+    set_session_cookie(
+        Cookie::build(SESSION_COOKIE, token)
+            .max_age(time::Duration::days(SESSION_DAYS))
+            .finish(),
+    );
+    Ok(())
+}
+
+/// Ends the session of the current request, if there is one, and clears the cookie.
+pub async fn end_session(pool: &Pool<Sqlite>) -> Result<(), AppError> {
+    if let Some(token) = session_token() {
+        sqlx::query("DELETE FROM session WHERE token = ?")
+            .bind(&token)
+            .execute(pool)
+            .await?;
+    }
+
+    let mut cookie = Cookie::named(SESSION_COOKIE);
+    cookie.make_removal();
+    set_session_cookie(cookie);
+    Ok(())
+}
+
+fn set_session_cookie(mut cookie: Cookie<'static>) {
+    cookie.set_path("/");
+    cookie.set_http_only(true);
+    cookie.set_same_site(SameSite::Lax);
+    cookie.set_secure(secure_cookies());
+    if let (Some(resp), Ok(value)) = (
+        use_context::<ResponseOptions>(),
+        HeaderValue::from_str(&cookie.to_string()),
+    ) {
+        resp.append_header(SET_COOKIE, value);
+    }
+}
+
+fn session_token() -> Option<String> {
+    use_context::<HttpRequest>()
+        .and_then(|req| req.cookie(SESSION_COOKIE))
+        .map(|cookie| cookie.value().to_string())
+}
+
+/// The user whose session cookie came with the current request.
+pub async fn current_user() -> Result<Option<CurrentUser>, ServerFnError> {
+    let Some(token) = session_token() else {
+        return Ok(None);
+    };
+    let Some(pool) =
+        use_context::<HttpRequest>().and_then(|req| req.app_data::<Data<Pool<Sqlite>>>().cloned())
+    else {
+        return Ok(None);
+    };
+
+    let user: Option<CurrentUser> = sqlx::query_as(
+        "SELECT user.id, user.handle, user.display_name, user.role FROM session JOIN user ON user.id = session.user_id WHERE session.token = ? AND session.expires_at > ?",
+    )
+    .bind(&token)
+    .bind(chrono::Local::now().naive_local())
+    .fetch_optional(&**pool)
+    .await
+    .map_err(AppError::from)?;
+
+    Ok(user)
+}
+
+/// The CSRF token of the session `session_token` belongs to, unless it has expired.
+pub async fn session_csrf_token(
+    pool: &Pool<Sqlite>,
+    session_token: &str,
+) -> Result<Option<String>, AppError> {
+    let csrf_token =
+        sqlx::query_scalar("SELECT csrf_token FROM session WHERE token = ? AND expires_at > ?")
+            .bind(session_token)
+            .bind(chrono::Local::now().naive_local())
+            .fetch_optional(pool)
+            .await?;
+    Ok(csrf_token)
+}
+
+/// The CSRF token of the current request's session, for pages to put in their forms.
+pub async fn csrf_token() -> Result<Option<String>, ServerFnError> {
+    let Some(token) = session_token() else {
+        return Ok(None);
+    };
+    let Some(pool) =
+        use_context::<HttpRequest>().and_then(|req| req.app_data::<Data<Pool<Sqlite>>>().cloned())
+    else {
+        return Ok(None);
+    };
+
+    Ok(session_csrf_token(&pool, &token).await?)
+}
+
+/// The current user, or an `Unauthorized` error for anonymous requests.
+pub async fn require_user() -> Result<CurrentUser, ServerFnError> {
+    current_user()
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("you need to log in first".to_string()).into())
+}
+
+/// Creates the admin named by `ADMIN_HANDLE` and `ADMIN_PASSWORD`, or resets their
+/// password and role, so there is always a way to log in to a fresh install.
+pub async fn ensure_admin_from_env(pool: &Pool<Sqlite>) -> Result<(), AppError> {
+    let (Ok(handle), Ok(password)) = (
+        std::env::var("ADMIN_HANDLE"),
+        std::env::var("ADMIN_PASSWORD"),
+    ) else {
+        return Ok(());
+    };
+    let handle = handle.trim().to_lowercase();
+
+    sqlx::query("INSERT INTO user (id, handle, display_name, role, password_hash) VALUES ($1, $2, $2, $3, $4) ON CONFLICT (handle) DO UPDATE SET role=excluded.role, password_hash=excluded.password_hash")
+        .bind(Uuid::new_v4().to_string())
+        .bind(&handle)
+        .bind(Role::Admin)
+        .bind(hash_password(&password)?)
+        .execute(pool)
+        .await?;
+
+    tracing::info!("admin account {:?} is ready", handle);
+    Ok(())
+}
@@ -0,0 +1,429 @@
+//! In-process cache for post data and for the server-rendered HTML of public pages.
+//!
+//! Every entry remembers what it was built from, so saving a post only evicts
+//! the entries that showed it rather than everything.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::future::{ready, Future, Ready};
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use actix_web::body::{to_bytes, BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::{
+    HeaderName, HeaderValue, CACHE_CONTROL, CONTENT_LENGTH, ETAG, IF_NONE_MATCH, SET_COOKIE,
+    TRANSFER_ENCODING, VARY,
+};
+use actix_web::http::{Method, StatusCode};
+use actix_web::web::{Bytes, Data};
+use actix_web::{Error, HttpMessage, HttpRequest, HttpResponse};
+use leptos::{use_context, ServerFnError};
+use leptos_actix::extract;
+
+use crate::component::theme::THEME_COOKIE;
+use crate::model::blog_post::Post;
+use crate::server::assets::PUBLIC_HTML;
+use crate::server::auth::SESSION_COOKIE;
+use crate::server::security::{nonce_of, with_fresh_nonce};
+
+/// Something a cached entry was built from. When it changes, the entry is evicted.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dependency {
+    /// A single post, by id.
+    Post(String),
+    /// Which posts are published, and in what order. Anything listing posts depends on this.
+    PublishedPosts,
+}
+
+struct Entry<V> {
+    value: V,
+    dependencies: HashSet<Dependency>,
+}
+
+pub struct Cache<V> {
+    entries: Mutex<HashMap<String, Entry<V>>>,
+    /// Bumped on every invalidation, so values read before a write aren't stored after it.
+    generation: AtomicU64,
+    capacity: usize,
+}
+
+impl<V: Clone> Cache<V> {
+    fn new(capacity: usize) -> Cache<V> {
+        Cache {
+            entries: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            capacity,
+        }
+    }
+
+    fn entries(&self) -> MutexGuard<'_, HashMap<String, Entry<V>>> {
+        // a panic elsewhere can't leave an entry half written, so the map is still usable
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        self.entries().get(key).map(|entry| entry.value.clone())
+    }
+
+    /// Read this before loading a value, and hand it to [`Cache::insert`] afterwards.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Stores `value`, unless anything was invalidated since `generation` was read,
+    /// in which case the value may already be out of date.
+    pub fn insert(
+        &self,
+        key: String,
+        value: V,
+        dependencies: HashSet<Dependency>,
+        generation: u64,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries();
+        if self.generation() != generation {
+            return;
+        }
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            // recency isn't tracked, so any entry makes room as well as another
+            if let Some(evicted) = entries.keys().next().cloned() {
+                entries.remove(&evicted);
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                value,
+                dependencies,
+            },
+        );
+    }
+
+    /// Evicts every entry built from any of `dependencies`.
+    pub fn invalidate(&self, dependencies: &[Dependency]) {
+        let mut entries = self.entries();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        entries.retain(|_, entry| {
+            !dependencies
+                .iter()
+                .any(|dependency| entry.dependencies.contains(dependency))
+        });
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        entries.clear();
+    }
+}
+
+/// A rendered page, as sent to a visitor who isn't logged in.
+#[derive(Clone)]
+pub struct CachedPage {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    body: Bytes,
+    /// What the page's scripts were rendered with, replaced by a fresh one in each response.
+    nonce: Option<String>,
+    etag: String,
+}
+
+pub struct ResponseCache {
+    /// Published posts by id, as returned by `get_post`.
+    pub posts: Cache<Post>,
+    /// Results of `get_previews`, by their arguments.
+    pub previews: Cache<Vec<Post>>,
+    /// Public pages by path.
+    pub pages: Cache<CachedPage>,
+}
+
+impl ResponseCache {
+    /// Reads `RESPONSE_CACHE_ENTRIES` (default 500), how many posts, preview lists and pages
+    /// are each kept at most. 0 turns the cache off.
+    pub fn from_env() -> ResponseCache {
+        let capacity = std::env::var("RESPONSE_CACHE_ENTRIES")
+            .ok()
+            .and_then(|entries| entries.parse().ok())
+            .unwrap_or(500);
+
+        ResponseCache {
+            posts: Cache::new(capacity),
+            previews: Cache::new(capacity),
+            pages: Cache::new(capacity),
+        }
+    }
+
+    pub fn invalidate(&self, dependencies: &[Dependency]) {
+        tracing::debug!(
+            "invalidating cached responses built from {:?}",
+            dependencies
+        );
+        self.posts.invalidate(dependencies);
+        self.previews.invalidate(dependencies);
+        self.pages.invalidate(dependencies);
+    }
+
+    /// Evicts what showed the post with the given id. Pass `listed` if the post was or is
+    /// published, since lists of posts and links between them change along with it.
+    pub fn invalidate_post(&self, id: &str, listed: bool) {
+        let mut dependencies = vec![Dependency::Post(id.to_string())];
+        if listed {
+            dependencies.push(Dependency::PublishedPosts);
+        }
+        self.invalidate(&dependencies);
+    }
+
+    /// For changes that show on every page, like the blog's title or an author's name.
+    pub fn clear(&self) {
+        tracing::debug!("clearing all cached responses");
+        self.posts.clear();
+        self.previews.clear();
+        self.pages.clear();
+    }
+}
+
+/// The cache, from inside a server function.
+pub async fn response_cache() -> Result<Arc<ResponseCache>, ServerFnError> {
+    extract(|cache: Data<ResponseCache>| async move { cache.into_inner() }).await
+}
+
+/// What the page being rendered for the current request was built from so far.
+struct PageDependencies(HashSet<Dependency>);
+
+/// Notes that the response to the current request was built from `dependencies`.
+/// Server functions that load public data call this, so a cached copy of a page
+/// is evicted when anything on it changes.
+pub fn depends_on(dependencies: impl IntoIterator<Item = Dependency>) {
+    let Some(req) = use_context::<HttpRequest>() else {
+        return;
+    };
+    let mut extensions = req.extensions_mut();
+    match extensions.get_mut::<PageDependencies>() {
+        Some(recorded) => recorded.0.extend(dependencies),
+        None => {
+            extensions.insert(PageDependencies(dependencies.into_iter().collect()));
+        }
+    }
+}
+
+/// The key to cache the response to `req` under, if it is for a public page and the
+/// visitor isn't logged in. Logged in visitors see menus and edit links for their role.
+/// Pages are rendered in the visitor's theme, so each theme is cached separately.
+fn page_key(req: &ServiceRequest) -> Option<String> {
+    if req.method() != Method::GET || req.cookie(SESSION_COOKIE).is_some() {
+        return None;
+    }
+    let path = req.path();
+    if path != "/" && !path.starts_with("/view/") {
+        return None;
+    }
+    let theme = req
+        .cookie(THEME_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .unwrap_or_default();
+    Some(match req.query_string() {
+        "" => format!("{} {}", theme, path),
+        query => format!("{} {}?{}", theme, path, query),
+    })
+}
+
+fn etag_of(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// Whether an `If-None-Match` header lists `etag`. Weak comparison, as RFC 9110 asks for.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.trim_start_matches("W/") == etag.trim_start_matches("W/")
+    })
+}
+
+/// Sends `page`, or just 304 Not Modified if the browser already has it.
+fn respond(req: HttpRequest, page: &CachedPage, cache_status: &'static str) -> ServiceResponse {
+    let not_modified = req
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| etag_matches(value, &page.etag));
+
+    let mut builder = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    for (name, value) in &page.headers {
+        builder.insert_header((name.clone(), value.clone()));
+    }
+    builder
+        .insert_header((ETAG, page.etag.clone()))
+        .insert_header((CACHE_CONTROL, PUBLIC_HTML))
+        // logging in or switching theme changes the page
+        .insert_header((VARY, "cookie"))
+        .insert_header(("x-cache", cache_status));
+
+    let res = match (&page.nonce, not_modified) {
+        (_, true) => builder.finish(),
+        (Some(nonce), false) => builder.body(with_fresh_nonce(&req, &page.body, nonce)),
+        (None, false) => builder.body(page.body.clone()),
+    };
+    ServiceResponse::new(req, res)
+}
+
+/// Middleware serving public pages from the [`ResponseCache`], with ETags and 304 responses.
+pub struct PageCache;
+
+impl<S, B> Transform<S, ServiceRequest> for PageCache
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = PageCacheMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(PageCacheMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct PageCacheMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for PageCacheMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let cache = req.app_data::<Data<ResponseCache>>().cloned();
+            let (Some(key), Some(cache)) = (page_key(&req), cache) else {
+                return service.call(req).await.map(|res| res.map_into_boxed_body());
+            };
+
+            if let Some(page) = cache.pages.get(&key) {
+                return Ok(respond(req.into_parts().0, &page, "HIT"));
+            }
+
+            let generation = cache.pages.generation();
+            let res = service.call(req).await?;
+            if res.status() != StatusCode::OK || res.headers().contains_key(SET_COOKIE) {
+                return Ok(res.map_into_boxed_body());
+            }
+
+            let (req, res) = res.into_parts();
+            let (head, body) = res.into_parts();
+            let body = to_bytes(body).await.map_err(|e| {
+                let e: Box<dyn std::error::Error> = e.into();
+                ErrorInternalServerError(e.to_string())
+            })?;
+
+            let page = CachedPage {
+                headers: head
+                    .headers()
+                    .iter()
+                    .filter(|(name, _)| **name != CONTENT_LENGTH && **name != TRANSFER_ENCODING)
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect(),
+                nonce: nonce_of(&req),
+                etag: etag_of(&body),
+                body,
+            };
+
+            // a page that didn't say what it was built from could never be evicted
+            let dependencies = req.extensions_mut().remove::<PageDependencies>();
+            if let Some(PageDependencies(dependencies)) = dependencies {
+                cache
+                    .pages
+                    .insert(key, page.clone(), dependencies, generation);
+            }
+
+            Ok(respond(req, &page, "MISS"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::CONTENT_SECURITY_POLICY;
+    use actix_web::{test, web, App};
+
+    use super::*;
+    use crate::server::images::ImagePolicy;
+    use crate::server::security::{set_nonce, SecurityConfig, SecurityHeaders};
+
+    const RENDERED_NONCE: &str = "rendered";
+
+    /// A page as Leptos would render it, with its nonce on a script.
+    async fn page(req: HttpRequest) -> HttpResponse {
+        req.extensions_mut()
+            .insert(PageDependencies([Dependency::PublishedPosts].into()));
+        set_nonce(&req, RENDERED_NONCE.to_string());
+        HttpResponse::Ok().body(format!("<script nonce=\"{}\"></script>", RENDERED_NONCE))
+    }
+
+    /// What follows the first `start` in `text`, up to `end`.
+    fn between<'a>(text: &'a str, start: &str, end: char) -> &'a str {
+        text.split(start).nth(1).unwrap().split(end).next().unwrap()
+    }
+
+    /// The nonce a response's CSP allows, and the one on the script in its body.
+    async fn nonces(res: ServiceResponse) -> (String, String) {
+        let csp = res
+            .headers()
+            .get(CONTENT_SECURITY_POLICY)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        let header = between(csp, "'nonce-", '\'').to_string();
+        let body = test::read_body(res).await;
+        let script = between(std::str::from_utf8(&body).unwrap(), "nonce=\"", '"').to_string();
+        (header, script)
+    }
+
+    #[actix_web::test]
+    async fn cached_pages_get_a_fresh_nonce() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(ResponseCache::from_env()))
+                .route("/", web::get().to(page))
+                .wrap(PageCache)
+                .wrap(SecurityHeaders(SecurityConfig::from_env(
+                    &ImagePolicy::for_tests(false),
+                ))),
+        )
+        .await;
+
+        let mut seen = HashSet::new();
+        for expected in ["MISS", "HIT", "HIT"] {
+            let res =
+                test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+            assert_eq!(res.headers().get("x-cache").unwrap(), expected);
+            let (header, script) = nonces(res).await;
+            assert_eq!(header, script);
+            assert_ne!(script, RENDERED_NONCE);
+            assert!(seen.insert(script), "a nonce was sent twice");
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Cross-site request forgery protection for server functions that change something.
+//!
+//! Before hydration, an `ActionForm` is a plain HTML form, and any other site could post
+//! one to us with the visitor's session cookie attached. So every write has to carry the
+//! session's CSRF token, which other sites can't read, in its [`CSRF_FIELD`]. As a second
+//! line of defense, writes sent from another origin are refused whatever they carry.
+
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::body::{to_bytes_limited, BodyStream, BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::{ORIGIN, REFERER};
+use actix_web::web::Data;
+use actix_web::{Error, HttpResponse};
+use sqlx::{Pool, Sqlite};
+
+use super::auth::{session_csrf_token, SESSION_COOKIE};
+use super::metrics::server_fn_name;
+use crate::component::auth::CSRF_FIELD;
+
+/// The largest request body server functions accept, which leaves room for long posts and
+/// `data:` images. The app's `PayloadConfig` is set to it, and the token check reads bodies up
+/// to it too, so anything a server function would take gets to it.
+pub const MAX_BODY_BYTES: usize = 8 * 1024 * 1024;
+
+/// Server functions that only read, and so are let through without a token. Anything else
+/// is taken to change something, so a new server function is checked until it is listed.
+pub(crate) const READ_ONLY_SERVER_FNS: &[&str] = &[
+    "GetAdjacentPosts",
+    "GetAuthor",
+    "GetAuthors",
+    "GetCsrfToken",
+    "GetCurrentUser",
+    "GetDeletedPosts",
+    "GetDraft",
+    "GetImageSrc",
+    "GetNewsletterEnabled",
+    "GetPost",
+    "GetPostHistory",
+    "GetPreviews",
+    "GetRelatedPosts",
+    "GetReviewNotes",
+    "GetReviewQueue",
+    "GetSettings",
+    "GetUnpublishedPosts",
+    "GetUsers",
+    "GetWebhookDeliveries",
+    "RenderParagraphs",
+];
+
+/// Whether the request came from a page on this site, going by `Origin`, or `Referer` for
+/// browsers that leave `Origin` out. Requests with neither are let through to the token check.
+fn same_origin(req: &ServiceRequest) -> bool {
+    let headers = req.headers();
+    let Some(source) = headers
+        .get(ORIGIN)
+        .or_else(|| headers.get(REFERER))
+        .and_then(|value| value.to_str().ok())
+    else {
+        return true;
+    };
+    // `Origin: null`, from sandboxed frames and the like, has no host and never matches
+    let source_host = source
+        .split_once("://")
+        .map(|(_, rest)| rest.split('/').next().unwrap_or_default());
+    source_host == Some(req.connection_info().host())
+}
+
+/// The value of `name` in a form-encoded body. Tokens are hex, so they need no decoding.
+fn form_field<'a>(body: &'a [u8], name: &str) -> Option<&'a str> {
+    std::str::from_utf8(body)
+        .ok()?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Compares in time that depends only on the length, so the token can't be guessed a byte
+/// at a time from how quickly requests are refused.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn forbidden(req: ServiceRequest, reason: &'static str) -> ServiceResponse<BoxBody> {
+    tracing::warn!(
+        path = req.path(),
+        reason,
+        "refused a possibly forged request"
+    );
+    req.into_response(HttpResponse::Forbidden().body("This request didn't come from this site."))
+}
+
+/// Middleware refusing writes to server functions that come from another site, or that
+/// don't carry the CSRF token of the session they are sent with.
+pub struct Csrf;
+
+impl<S, B> Transform<S, ServiceRequest> for Csrf
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = CsrfMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CsrfMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct CsrfMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for CsrfMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            // pages are never posted to
+            let is_write = server_fn_name(req.path())
+                .map_or(false, |name| !READ_ONLY_SERVER_FNS.contains(&name.as_str()));
+            if !is_write {
+                return service.call(req).await.map(|res| res.map_into_boxed_body());
+            }
+
+            if !same_origin(&req) {
+                return Ok(forbidden(req, "cross-origin"));
+            }
+
+            // without a session there is nobody to act as, so there is no token to check
+            let session = req
+                .cookie(SESSION_COOKIE)
+                .map(|cookie| cookie.value().to_string());
+            let pool = req.app_data::<Data<Pool<Sqlite>>>().cloned();
+            if let (Some(session), Some(pool)) = (session, pool) {
+                let expected = session_csrf_token(&pool, &session)
+                    .await
+                    .map_err(|e| ErrorInternalServerError(e.to_string()))?;
+                if let Some(expected) = expected {
+                    let payload = BodyStream::new(req.take_payload());
+                    let body = match to_bytes_limited(payload, MAX_BODY_BYTES).await {
+                        Ok(body) => body?,
+                        Err(_) => {
+                            return Ok(req.into_response(HttpResponse::PayloadTooLarge().finish()));
+                        }
+                    };
+                    let valid = form_field(&body, CSRF_FIELD).map_or(false, |given| {
+                        constant_time_eq(given.as_bytes(), expected.as_bytes())
+                    });
+                    // put the body back for the server function to read
+                    let (_, mut payload) = actix_http::h1::Payload::create(true);
+                    payload.unread_data(body);
+                    req.set_payload(payload.into());
+
+                    if !valid {
+                        return Ok(forbidden(req, "missing or wrong CSRF token"));
+                    }
+                }
+            }
+
+            service.call(req).await.map(|res| res.map_into_boxed_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::cookie::Cookie;
+    use actix_web::http::StatusCode;
+    use actix_web::web::Bytes;
+    use actix_web::{test, web, App};
+
+    use super::*;
+    use crate::server::test_support::test_pool;
+
+    /// A misspelt name would leave a read-only server function asking for a token.
+    #[test]
+    fn read_only_server_fns_exist() {
+        let names: Vec<String> = leptos::leptos_server::server_fns_by_path()
+            .into_iter()
+            .filter_map(|path| server_fn_name(&format!("/api/{}", path)))
+            .collect();
+        for name in READ_ONLY_SERVER_FNS {
+            assert!(
+                names.iter().any(|known| known == name),
+                "no server function {}",
+                name
+            );
+        }
+    }
+
+    /// Where a write server function is served, as the CSRF check only looks at those.
+    fn upsert_post_path() -> String {
+        leptos::leptos_server::server_fns_by_path()
+            .into_iter()
+            .map(|path| format!("/api/{}", path))
+            .find(|path| server_fn_name(path).as_deref() == Some("UpsertPost"))
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn passes_long_posts_on_whole() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO user (id, handle, display_name) VALUES ('u', 'u', 'U')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO session (token, user_id, expires_at, csrf_token) VALUES ('session', 'u', ?, 'token')")
+            .bind(chrono::Local::now().naive_local() + chrono::Duration::days(1))
+            .execute(&pool)
+            .await
+            .unwrap();
+        let path = upsert_post_path();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool))
+                .app_data(web::PayloadConfig::new(MAX_BODY_BYTES))
+                .route(
+                    &path,
+                    web::post().to(|body: Bytes| async move { HttpResponse::Ok().body(body) }),
+                )
+                .wrap(Csrf),
+        )
+        .await;
+        let post = |body: String| {
+            test::TestRequest::post()
+                .uri(&path)
+                .cookie(Cookie::new(SESSION_COOKIE, "session"))
+                .set_payload(body)
+                .to_request()
+        };
+
+        // well over actix's default limit of 256 KiB
+        let body = format!("_csrf=token&text={}", "a".repeat(300 * 1024));
+        let res = test::call_service(&app, post(body.clone())).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, body.as_bytes());
+
+        let body = format!("_csrf=wrong&text={}", "a".repeat(300 * 1024));
+        let res = test::call_service(&app, post(body)).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let body = format!("_csrf=token&text={}", "a".repeat(MAX_BODY_BYTES));
+        let res = test::call_service(&app, post(body)).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
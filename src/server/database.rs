@@ -0,0 +1,86 @@
+//! Opening the SQLite database at startup and closing it cleanly at shutdown.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{Pool, Sqlite};
+
+/// The longest wait between two attempts, so many retries add up to a long wait rather than
+/// an overflow.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+pub struct DatabaseConfig {
+    pub url: String,
+    /// How many more times to try opening the database after the first attempt fails.
+    pub connect_retries: u32,
+    /// Before the first retry, doubling for each one after, up to [`MAX_RETRY_DELAY`].
+    pub retry_delay: Duration,
+}
+
+impl DatabaseConfig {
+    /// Reads `DATABASE_URL` (default `sqlite:post.db`), `DB_CONNECT_RETRIES` (default 5)
+    /// and `DB_CONNECT_RETRY_DELAY_MS` (default 500).
+    pub fn from_env() -> DatabaseConfig {
+        let url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:post.db".to_string());
+        let connect_retries = std::env::var("DB_CONNECT_RETRIES")
+            .ok()
+            .and_then(|retries| retries.parse().ok())
+            .unwrap_or(5);
+        let retry_delay_ms = std::env::var("DB_CONNECT_RETRY_DELAY_MS")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .unwrap_or(500);
+
+        DatabaseConfig {
+            url,
+            connect_retries,
+            retry_delay: Duration::from_millis(retry_delay_ms),
+        }
+    }
+}
+
+/// Opens the database, creating the file if there isn't one yet. A database that is briefly
+/// unavailable, like a volume that is still being mounted, is retried with backoff.
+pub async fn connect(config: &DatabaseConfig) -> Result<Pool<Sqlite>, sqlx::Error> {
+    let options = SqliteConnectOptions::from_str(&config.url)?.create_if_missing(true);
+
+    let mut delay = config.retry_delay;
+    let mut attempt = 0;
+    loop {
+        match SqlitePoolOptions::new().connect_with(options.clone()).await {
+            Ok(pool) => {
+                tracing::info!(url = %config.url, "opened the database");
+                return Ok(pool);
+            }
+            Err(e) if attempt < config.connect_retries => {
+                attempt += 1;
+                tracing::warn!(
+                    url = %config.url,
+                    error = %e,
+                    "could not open the database, retrying in {:?} ({}/{})",
+                    delay,
+                    attempt,
+                    config.connect_retries
+                );
+                actix_web::rt::time::sleep(delay).await;
+                delay = delay.saturating_mul(2).min(MAX_RETRY_DELAY);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Writes everything still in the write-ahead log back to the database file and closes
+/// the pool, so nothing is left half-written when the process exits.
+pub async fn close(pool: Pool<Sqlite>) {
+    // does nothing unless the database is in WAL mode
+    if let Err(e) = sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(&pool)
+        .await
+    {
+        tracing::warn!(error = %e, "could not checkpoint the database");
+    }
+    pool.close().await;
+    tracing::info!("closed the database");
+}
@@ -0,0 +1,158 @@
+//! Probes for the orchestrator: `/healthz` answers as long as the process can serve requests,
+//! `/readyz` only once it can do useful work with them.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use actix_web::web::Data;
+use actix_web::{get, HttpResponse};
+use serde::Serialize;
+use sqlx::migrate::Migrator;
+use sqlx::{Pool, Sqlite};
+
+/// The migrations built into the binary, which the database has to be up to date with.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Where uploaded media lives. Reads `MEDIA_DIR` (default `media`).
+pub fn media_dir() -> PathBuf {
+    std::env::var("MEDIA_DIR")
+        .unwrap_or_else(|_| "media".to_string())
+        .into()
+}
+
+#[derive(Serialize)]
+struct Check {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl From<Result<(), String>> for Check {
+    fn from(result: Result<(), String>) -> Check {
+        match result {
+            Ok(()) => Check {
+                ok: true,
+                error: None,
+            },
+            Err(error) => Check {
+                ok: false,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    ready: bool,
+    database: Check,
+    migrations: Check,
+    media: Check,
+}
+
+/// Liveness: doesn't touch anything, so a slow database doesn't get the process restarted.
+#[get("/healthz")]
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "ok": true }))
+}
+
+/// Readiness, with the outcome of each check. Responds 503 unless all of them pass.
+#[get("/readyz")]
+pub async fn readyz(pool: Data<Pool<Sqlite>>) -> HttpResponse {
+    let database = check_database(&pool).await;
+    let migrations = check_migrations(&pool).await;
+    let media = check_media().await;
+
+    let ready = database.is_ok() && migrations.is_ok() && media.is_ok();
+    if !ready {
+        tracing::warn!(
+            database = ?database.as_ref().err(),
+            migrations = ?migrations.as_ref().err(),
+            media = ?media.as_ref().err(),
+            "not ready"
+        );
+    }
+    let readiness = Readiness {
+        ready,
+        database: database.into(),
+        migrations: migrations.into(),
+        media: media.into(),
+    };
+    if ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+async fn check_database(pool: &Pool<Sqlite>) -> Result<(), String> {
+    sqlx::query("SELECT 1")
+        .execute(pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn check_migrations(pool: &Pool<Sqlite>) -> Result<(), String> {
+    let applied: HashSet<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = 1")
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .collect();
+
+    let pending: Vec<String> = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| migration.version.to_string())
+        .collect();
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("not applied: {}", pending.join(", ")))
+    }
+}
+
+/// Writes and removes a file, since permissions alone don't show a read-only filesystem.
+async fn check_media() -> Result<(), String> {
+    let probe = media_dir().join(".readyz");
+    actix_web::web::block(move || {
+        std::fs::write(&probe, b"ok")?;
+        std::fs::remove_file(&probe)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("{} is not writable: {}", media_dir().display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::test_support::test_pool;
+
+    #[actix_web::test]
+    async fn migrations_can_be_undone() {
+        let pool = test_pool().await;
+        sqlx::query("INSERT INTO user (id, handle, display_name) VALUES ('u', 'u', 'U')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO post (id, dt, title, text, author_id) VALUES ('p', '2024-01-01T00:00', 'Title', 'Text', 'u')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        MIGRATOR.undo(&pool, 0).await.unwrap();
+        let tables: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name != '_sqlx_migrations'",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert!(tables.is_empty(), "left behind: {:?}", tables);
+
+        MIGRATOR.run(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,491 @@
+//! `/image`, which serves the images the blog can't link to directly: inline `data:`
+//! images, and in proxy mode every image hosted elsewhere, which the server then fetches so
+//! readers never contact the host it is on.
+//!
+//! Only URLs signed by [`ImagePolicy::src`] are served, so the endpoint can't be used to
+//! fetch arbitrary URLs through the blog.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_TYPE, USER_AGENT};
+use actix_web::http::Uri;
+use actix_web::web::{self, Data, Query};
+use actix_web::{get, HttpResponse};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use leptos::ServerFnError;
+use leptos_actix::extract;
+use serde::Deserialize;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::model::author::Author;
+use crate::model::blog_post::Post;
+use crate::model::image::{encode_query_value, is_allowed_image_url, IMAGE_PATH};
+
+/// Images rarely change at the same URL, and a stale one for a day does no harm.
+const IMAGE_CACHE_CONTROL: &str = "public, max-age=86400";
+
+pub struct ImagePolicy {
+    /// Fetch images for readers rather than having their browsers load them from the image's host.
+    pub proxy: bool,
+    /// Show images given inline as `data:` URLs.
+    pub allow_data: bool,
+    /// The largest image that is fetched or decoded.
+    pub max_bytes: usize,
+    /// Key for the signatures on `/image` URLs.
+    secret: Vec<u8>,
+}
+
+impl ImagePolicy {
+    /// Reads `IMAGE_PROXY` and `ALLOW_DATA_IMAGES` (both `true` or `false`, default `false`),
+    /// `IMAGE_MAX_BYTES` (default 5 MiB) and `IMAGE_URL_SECRET` (default random at startup).
+    pub fn from_env() -> ImagePolicy {
+        let flag = |name: &str| {
+            std::env::var(name).map_or(false, |value| matches!(value.as_str(), "true" | "1"))
+        };
+        // several servers behind one load balancer have to share the secret
+        let secret = std::env::var("IMAGE_URL_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .unwrap_or_else(|| format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()));
+        ImagePolicy {
+            proxy: flag("IMAGE_PROXY"),
+            allow_data: flag("ALLOW_DATA_IMAGES"),
+            max_bytes: std::env::var("IMAGE_MAX_BYTES")
+                .ok()
+                .and_then(|bytes| bytes.parse().ok())
+                .unwrap_or(5 * 1024 * 1024),
+            secret: secret.into_bytes(),
+        }
+    }
+
+    /// A policy with a fixed secret, allowing `data:` images.
+    #[cfg(test)]
+    pub(crate) fn for_tests(proxy: bool) -> ImagePolicy {
+        ImagePolicy {
+            proxy,
+            allow_data: true,
+            max_bytes: 1024,
+            secret: b"s3cret".to_vec(),
+        }
+    }
+
+    fn mac(&self, url: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(url.as_bytes());
+        mac
+    }
+
+    /// The hex HMAC-SHA256 of `url`.
+    fn sign(&self, url: &str) -> String {
+        format!("{:x}", self.mac(url).finalize().into_bytes())
+    }
+
+    fn is_signed(&self, url: &str, signature: &str) -> bool {
+        let Some(signature) = decode_hex(signature) else {
+            return false;
+        };
+        self.mac(url).verify_slice(&signature).is_ok()
+    }
+
+    /// The `src` to show the image at `url` with, or `None` if there is nothing to show.
+    ///
+    /// Images on this site are linked to directly, and so are images elsewhere unless the
+    /// server proxies them. Proxied and `data:` images go through a signed `/image` URL.
+    pub fn src(&self, url: &str) -> Option<String> {
+        let url = url.trim();
+        if url.is_empty() || !is_allowed_image_url(url) {
+            return None;
+        }
+        if url.starts_with('/') && !url.starts_with("//") {
+            return Some(url.to_string());
+        }
+        if is_data_url(url) {
+            if !self.allow_data {
+                return None;
+            }
+        } else if !self.proxy {
+            return Some(url.to_string());
+        }
+        Some(format!(
+            "{}?url={}&sig={}",
+            IMAGE_PATH,
+            encode_query_value(url),
+            self.sign(url)
+        ))
+    }
+
+    /// Fills in the `src`s a post's images are shown with.
+    pub fn set_post_srcs(&self, post: &mut Post) {
+        post.image_src = self.src(&post.image_url);
+        post.author_avatar_src = post
+            .author_avatar_url
+            .as_deref()
+            .and_then(|url| self.src(url));
+    }
+
+    pub fn set_author_srcs(&self, author: &mut Author) {
+        author.avatar_src = self.src(&author.avatar_url);
+    }
+}
+
+/// The image policy of the server handling the current request.
+pub async fn image_policy() -> Result<Arc<ImagePolicy>, ServerFnError> {
+    extract(|policy: Data<ImagePolicy>| async move { policy.into_inner() }).await
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn is_data_url(url: &str) -> bool {
+    url.get(..5)
+        .map_or(false, |scheme| scheme.eq_ignore_ascii_case("data:"))
+}
+
+/// SVG is left out: it can carry scripts, which would run if it were opened from our origin.
+fn is_raster_image(content_type: &str) -> bool {
+    content_type.starts_with("image/") && !content_type.starts_with("image/svg")
+}
+
+/// Whether `ip` is an address on the public internet, rather than one of ours, one on our
+/// network, or one reserved for something else. The proxy only connects to those.
+fn is_global(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_global_v4(ip),
+        IpAddr::V6(ip) => is_global_v6(ip),
+    }
+}
+
+fn is_global_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "this network"
+        || a == 0
+        // shared address space for carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        // benchmarking
+        || (a == 198 && (18..20).contains(&b))
+        // reserved
+        || a >= 240)
+}
+
+fn is_global_v6(ip: Ipv6Addr) -> bool {
+    // IPv4-mapped (::ffff:a.b.c.d) and NAT64 (64:ff9b::a.b.c.d) addresses reach the IPv4 address
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_global_v4(v4);
+    }
+    let segments = ip.segments();
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [a, b] = segments[6].to_be_bytes();
+        let [c, d] = segments[7].to_be_bytes();
+        return is_global_v4(Ipv4Addr::new(a, b, c, d));
+    }
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // IPv4-compatible, deprecated
+        || segments[..6] == [0; 6]
+        // unique local
+        || segments[0] & 0xfe00 == 0xfc00
+        // link-local
+        || segments[0] & 0xffc0 == 0xfe80
+        // documentation
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+}
+
+/// Resolves `host`, and picks an address to connect to, provided every address it resolves
+/// to is global. Checking them all means a name with one private address among public ones
+/// can't be used to reach the private one, whichever the connection would have picked.
+async fn resolve_global(host: &str, port: u16) -> Result<SocketAddr, String> {
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let addrs: Vec<SocketAddr> = web::block(move || (host.as_str(), port).to_socket_addrs())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("could not resolve: {}", e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err("resolves to no address".to_string());
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_global(addr.ip())) {
+        return Err(format!("resolves to the non-public address {}", addr.ip()));
+    }
+    Ok(addrs[0])
+}
+
+#[derive(Deserialize)]
+pub struct ImageQuery {
+    url: String,
+    sig: String,
+}
+
+/// The image at `?url=`, provided `?sig=` is the signature [`ImagePolicy::src`] gave it.
+#[get("/image")]
+pub async fn image(query: Query<ImageQuery>, policy: Data<ImagePolicy>) -> HttpResponse {
+    let url = query.url.trim();
+    if !policy.is_signed(url, &query.sig) {
+        return HttpResponse::Forbidden().body("not a signed image URL");
+    }
+    if url.is_empty() || !is_allowed_image_url(url) {
+        return HttpResponse::BadRequest().body("not an image URL");
+    }
+
+    if is_data_url(url) {
+        return data_image(&url[5..], &policy);
+    }
+    // when not proxying, pages link to images elsewhere directly; following signed URLs
+    // anyway would make this a redirect to wherever a signature was once given out for
+    if !policy.proxy {
+        return HttpResponse::NotFound().finish();
+    }
+    proxied_image(url, &policy).await
+}
+
+/// Decodes an image given as `data:image/png;base64,...`.
+fn data_image(data_url: &str, policy: &ImagePolicy) -> HttpResponse {
+    if !policy.allow_data {
+        return HttpResponse::Forbidden().body("data: images aren't allowed");
+    }
+    let Some((content_type, data)) = data_url
+        .split_once(',')
+        .and_then(|(meta, data)| Some((meta.strip_suffix(";base64")?, data)))
+    else {
+        return HttpResponse::BadRequest().body("only base64 data: images are supported");
+    };
+    if !is_raster_image(content_type) {
+        return HttpResponse::UnsupportedMediaType().finish();
+    }
+    // the encoding is a third bigger than what it decodes to
+    if data.len() / 4 * 3 > policy.max_bytes {
+        return HttpResponse::PayloadTooLarge().finish();
+    }
+
+    match base64::engine::general_purpose::STANDARD.decode(data) {
+        Ok(bytes) => HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header((CACHE_CONTROL, IMAGE_CACHE_CONTROL))
+            .body(bytes),
+        Err(_) => HttpResponse::BadRequest().body("invalid base64"),
+    }
+}
+
+/// Fetches the image at `url` and passes it on.
+async fn proxied_image(url: &str, policy: &ImagePolicy) -> HttpResponse {
+    let Ok(uri) = url.parse::<Uri>() else {
+        return HttpResponse::BadRequest().body("not an image URL");
+    };
+    let (Some(host), Some(scheme)) = (uri.host(), uri.scheme_str()) else {
+        return HttpResponse::BadRequest().body("not an image URL");
+    };
+    let port = uri
+        .port_u16()
+        .unwrap_or(if scheme.eq_ignore_ascii_case("https") {
+            443
+        } else {
+            80
+        });
+    // connect to the address that was checked, so a second lookup can't give another one
+    let addr = match resolve_global(host, port).await {
+        Ok(addr) => addr,
+        Err(reason) => {
+            tracing::info!(url, reason, "refused to proxy image");
+            return HttpResponse::Forbidden().body("not a public host");
+        }
+    };
+
+    // redirects could lead anywhere, including past the host check
+    let client = awc::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .disable_redirects()
+        .finish();
+    let mut upstream = match client
+        .get(uri)
+        // the Host header and TLS still go by the name in the URL
+        .address(addr)
+        .insert_header((USER_AGENT, "hot-blog image proxy"))
+        .send()
+        .await
+    {
+        Ok(upstream) if upstream.status().is_success() => upstream,
+        Ok(upstream) => {
+            tracing::info!(url, status = %upstream.status(), "image host refused");
+            return HttpResponse::BadGateway().finish();
+        }
+        Err(e) => {
+            tracing::warn!(url, error = %e, "could not fetch image");
+            return HttpResponse::BadGateway().finish();
+        }
+    };
+
+    let content_type = upstream
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if !is_raster_image(&content_type) {
+        return HttpResponse::UnsupportedMediaType().finish();
+    }
+
+    match upstream.body().limit(policy.max_bytes).await {
+        Ok(bytes) => HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header((CACHE_CONTROL, IMAGE_CACHE_CONTROL))
+            .body(bytes),
+        Err(e) => {
+            tracing::warn!(url, error = %e, "could not read image");
+            HttpResponse::BadGateway().finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::LOCATION;
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+
+    use super::*;
+
+    /// Asks a server with `policy` for `/image` with the given query string.
+    async fn get_image(policy: ImagePolicy, query: &str) -> (StatusCode, bool) {
+        let app = test::init_service(App::new().app_data(Data::new(policy)).service(image)).await;
+        let req = test::TestRequest::get()
+            .uri(&format!("{}?{}", IMAGE_PATH, query))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        (res.status(), res.headers().contains_key(LOCATION))
+    }
+
+    fn query_of(src: &str) -> &str {
+        src.split_once('?').unwrap().1
+    }
+
+    #[test]
+    fn links_directly_unless_proxying() {
+        let url = "https://images.example.com/a.png";
+        assert_eq!(ImagePolicy::for_tests(false).src(url).as_deref(), Some(url));
+        assert!(ImagePolicy::for_tests(true)
+            .src(url)
+            .unwrap()
+            .starts_with("/image?url=https%3A%2F%2F"));
+        assert_eq!(
+            ImagePolicy::for_tests(true).src("/assets/a.png").as_deref(),
+            Some("/assets/a.png")
+        );
+        assert_eq!(
+            ImagePolicy::for_tests(true).src("javascript:alert(1)"),
+            None
+        );
+    }
+
+    #[test]
+    fn only_accepts_its_own_signatures() {
+        let url = "https://images.example.com/a.png";
+        let signed = ImagePolicy::for_tests(true);
+        let signature = signed.sign(url);
+        assert!(signed.is_signed(url, &signature));
+        assert!(!signed.is_signed("https://images.example.com/b.png", &signature));
+        assert!(!signed.is_signed(url, "00"));
+        assert!(!signed.is_signed(url, "not hex"));
+
+        let other = ImagePolicy {
+            secret: b"other".to_vec(),
+            ..ImagePolicy::for_tests(true)
+        };
+        assert!(!other.is_signed(url, &signature));
+    }
+
+    #[actix_web::test]
+    async fn refuses_unsigned_urls() {
+        let (status, _) = get_image(
+            ImagePolicy::for_tests(true),
+            "url=https%3A%2F%2Fexample.com%2Fa.png&sig=00",
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn never_redirects() {
+        // a signature given out while proxying, followed after proxying was turned off
+        let src = ImagePolicy::for_tests(true)
+            .src("https://images.example.com/a.png")
+            .unwrap();
+        let (status, redirected) = get_image(ImagePolicy::for_tests(false), query_of(&src)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(!redirected);
+    }
+
+    #[actix_web::test]
+    async fn serves_signed_data_images() {
+        let src = ImagePolicy::for_tests(false)
+            .src("data:image/png;base64,iVBORw0KGgo=")
+            .unwrap();
+        let (status, _) = get_image(ImagePolicy::for_tests(false), query_of(&src)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn refuses_to_proxy_local_hosts() {
+        for url in [
+            "http://127.0.0.1/a.png",
+            "http://localhost/a.png",
+            "http://[::ffff:127.0.0.1]/a.png",
+            // decimal and octal spellings of 127.0.0.1, which the resolver accepts
+            "http://2130706433/a.png",
+            "http://0177.0.0.1/a.png",
+        ] {
+            let src = ImagePolicy::for_tests(true).src(url).unwrap();
+            let (status, _) = get_image(ImagePolicy::for_tests(true), query_of(&src)).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", url);
+        }
+    }
+
+    #[test]
+    fn tells_public_addresses_apart() {
+        for ip in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+            assert!(is_global(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "10.0.0.1",
+            "127.0.0.1",
+            "169.254.169.254",
+            "172.16.0.1",
+            "192.168.1.1",
+            "100.64.0.1",
+            "100.127.255.255",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+            "64:ff9b::a00:1",
+            "fc00::1",
+            "fe80::1",
+        ] {
+            assert!(!is_global(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}
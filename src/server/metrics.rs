@@ -0,0 +1,414 @@
+//! Prometheus metrics, served at `/metrics` in the text exposition format.
+
+use std::collections::HashSet;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use actix_web::body::{BodySize, BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{AUTHORIZATION, CONTENT_TYPE};
+use actix_web::web::{Bytes, Data};
+use actix_web::{get, Error, HttpRequest, HttpResponse};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::{Pool, Sqlite};
+
+use super::csrf::constant_time_eq;
+
+/// Upper bounds, in seconds, of the latency buckets.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    server_fn_calls: IntCounterVec,
+    server_fn_duration: HistogramVec,
+    ssr_render_duration: HistogramVec,
+    query_duration: HistogramVec,
+    pool_connections: IntGaugeVec,
+    pool_max_connections: IntGauge,
+    posts: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let registry = Registry::new();
+
+        let http_requests = IntCounterVec::new(
+            Opts::new(
+                "http_requests_total",
+                "Requests for pages and files, by route.",
+            ),
+            &["route", "method", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time from a request for a page or file arriving until its response is sent.",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["route", "method"],
+        )
+        .unwrap();
+        let server_fn_calls = IntCounterVec::new(
+            Opts::new(
+                "server_fn_calls_total",
+                "Calls to server functions from the browser.",
+            ),
+            &["function", "status"],
+        )
+        .unwrap();
+        let server_fn_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "server_fn_duration_seconds",
+                "Time taken by server functions called from the browser.",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["function"],
+        )
+        .unwrap();
+        let ssr_render_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "ssr_render_duration_seconds",
+                "Time to render and stream a page that wasn't served from the page cache.",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["route"],
+        )
+        .unwrap();
+        let query_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "sqlite_query_duration_seconds",
+                "Time taken by SQLite queries.",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["query"],
+        )
+        .unwrap();
+        let pool_connections = IntGaugeVec::new(
+            Opts::new(
+                "sqlite_pool_connections",
+                "Open SQLite connections, idle or in use.",
+            ),
+            &["state"],
+        )
+        .unwrap();
+        let pool_max_connections = IntGauge::new(
+            "sqlite_pool_max_connections",
+            "How many SQLite connections the pool may open.",
+        )
+        .unwrap();
+        let posts = IntGaugeVec::new(
+            Opts::new(
+                "posts",
+                "Posts by status. Posts in the trash have status \"deleted\".",
+            ),
+            &["status"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(server_fn_calls.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(server_fn_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(ssr_render_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(query_duration.clone())).unwrap();
+        registry
+            .register(Box::new(pool_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(pool_max_connections.clone()))
+            .unwrap();
+        registry.register(Box::new(posts.clone())).unwrap();
+
+        Metrics {
+            registry,
+            http_requests,
+            http_request_duration,
+            server_fn_calls,
+            server_fn_duration,
+            ssr_render_duration,
+            query_duration,
+            pool_connections,
+            pool_max_connections,
+            posts,
+        }
+    }
+}
+
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Runs a query, recording how long it took under `name`.
+pub async fn timed_query<T>(name: &'static str, query: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let result = query.await;
+    metrics()
+        .query_duration
+        .with_label_values(&[name])
+        .observe(started.elapsed().as_secs_f64());
+    result
+}
+
+/// The name of the server function at `path`, like `GetPost` for `/api/get_post1234`,
+/// or `None` if no server function is there.
+pub(crate) fn server_fn_name(path: &str) -> Option<String> {
+    static PATHS: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    let paths = PATHS.get_or_init(|| {
+        leptos::leptos_server::server_fns_by_path()
+            .into_iter()
+            .collect()
+    });
+
+    let path = path.strip_prefix("/api/")?;
+    if !paths.contains(path) {
+        return None;
+    }
+    // the path is the function's name followed by a hash of where it is defined
+    let name = path.trim_end_matches(|c: char| c.is_ascii_digit());
+    Some(
+        name.split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                    .unwrap_or_default()
+            })
+            .collect(),
+    )
+}
+
+/// What a request is counted under.
+enum Target {
+    ServerFn(String),
+    Route(String),
+}
+
+/// A response body that records how long the response took once it has been sent.
+struct TimedBody {
+    body: BoxBody,
+    started: Instant,
+    target: Target,
+    method: String,
+    /// Pages rendered for this request, as opposed to ones from the cache or static files.
+    rendered: bool,
+}
+
+impl MessageBody for TimedBody {
+    type Error = <BoxBody as MessageBody>::Error;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        Pin::new(&mut self.body).poll_next(cx)
+    }
+}
+
+impl Drop for TimedBody {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let metrics = metrics();
+        match &self.target {
+            Target::ServerFn(name) => {
+                metrics
+                    .server_fn_duration
+                    .with_label_values(&[name])
+                    .observe(elapsed);
+            }
+            Target::Route(route) => {
+                metrics
+                    .http_request_duration
+                    .with_label_values(&[route, &self.method])
+                    .observe(elapsed);
+                if self.rendered {
+                    metrics
+                        .ssr_render_duration
+                        .with_label_values(&[route])
+                        .observe(elapsed);
+                }
+            }
+        }
+    }
+}
+
+/// Middleware counting and timing every request, by route or by server function.
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let started = Instant::now();
+            let method = req.method().to_string();
+            // route patterns rather than paths, so every post doesn't get its own series
+            let target = match server_fn_name(req.path()) {
+                Some(name) => Target::ServerFn(name),
+                None => Target::Route(
+                    req.match_pattern()
+                        .unwrap_or_else(|| "unmatched".to_string()),
+                ),
+            };
+
+            let res = service.call(req).await?;
+            let status = res.status().as_u16().to_string();
+            match &target {
+                Target::ServerFn(name) => {
+                    metrics()
+                        .server_fn_calls
+                        .with_label_values(&[name, &status])
+                        .inc();
+                }
+                Target::Route(route) => {
+                    metrics()
+                        .http_requests
+                        .with_label_values(&[route, &method, &status])
+                        .inc();
+                }
+            }
+
+            let is_html = res
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map_or(false, |value| value.starts_with("text/html"));
+            let from_cache = res
+                .headers()
+                .get("x-cache")
+                .map_or(false, |value| value == "HIT");
+            let rendered = is_html && !from_cache;
+
+            Ok(res.map_body(|_, body| {
+                BoxBody::new(TimedBody {
+                    body: body.boxed(),
+                    started,
+                    target,
+                    method,
+                    rendered,
+                })
+            }))
+        })
+    }
+}
+
+/// The metrics, for Prometheus to scrape. If `METRICS_TOKEN` is set, the scraper has to send it
+/// as a bearer token.
+#[get("/metrics")]
+pub async fn metrics_endpoint(
+    req: HttpRequest,
+    pool: Data<Pool<Sqlite>>,
+) -> actix_web::Result<HttpResponse> {
+    if let Ok(token) = std::env::var("METRICS_TOKEN") {
+        let authorized = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map_or(false, |given| {
+                constant_time_eq(given.as_bytes(), token.as_bytes())
+            });
+        if !authorized {
+            return Ok(HttpResponse::Unauthorized().finish());
+        }
+    }
+
+    let metrics = metrics();
+
+    // gauges are read when scraped rather than kept up to date
+    let idle = pool.num_idle() as i64;
+    metrics
+        .pool_connections
+        .with_label_values(&["idle"])
+        .set(idle);
+    metrics
+        .pool_connections
+        .with_label_values(&["in_use"])
+        .set(pool.size() as i64 - idle);
+    metrics
+        .pool_max_connections
+        .set(pool.options().get_max_connections() as i64);
+
+    let counts: Vec<(String, i64)> = timed_query(
+        "count_posts",
+        sqlx::query_as(
+            "SELECT CASE WHEN deleted_at IS NULL THEN status ELSE 'deleted' END AS state, COUNT(*)
+            FROM post GROUP BY state",
+        )
+        .fetch_all(pool.get_ref()),
+    )
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    // statuses with no posts left still have to drop to zero
+    metrics.posts.reset();
+    for (status, count) in counts {
+        metrics.posts.with_label_values(&[&status]).set(count);
+    }
+
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    encoder
+        .encode(&metrics.registry.gather(), &mut body)
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(encoder.format_type())
+        .body(body))
+}
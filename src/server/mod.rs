@@ -0,0 +1,18 @@
+//! Server-only pieces that run alongside the Leptos app: background jobs and middleware.
+pub mod assets;
+pub mod auth;
+pub mod cache;
+pub mod csrf;
+pub mod database;
+pub mod health;
+pub mod images;
+pub mod metrics;
+pub mod newsletter;
+pub mod rate_limit;
+pub mod sanitize;
+pub mod security;
+pub mod telemetry;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod trash;
+pub mod webhooks;
@@ -0,0 +1,963 @@
+//! The email newsletter: readers subscribe with their address, confirm it from an email
+//! (double opt-in), and then get an email whenever a post is published.
+//!
+//! Publishing a post only notes it in the `newsletter_digest` table. A background task
+//! renders an email per subscriber from it into the `newsletter_email` table and sends those
+//! over SMTP, retrying failures with exponential backoff, like webhooks do.
+
+use std::time::Duration;
+
+use actix_web::web::Data;
+use leptos_actix::extract;
+use lettre::message::header::{Header, HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use sqlx::types::chrono::NaiveDateTime;
+use sqlx::{FromRow, Pool, Sqlite, SqliteExecutor};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::model::blog_post::PostStatus;
+use crate::model::image::is_allowed_image_url;
+use crate::model::post_body::excerpt;
+use crate::model::setting::Settings;
+use crate::repository::blog_repository::find_post;
+
+use super::webhooks::backoff;
+
+const CONFIRM_HTML: &str = include_str!("../../templates/email/confirm.html");
+const CONFIRM_TEXT: &str = include_str!("../../templates/email/confirm.txt");
+const DIGEST_HTML: &str = include_str!("../../templates/email/digest.html");
+const DIGEST_TEXT: &str = include_str!("../../templates/email/digest.txt");
+
+/// How many due emails are sent per round.
+const BATCH_SIZE: i64 = 50;
+
+/// Length of the excerpt in digests of posts without a hand-written one.
+const EXCERPT_LENGTH: usize = 280;
+
+/// Asking to subscribe again within this long doesn't send another confirmation email,
+/// so the form can't be used to flood someone's inbox.
+const CONFIRMATION_RESEND_AFTER_MINUTES: i64 = 10;
+
+/// How the connection to the SMTP server is secured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// TLS from the start, usually on port 465.
+    Tls,
+    /// Upgraded with STARTTLS, usually on port 587.
+    StartTls,
+    /// Plain text, for a local SMTP server or sink.
+    None,
+}
+
+pub struct NewsletterConfig {
+    /// No emails are sent, and nobody can subscribe, without one.
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_security: SmtpSecurity,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    /// The sender of every email, like `Moonbound <news@blog.example.com>`.
+    pub from: String,
+    /// Prefixed to paths in emails, like `https://blog.example.com`.
+    pub site_url: String,
+    /// How many times an email is attempted before it is marked failed.
+    pub max_attempts: i64,
+    /// Before the first retry; later ones back off from it like webhook deliveries do.
+    pub retry_delay: Duration,
+    pub poll_interval: Duration,
+}
+
+impl NewsletterConfig {
+    /// Reads `SMTP_HOST`, `SMTP_PORT` (default depends on `SMTP_SECURITY`), `SMTP_SECURITY`
+    /// (`tls`, `starttls` or `none`, default `starttls`), `SMTP_USERNAME`, `SMTP_PASSWORD`,
+    /// `NEWSLETTER_FROM`, `SITE_URL`, `NEWSLETTER_MAX_ATTEMPTS` (default 5),
+    /// `NEWSLETTER_RETRY_DELAY_SECS` (default 60) and `NEWSLETTER_POLL_INTERVAL_SECS` (default 10).
+    pub fn from_env() -> NewsletterConfig {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let number = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        let smtp_security = match var("SMTP_SECURITY").as_deref() {
+            Some("tls") => SmtpSecurity::Tls,
+            Some("none") => SmtpSecurity::None,
+            Some("starttls") | None => SmtpSecurity::StartTls,
+            Some(other) => {
+                tracing::warn!(value = other, "unknown SMTP_SECURITY, using starttls");
+                SmtpSecurity::StartTls
+            }
+        };
+        let mut smtp_host = var("SMTP_HOST");
+        let from = var("NEWSLETTER_FROM").unwrap_or_default();
+        if smtp_host.is_some() && from.parse::<Mailbox>().is_err() {
+            tracing::warn!(
+                "SMTP_HOST is set but NEWSLETTER_FROM isn't a valid sender, so the newsletter is off"
+            );
+            smtp_host = None;
+        }
+
+        NewsletterConfig {
+            smtp_host,
+            smtp_port: var("SMTP_PORT").and_then(|port| port.parse().ok()),
+            smtp_security,
+            smtp_username: var("SMTP_USERNAME"),
+            smtp_password: var("SMTP_PASSWORD"),
+            from,
+            site_url: std::env::var("SITE_URL")
+                .unwrap_or_default()
+                .trim_end_matches('/')
+                .to_string(),
+            max_attempts: number("NEWSLETTER_MAX_ATTEMPTS", 5) as i64,
+            retry_delay: Duration::from_secs(number("NEWSLETTER_RETRY_DELAY_SECS", 60)),
+            poll_interval: Duration::from_secs(number("NEWSLETTER_POLL_INTERVAL_SECS", 10)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.smtp_host.is_some()
+    }
+
+    fn transport(
+        &self,
+    ) -> Result<AsyncSmtpTransport<Tokio1Executor>, lettre::transport::smtp::Error> {
+        let host = self.smtp_host.as_deref().unwrap_or_default();
+        let mut builder = match self.smtp_security {
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        if let Some(port) = self.smtp_port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&self.smtp_username, &self.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        Ok(builder.build())
+    }
+}
+
+/// The newsletter configuration of the server handling the current request.
+pub async fn config() -> Result<Data<NewsletterConfig>, AppError> {
+    extract(|config: Data<NewsletterConfig>| async move { config })
+        .await
+        .map_err(|e| AppError::Internal(format!("no newsletter configuration: {}", e)))
+}
+
+/// Whether `email` looks like an address mail can be sent to. The confirmation email is
+/// the real check.
+pub fn is_valid_email(email: &str) -> bool {
+    // a bare address, not `Name <address>`
+    email.len() <= 254
+        && !email.contains(|c: char| c.is_whitespace() || c == '<' || c == '>')
+        && email.parse::<Mailbox>().is_ok()
+}
+
+/// `value` with the characters that mean something in HTML escaped.
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `template` with every `{{name}}` replaced by its value. Values go in as they are, so
+/// anything going into an HTML template has to be escaped first. Placeholders in values
+/// are left alone, so a post titled `{{unsubscribe_url}}` stays that way.
+fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let value = after.find("}}").and_then(|end| {
+            let value = values.iter().find(|(name, _)| *name == &after[..end])?.1;
+            Some((value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                rendered.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                rendered.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// The blog's title, as emails are signed with it.
+async fn blog_title(pool: &Pool<Sqlite>) -> Result<String, sqlx::Error> {
+    let title: Option<String> =
+        sqlx::query_scalar("SELECT value FROM setting WHERE key = 'blog_title'")
+            .fetch_optional(pool)
+            .await?;
+    Ok(title.unwrap_or_else(|| Settings::default().blog_title))
+}
+
+/// Adds `email` as a subscriber, or takes it back from having unsubscribed, and sends it a
+/// confirmation email. Confirmed addresses are left alone, and the caller isn't told either
+/// way, so the form doesn't reveal who is subscribed.
+pub async fn subscribe(
+    pool: &Pool<Sqlite>,
+    config: &NewsletterConfig,
+    email: &str,
+) -> Result<(), AppError> {
+    if !config.is_enabled() {
+        return Err(AppError::NotFound(
+            "the newsletter isn't set up".to_string(),
+        ));
+    }
+    let email = email.trim().to_lowercase();
+    if !is_valid_email(&email) {
+        return Err(AppError::Validation(format!(
+            "{:?} isn't an email address",
+            email
+        )));
+    }
+
+    let now = chrono::Local::now().naive_local();
+    let existing: Option<(String, String, Option<NaiveDateTime>)> =
+        sqlx::query_as("SELECT id, status, confirmation_sent_at FROM subscriber WHERE email = ?")
+            .bind(&email)
+            .fetch_optional(pool)
+            .await?;
+
+    let confirm_token = Uuid::new_v4().simple().to_string();
+    let subscriber_id = match existing {
+        Some((_, status, _)) if status == "confirmed" => return Ok(()),
+        Some((_, _, Some(sent_at)))
+            if now - sent_at < chrono::Duration::minutes(CONFIRMATION_RESEND_AFTER_MINUTES) =>
+        {
+            return Ok(());
+        }
+        Some((id, _, _)) => {
+            sqlx::query("UPDATE subscriber SET status = 'pending', confirm_token = ?, confirmation_sent_at = ?, unsubscribed_at = NULL WHERE id = ?")
+                .bind(&confirm_token)
+                .bind(now)
+                .bind(&id)
+                .execute(pool)
+                .await?;
+            id
+        }
+        None => {
+            let id = Uuid::new_v4().to_string();
+            sqlx::query("INSERT INTO subscriber (id, email, confirm_token, unsubscribe_token, created_at, confirmation_sent_at) VALUES ($1, $2, $3, $4, $5, $5)")
+                .bind(&id)
+                .bind(&email)
+                .bind(&confirm_token)
+                .bind(Uuid::new_v4().simple().to_string())
+                .bind(now)
+                .execute(pool)
+                .await?;
+            id
+        }
+    };
+
+    let blog_title = blog_title(pool).await?;
+    let confirm_url = format!("{}/newsletter/confirm/{}", config.site_url, confirm_token);
+    let html = render(
+        CONFIRM_HTML,
+        &[
+            ("blog_title", &escape_html(&blog_title)),
+            ("confirm_url", &escape_html(&confirm_url)),
+        ],
+    );
+    let text = render(
+        CONFIRM_TEXT,
+        &[("blog_title", &blog_title), ("confirm_url", &confirm_url)],
+    );
+    let subject = format!("Confirm your subscription to {}", blog_title);
+    enqueue(
+        pool,
+        &subscriber_id,
+        None,
+        &email,
+        &subject,
+        &html,
+        &text,
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Confirms the subscription with the given confirmation token.
+pub async fn confirm(pool: &Pool<Sqlite>, token: &str) -> Result<(), AppError> {
+    let result = sqlx::query("UPDATE subscriber SET status = 'confirmed', confirmed_at = ? WHERE confirm_token = ? AND status = 'pending'")
+        .bind(chrono::Local::now().naive_local())
+        .bind(token)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        let confirmed: Option<String> =
+            sqlx::query_scalar("SELECT status FROM subscriber WHERE confirm_token = ?")
+                .bind(token)
+                .fetch_optional(pool)
+                .await?;
+        // following the link twice is fine
+        if confirmed.as_deref() != Some("confirmed") {
+            return Err(AppError::NotFound(
+                "this confirmation link is no longer valid".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Ends the subscription with the given unsubscribe token. Emails still queued for it
+/// aren't sent.
+pub async fn unsubscribe(pool: &Pool<Sqlite>, token: &str) -> Result<(), AppError> {
+    let subscriber_id: Option<String> =
+        sqlx::query_scalar("SELECT id FROM subscriber WHERE unsubscribe_token = ?")
+            .bind(token)
+            .fetch_optional(pool)
+            .await?;
+    let Some(subscriber_id) = subscriber_id else {
+        return Err(AppError::NotFound(
+            "this unsubscribe link is no longer valid".to_string(),
+        ));
+    };
+
+    sqlx::query("UPDATE subscriber SET status = 'unsubscribed', unsubscribed_at = ? WHERE id = ? AND status != 'unsubscribed'")
+        .bind(chrono::Local::now().naive_local())
+        .bind(&subscriber_id)
+        .execute(pool)
+        .await?;
+    sqlx::query("UPDATE newsletter_email SET status = 'failed', last_error = 'unsubscribed' WHERE subscriber_id = ? AND status = 'pending'")
+        .bind(&subscriber_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Queues an email about the newly published post `post_id` for every confirmed subscriber,
+/// which the background task writes and sends.
+///
+/// Like [`post_event`](super::webhooks::post_event), this runs after the post has been
+/// saved, so a failure is logged rather than returned.
+pub async fn post_published(pool: &Pool<Sqlite>, post_id: &str) {
+    let config = match config().await {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!(error = %e, "no newsletter configuration");
+            return;
+        }
+    };
+    if !config.is_enabled() {
+        return;
+    }
+    if let Err(e) = queue_digest(pool, post_id).await {
+        tracing::error!(post_id, error = %e, "could not queue the newsletter");
+    }
+}
+
+/// Leaves `post_id` for the background task, which writes the emails in
+/// [`write_digests`].
+async fn queue_digest(pool: &Pool<Sqlite>, post_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT OR IGNORE INTO newsletter_digest (post_id, created_at) VALUES (?, ?)")
+        .bind(post_id)
+        .bind(chrono::Local::now().naive_local())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Turns every queued digest into an email per confirmed subscriber.
+async fn write_digests(pool: &Pool<Sqlite>, config: &NewsletterConfig) -> Result<(), AppError> {
+    let post_ids: Vec<String> =
+        sqlx::query_scalar("SELECT post_id FROM newsletter_digest ORDER BY created_at")
+            .fetch_all(pool)
+            .await?;
+    for post_id in post_ids {
+        write_digest(pool, config, &post_id).await?;
+    }
+    Ok(())
+}
+
+/// Queues the emails about `post_id`, and takes it off the digests to write, all at once.
+async fn write_digest(
+    pool: &Pool<Sqlite>,
+    config: &NewsletterConfig,
+    post_id: &str,
+) -> Result<(), AppError> {
+    // unpublished or trashed before its turn came, so nobody hears about it
+    let post = find_post(pool, post_id)
+        .await?
+        .filter(|post| post.status == PostStatus::Published && post.deleted_at.is_none());
+    let Some(post) = post else {
+        sqlx::query("DELETE FROM newsletter_digest WHERE post_id = ?")
+            .bind(post_id)
+            .execute(pool)
+            .await?;
+        return Ok(());
+    };
+    let blog_title = blog_title(pool).await?;
+    let post_url = format!("{}/view/{}", config.site_url, post.id);
+    let post_excerpt = post
+        .excerpt
+        .clone()
+        .unwrap_or_else(|| excerpt(&post.text, EXCERPT_LENGTH));
+    // email clients load images themselves, so they get the image's own URL; `data:` URLs
+    // are left out, as most clients don't show them
+    let image_url = post.image_url.trim();
+    let image = match image_url {
+        "" => String::new(),
+        url if url.starts_with('/') && !url.starts_with("//") => {
+            format!("{}{}", config.site_url, url)
+        }
+        url if is_allowed_image_url(url) && !url.to_ascii_lowercase().starts_with("data:") => {
+            url.to_string()
+        }
+        _ => String::new(),
+    };
+    let image_html = if image.is_empty() {
+        String::new()
+    } else {
+        format!(
+            r#"<img src="{}" alt="" style="display: block; width: 100%; max-height: 320px; object-fit: cover; border-radius: 4px;">"#,
+            escape_html(&image)
+        )
+    };
+    let subject = format!("{}: {}", blog_title, post.title);
+
+    let mut tx = pool.begin().await?;
+    let subscribers: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT id, email, unsubscribe_token FROM subscriber WHERE status = 'confirmed'",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for (subscriber_id, email, unsubscribe_token) in subscribers {
+        let unsubscribe_url = format!(
+            "{}/newsletter/unsubscribe/{}",
+            config.site_url, unsubscribe_token
+        );
+        let html = render(
+            DIGEST_HTML,
+            &[
+                ("blog_title", &escape_html(&blog_title)),
+                ("title", &escape_html(&post.title)),
+                ("excerpt", &escape_html(&post_excerpt)),
+                ("image", &image_html),
+                ("post_url", &escape_html(&post_url)),
+                ("unsubscribe_url", &escape_html(&unsubscribe_url)),
+            ],
+        );
+        let text = render(
+            DIGEST_TEXT,
+            &[
+                ("blog_title", &blog_title),
+                ("title", &post.title),
+                ("excerpt", &post_excerpt),
+                ("post_url", &post_url),
+                ("unsubscribe_url", &unsubscribe_url),
+            ],
+        );
+        enqueue(
+            &mut *tx,
+            &subscriber_id,
+            Some(post_id),
+            &email,
+            &subject,
+            &html,
+            &text,
+            Some(&unsubscribe_url),
+        )
+        .await?;
+    }
+    sqlx::query("DELETE FROM newsletter_digest WHERE post_id = ?")
+        .bind(post_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn enqueue(
+    conn: impl SqliteExecutor<'_>,
+    subscriber_id: &str,
+    post_id: Option<&str>,
+    to_address: &str,
+    subject: &str,
+    html: &str,
+    text: &str,
+    unsubscribe_url: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    // a digest already queued for this post and subscriber is kept
+    sqlx::query("INSERT OR IGNORE INTO newsletter_email (id, subscriber_id, post_id, to_address, subject, html_body, text_body, unsubscribe_url, created_at, next_attempt_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $9)")
+        .bind(Uuid::new_v4().to_string())
+        .bind(subscriber_id)
+        .bind(post_id)
+        .bind(to_address)
+        .bind(subject)
+        .bind(html)
+        .bind(text)
+        .bind(unsubscribe_url)
+        .bind(chrono::Local::now().naive_local())
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// `List-Unsubscribe`, which lets mail clients offer an unsubscribe button.
+#[derive(Clone)]
+struct ListUnsubscribe(String);
+
+impl Header for ListUnsubscribe {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe")
+    }
+
+    fn parse(s: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(ListUnsubscribe(
+            s.trim_start_matches('<').trim_end_matches('>').to_string(),
+        ))
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), format!("<{}>", self.0))
+    }
+}
+
+/// An email that is due to be sent.
+#[derive(FromRow)]
+struct DueEmail {
+    id: String,
+    to_address: String,
+    subject: String,
+    html_body: String,
+    text_body: String,
+    unsubscribe_url: Option<String>,
+    attempts: i64,
+}
+
+/// Periodically sends the emails that are due.
+pub fn spawn_newsletter_task(pool: Pool<Sqlite>, config: Data<NewsletterConfig>) {
+    if !config.is_enabled() {
+        return;
+    }
+    let transport = match config.transport() {
+        Ok(transport) => transport,
+        Err(e) => {
+            tracing::error!(error = %e, "could not set up SMTP, so no emails will be sent");
+            return;
+        }
+    };
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(config.poll_interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = write_digests(&pool, &config).await {
+                tracing::error!(error = %e, "could not write newsletter digests");
+            }
+            if let Err(e) = send_due(&pool, &transport, &config).await {
+                tracing::error!(error = %e, "could not send newsletter emails");
+            }
+        }
+    });
+}
+
+async fn send_due(
+    pool: &Pool<Sqlite>,
+    transport: &AsyncSmtpTransport<Tokio1Executor>,
+    config: &NewsletterConfig,
+) -> Result<(), sqlx::Error> {
+    let now = chrono::Local::now().naive_local();
+    let due: Vec<DueEmail> = sqlx::query_as(
+        "SELECT id, to_address, subject, html_body, text_body, unsubscribe_url, attempts FROM newsletter_email WHERE status = 'pending' AND next_attempt_at <= ? ORDER BY created_at LIMIT ?",
+    )
+    .bind(now)
+    .bind(BATCH_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    for email in due {
+        let attempts = email.attempts + 1;
+        let result = match message(config, &email) {
+            Ok(message) => transport
+                .send(message)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        let now = chrono::Local::now().naive_local();
+
+        match result {
+            Ok(()) => {
+                tracing::info!(email = %email.id, "sent newsletter email");
+                sqlx::query("UPDATE newsletter_email SET status = 'sent', attempts = ?, last_error = NULL, sent_at = ? WHERE id = ?")
+                    .bind(attempts)
+                    .bind(now)
+                    .bind(&email.id)
+                    .execute(pool)
+                    .await?;
+            }
+            Err(error) => {
+                let status = if attempts >= config.max_attempts {
+                    "failed"
+                } else {
+                    "pending"
+                };
+                let next_attempt_at = now
+                    + chrono::Duration::from_std(backoff(config.retry_delay, attempts))
+                        .unwrap_or(chrono::Duration::zero());
+                tracing::warn!(
+                    email = %email.id,
+                    attempts,
+                    error = %error,
+                    "could not send newsletter email"
+                );
+                sqlx::query("UPDATE newsletter_email SET status = ?, attempts = ?, last_error = ?, next_attempt_at = ? WHERE id = ?")
+                    .bind(status)
+                    .bind(attempts)
+                    .bind(error)
+                    .bind(next_attempt_at)
+                    .bind(&email.id)
+                    .execute(pool)
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The email to send, in HTML with a plain-text alternative.
+fn message(config: &NewsletterConfig, email: &DueEmail) -> Result<Message, String> {
+    let from: Mailbox = config
+        .from
+        .parse()
+        .map_err(|e| format!("invalid sender: {}", e))?;
+    let to: Mailbox = email
+        .to_address
+        .parse()
+        .map_err(|e| format!("invalid recipient: {}", e))?;
+    let mut builder = Message::builder()
+        .from(from)
+        .to(to)
+        .subject(email.subject.as_str());
+    if let Some(url) = &email.unsubscribe_url {
+        builder = builder.header(ListUnsubscribe(url.clone()));
+    }
+    builder
+        .multipart(MultiPart::alternative_plain_html(
+            email.text_body.clone(),
+            email.html_body.clone(),
+        ))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::server::test_support::test_pool;
+
+    const SITE_URL: &str = "https://blog.example.com";
+
+    /// The recipient and raw content of every message an [`smtp_sink`] was given.
+    type Received = Arc<Mutex<Vec<(String, String)>>>;
+
+    /// A local SMTP server that accepts every message, standing in for a real one.
+    /// Returns its port.
+    fn smtp_sink(received: Received) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let received = received.clone();
+                std::thread::spawn(move || {
+                    let _ = serve_smtp(stream, received);
+                });
+            }
+        });
+        port
+    }
+
+    fn serve_smtp(stream: TcpStream, received: Received) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        writer.write_all(b"220 sink ESMTP\r\n")?;
+        let mut recipient = String::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let command = line.to_ascii_uppercase();
+            if command.starts_with("RCPT TO:") {
+                recipient = line[8..].trim().trim_matches(['<', '>']).to_string();
+                writer.write_all(b"250 ok\r\n")?;
+            } else if command.starts_with("DATA") {
+                writer.write_all(b"354 go ahead\r\n")?;
+                let mut message = String::new();
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line)? == 0 || line == ".\r\n" {
+                        break;
+                    }
+                    message.push_str(&line);
+                }
+                received.lock().unwrap().push((recipient.clone(), message));
+                writer.write_all(b"250 queued\r\n")?;
+            } else if command.starts_with("QUIT") {
+                writer.write_all(b"221 bye\r\n")?;
+                return Ok(());
+            } else {
+                writer.write_all(b"250 ok\r\n")?;
+            }
+        }
+    }
+
+    fn config(port: u16) -> NewsletterConfig {
+        NewsletterConfig {
+            smtp_host: Some("127.0.0.1".to_string()),
+            smtp_port: Some(port),
+            smtp_security: SmtpSecurity::None,
+            smtp_username: None,
+            smtp_password: None,
+            from: "Moonbound <news@blog.example.com>".to_string(),
+            site_url: SITE_URL.to_string(),
+            max_attempts: 3,
+            retry_delay: Duration::from_secs(60),
+            poll_interval: Duration::from_secs(10),
+        }
+    }
+
+    /// Sends what is due through the sink, and returns what it received.
+    async fn send(
+        pool: &Pool<Sqlite>,
+        config: &NewsletterConfig,
+        received: &Received,
+    ) -> Vec<(String, String)> {
+        send_due(pool, &config.transport().unwrap(), config)
+            .await
+            .unwrap();
+        std::mem::take(&mut *received.lock().unwrap())
+    }
+
+    async fn subscriber(pool: &Pool<Sqlite>, email: &str) -> (String, String, String) {
+        sqlx::query_as(
+            "SELECT status, confirm_token, unsubscribe_token FROM subscriber WHERE email = ?",
+        )
+        .bind(email)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    /// Every queued email's recipient, status and bodies, oldest first.
+    async fn emails(pool: &Pool<Sqlite>) -> Vec<(String, String, String, String)> {
+        sqlx::query_as("SELECT to_address, status, html_body, text_body FROM newsletter_email ORDER BY created_at")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn insert_post(pool: &Pool<Sqlite>, title: &str, excerpt: &str) -> String {
+        let id = Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO post (id, dt, image_url, title, text, excerpt) VALUES (?, ?, '', ?, 'The text.', ?)")
+            .bind(&id)
+            .bind(chrono::Local::now().naive_local())
+            .bind(title)
+            .bind(excerpt)
+            .execute(pool)
+            .await
+            .unwrap();
+        id
+    }
+
+    /// Queues the post's digest and writes its emails, as the background task would.
+    async fn publish(pool: &Pool<Sqlite>, config: &NewsletterConfig, post_id: &str) {
+        queue_digest(pool, post_id).await.unwrap();
+        write_digests(pool, config).await.unwrap();
+    }
+
+    async fn subscribe_and_confirm(pool: &Pool<Sqlite>, config: &NewsletterConfig, email: &str) {
+        subscribe(pool, config, email).await.unwrap();
+        let (_, confirm_token, _) = subscriber(pool, email).await;
+        confirm(pool, &confirm_token).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn only_sends_posts_to_confirmed_addresses() {
+        let pool = test_pool().await;
+        let received = Received::default();
+        let config = config(smtp_sink(received.clone()));
+
+        subscribe(&pool, &config, " Reader@Example.com ")
+            .await
+            .unwrap();
+        let (status, confirm_token, _) = subscriber(&pool, "reader@example.com").await;
+        assert_eq!(status, "pending");
+        let sent = send(&pool, &config, &received).await;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, "reader@example.com");
+        let (_, _, html, text) = emails(&pool).await.remove(0);
+        let confirm_url = format!("{}/newsletter/confirm/{}", SITE_URL, confirm_token);
+        assert!(html.contains(&confirm_url) && text.contains(&confirm_url));
+
+        // not confirmed yet, so nothing to send
+        let post_id = insert_post(&pool, "First", "").await;
+        publish(&pool, &config, &post_id).await;
+        assert!(send(&pool, &config, &received).await.is_empty());
+
+        confirm(&pool, &confirm_token).await.unwrap();
+        // following the link again is fine, a made-up one isn't
+        confirm(&pool, &confirm_token).await.unwrap();
+        assert!(confirm(&pool, "made-up").await.is_err());
+
+        let post_id = insert_post(&pool, "Second", "").await;
+        publish(&pool, &config, &post_id).await;
+        // queuing a post twice, as when it is published again, sends it once
+        publish(&pool, &config, &post_id).await;
+        let sent = send(&pool, &config, &received).await;
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0]
+            .1
+            .contains("List-Unsubscribe: <https://blog.example.com/newsletter/unsubscribe/"));
+    }
+
+    #[actix_web::test]
+    async fn publishing_leaves_the_emails_to_the_background_task() {
+        let pool = test_pool().await;
+        let config = config(smtp_sink(Received::default()));
+        for email in ["one@example.com", "two@example.com"] {
+            subscribe_and_confirm(&pool, &config, email).await;
+        }
+
+        let post_id = insert_post(&pool, "Post", "").await;
+        queue_digest(&pool, &post_id).await.unwrap();
+        queue_digest(&pool, &post_id).await.unwrap();
+        assert_eq!(emails(&pool).await.len(), 2);
+
+        write_digests(&pool, &config).await.unwrap();
+        assert_eq!(emails(&pool).await.len(), 4);
+        let queued: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM newsletter_digest")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(queued, 0);
+    }
+
+    #[actix_web::test]
+    async fn throttles_confirmation_emails() {
+        let pool = test_pool().await;
+        let config = config(smtp_sink(Received::default()));
+
+        subscribe(&pool, &config, "reader@example.com")
+            .await
+            .unwrap();
+        let (_, first_token, _) = subscriber(&pool, "reader@example.com").await;
+        subscribe(&pool, &config, "reader@example.com")
+            .await
+            .unwrap();
+        assert_eq!(emails(&pool).await.len(), 1);
+        assert_eq!(subscriber(&pool, "reader@example.com").await.1, first_token);
+
+        let earlier = chrono::Local::now().naive_local()
+            - chrono::Duration::minutes(CONFIRMATION_RESEND_AFTER_MINUTES + 1);
+        sqlx::query("UPDATE subscriber SET confirmation_sent_at = ?")
+            .bind(earlier)
+            .execute(&pool)
+            .await
+            .unwrap();
+        subscribe(&pool, &config, "reader@example.com")
+            .await
+            .unwrap();
+        assert_eq!(emails(&pool).await.len(), 2);
+        // only the newest link works
+        let (_, second_token, _) = subscriber(&pool, "reader@example.com").await;
+        assert_ne!(second_token, first_token);
+        assert!(confirm(&pool, &first_token).await.is_err());
+        confirm(&pool, &second_token).await.unwrap();
+
+        // confirmed addresses get nothing more
+        subscribe(&pool, &config, "reader@example.com")
+            .await
+            .unwrap();
+        assert_eq!(emails(&pool).await.len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn unsubscribing_cancels_queued_emails() {
+        let pool = test_pool().await;
+        let received = Received::default();
+        let config = config(smtp_sink(received.clone()));
+        subscribe_and_confirm(&pool, &config, "reader@example.com").await;
+        send(&pool, &config, &received).await;
+
+        let post_id = insert_post(&pool, "Post", "").await;
+        publish(&pool, &config, &post_id).await;
+        let (_, _, unsubscribe_token) = subscriber(&pool, "reader@example.com").await;
+        unsubscribe(&pool, &unsubscribe_token).await.unwrap();
+
+        assert!(send(&pool, &config, &received).await.is_empty());
+        assert_eq!(emails(&pool).await[1].1, "failed");
+        assert_eq!(
+            subscriber(&pool, "reader@example.com").await.0,
+            "unsubscribed"
+        );
+
+        // and nothing new is queued
+        let post_id = insert_post(&pool, "Another", "").await;
+        publish(&pool, &config, &post_id).await;
+        assert_eq!(emails(&pool).await.len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn digests_escape_the_post() {
+        let pool = test_pool().await;
+        let config = config(smtp_sink(Received::default()));
+        subscribe_and_confirm(&pool, &config, "reader@example.com").await;
+
+        let title = r#"<script>"{{unsubscribe_url}}""#;
+        let excerpt = r#"a < b & "{{post_url}}""#;
+        let post_id = insert_post(&pool, title, excerpt).await;
+        publish(&pool, &config, &post_id).await;
+
+        let (_, _, html, text) = emails(&pool).await.remove(1);
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;&quot;{{unsubscribe_url}}&quot;"));
+        assert!(html.contains("a &lt; b &amp; &quot;{{post_url}}&quot;"));
+        // the text version is plain, so it is left as it is
+        assert!(text.contains(title) && text.contains(excerpt));
+        // placeholders in the post aren't filled in, only the template's are
+        let unsubscribe_url = format!("{}/newsletter/unsubscribe/", SITE_URL);
+        assert_eq!(html.matches(&unsubscribe_url).count(), 1);
+        assert_eq!(text.matches(&unsubscribe_url).count(), 1);
+    }
+
+    #[test]
+    fn renders_placeholders_once() {
+        assert_eq!(
+            render("{{a}} and {{b}} and {{c}}", &[("a", "{{b}}"), ("b", "<b>")]),
+            "{{b}} and <b> and {{c}}"
+        );
+        assert_eq!(render("{{a", &[("a", "x")]), "{{a");
+        assert_eq!(
+            escape_html(r#"<a href="x">'&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+}
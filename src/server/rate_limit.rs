@@ -0,0 +1,497 @@
+//! Token-bucket rate limiting for pages and server functions, per client IP and per session.
+//!
+//! Every client has a bucket of tokens for reads and another for writes. Each request takes
+//! a token, and buckets refill at a steady rate up to their size, which allows short bursts
+//! while capping the sustained rate.
+
+use std::collections::HashMap;
+use std::future::{ready, Future, Ready};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{AUTHORIZATION, RETRY_AFTER};
+use actix_web::http::Method;
+use actix_web::web::Data;
+use actix_web::{Error, HttpResponse};
+
+use super::auth::SESSION_COOKIE;
+use super::csrf::READ_ONLY_SERVER_FNS;
+use super::metrics::server_fn_name;
+
+/// Where the time comes from, so tests can move it along by hand.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// How many requests a bucket holds, and how fast it refills.
+#[derive(Clone, Copy, Debug)]
+pub struct Limit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl Limit {
+    fn from_env(prefix: &str, default: Limit) -> Limit {
+        let var = |name: &str| {
+            std::env::var(format!("{}_{}", prefix, name))
+                .ok()
+                .and_then(|value| value.parse().ok())
+        };
+        Limit {
+            burst: var("BURST").unwrap_or(default.burst),
+            per_minute: var("PER_MINUTE").unwrap_or(default.per_minute),
+        }
+    }
+
+    fn per_second(&self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+pub struct RateLimitConfig {
+    pub reads: Limit,
+    pub writes: Limit,
+    /// Proxies whose `X-Forwarded-For` is believed. Anyone else could put any address there.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl RateLimitConfig {
+    /// Reads `RATE_LIMIT_READ_BURST` (default 120), `RATE_LIMIT_READ_PER_MINUTE` (default 600),
+    /// `RATE_LIMIT_WRITE_BURST` (default 10), `RATE_LIMIT_WRITE_PER_MINUTE` (default 30) and
+    /// `TRUSTED_PROXIES`, a comma-separated list of IP addresses (default none).
+    /// A `PER_MINUTE` of 0 turns that limit off.
+    pub fn from_env() -> RateLimitConfig {
+        let trusted_proxies = std::env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            .filter_map(|proxy| match proxy.parse() {
+                Ok(ip) => Some(ip),
+                Err(_) => {
+                    tracing::warn!(
+                        "ignoring trusted proxy {:?}, which isn't an IP address",
+                        proxy
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        RateLimitConfig {
+            reads: Limit::from_env(
+                "RATE_LIMIT_READ",
+                Limit {
+                    burst: 120,
+                    per_minute: 600,
+                },
+            ),
+            writes: Limit::from_env(
+                "RATE_LIMIT_WRITE",
+                Limit {
+                    burst: 10,
+                    per_minute: 30,
+                },
+            ),
+            trusted_proxies,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Kind {
+    Read,
+    Write,
+}
+
+/// Who a bucket belongs to.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum Client {
+    Ip(IpAddr),
+    /// A session cookie or bearer token, so clients sharing an address don't share a limit.
+    Credential(String),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Buckets are forgotten once this many are kept, if they have filled up again since.
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+/// How often full buckets are looked for at most, so a busy server doesn't go through all
+/// of them on every request.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Buckets {
+    buckets: HashMap<(Client, Kind), Bucket>,
+    swept: Instant,
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    clock: Box<dyn Clock>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter::with_clock(config, SystemClock)
+    }
+
+    pub fn with_clock(config: RateLimitConfig, clock: impl Clock + 'static) -> RateLimiter {
+        let swept = clock.now();
+        RateLimiter {
+            config,
+            clock: Box::new(clock),
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                swept,
+            }),
+        }
+    }
+
+    fn limit(&self, kind: Kind) -> Limit {
+        match kind {
+            Kind::Read => self.config.reads,
+            Kind::Write => self.config.writes,
+        }
+    }
+
+    /// Takes a token from each of `clients`' buckets. If any of them is empty, none is
+    /// taken, and the result is how long until that one has a token again.
+    fn check(&self, clients: &[Client], kind: Kind) -> Result<(), Duration> {
+        let limit = self.limit(kind);
+        if limit.per_minute == 0 {
+            return Ok(());
+        }
+        let burst = f64::from(limit.burst.max(1));
+        let now = self.clock.now();
+        let mut state = self.buckets.lock().unwrap();
+        if state.buckets.len() > MAX_IDLE_BUCKETS
+            && now.duration_since(state.swept) >= SWEEP_INTERVAL
+        {
+            state.buckets.retain(|(_, kind), bucket| {
+                let limit = self.limit(*kind);
+                let refilled =
+                    now.duration_since(bucket.updated).as_secs_f64() * limit.per_second();
+                bucket.tokens + refilled < f64::from(limit.burst.max(1))
+            });
+            state.swept = now;
+        }
+        let buckets = &mut state.buckets;
+
+        let mut wait = Duration::ZERO;
+        for client in clients {
+            let bucket = buckets.entry((client.clone(), kind)).or_insert(Bucket {
+                tokens: burst,
+                updated: now,
+            });
+            let refilled = now.duration_since(bucket.updated).as_secs_f64() * limit.per_second();
+            bucket.tokens = (bucket.tokens + refilled).min(burst);
+            bucket.updated = now;
+            if bucket.tokens < 1.0 {
+                let until_refilled = (1.0 - bucket.tokens) / limit.per_second();
+                wait = wait.max(Duration::from_secs_f64(until_refilled));
+            }
+        }
+        if wait > Duration::ZERO {
+            return Err(wait);
+        }
+
+        for client in clients {
+            if let Some(bucket) = buckets.get_mut(&(client.clone(), kind)) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// The address the request came from. Behind a trusted proxy, that's the last address in
+    /// `X-Forwarded-For` that wasn't added by one of the trusted proxies.
+    fn client_ip(&self, req: &ServiceRequest) -> Option<IpAddr> {
+        let peer = req.peer_addr()?.ip();
+        if !self.config.trusted_proxies.contains(&peer) {
+            return Some(peer);
+        }
+
+        let forwarded: Vec<IpAddr> = req
+            .headers()
+            .get_all("x-forwarded-for")
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|ip| ip.trim().parse().ok())
+            .collect();
+        Some(
+            forwarded
+                .into_iter()
+                .rev()
+                .find(|ip| !self.config.trusted_proxies.contains(ip))
+                .unwrap_or(peer),
+        )
+    }
+}
+
+/// What kind of request this is, or `None` for requests that aren't limited: compiled files,
+/// assets, and the endpoints for monitoring.
+fn kind_of(req: &ServiceRequest) -> Option<Kind> {
+    let path = req.path();
+    if let Some(name) = server_fn_name(path) {
+        // server functions are all POSTs, so go by what they do
+        return Some(if READ_ONLY_SERVER_FNS.contains(&name.as_str()) {
+            Kind::Read
+        } else {
+            Kind::Write
+        });
+    }
+    if ["/pkg/", "/assets/"]
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+        || ["/favicon.ico", "/healthz", "/readyz", "/metrics"].contains(&path)
+    {
+        return None;
+    }
+    Some(match *req.method() {
+        Method::GET | Method::HEAD | Method::OPTIONS => Kind::Read,
+        _ => Kind::Write,
+    })
+}
+
+/// The session cookie or bearer token sent with the request, if any.
+fn credential(req: &ServiceRequest) -> Option<String> {
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        return Some(cookie.value().to_string());
+    }
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string)
+}
+
+/// Middleware answering 429 Too Many Requests, with `Retry-After`, to clients over their limit.
+pub struct RateLimit;
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let limiter = req.app_data::<Data<RateLimiter>>().cloned();
+            let (Some(kind), Some(limiter)) = (kind_of(&req), limiter) else {
+                return service.call(req).await.map(|res| res.map_into_boxed_body());
+            };
+
+            let clients: Vec<Client> = limiter
+                .client_ip(&req)
+                .map(Client::Ip)
+                .into_iter()
+                .chain(credential(&req).map(Client::Credential))
+                .collect();
+            if let Err(wait) = limiter.check(&clients, kind) {
+                // whole seconds, rounded up, so retrying on time succeeds
+                let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+                tracing::info!(?kind, path = req.path(), retry_after, "rate limited");
+                let res = HttpResponse::TooManyRequests()
+                    .insert_header((RETRY_AFTER, retry_after.to_string()))
+                    .body("Too many requests, please slow down.");
+                return Ok(req.into_response(res));
+            }
+
+            service.call(req).await.map(|res| res.map_into_boxed_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::http::StatusCode;
+    use actix_web::{test, web, App};
+
+    use super::*;
+
+    /// A clock that only moves when told to.
+    #[derive(Clone)]
+    struct ManualClock(Arc<Mutex<Instant>>);
+
+    impl ManualClock {
+        fn new() -> ManualClock {
+            ManualClock(Arc::new(Mutex::new(Instant::now())))
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    const PROXY: &str = "10.0.0.1";
+
+    /// Reads of 2 at a time and 60 a minute, so one token a second.
+    fn limiter(clock: ManualClock) -> RateLimiter {
+        let limit = Limit {
+            burst: 2,
+            per_minute: 60,
+        };
+        let config = RateLimitConfig {
+            reads: limit,
+            writes: limit,
+            trusted_proxies: vec![PROXY.parse().unwrap()],
+        };
+        RateLimiter::with_clock(config, clock)
+    }
+
+    /// Sends a GET from `peer`, with `forwarded_for` as its `X-Forwarded-For` if given.
+    async fn get(
+        app: &impl Service<actix_http::Request, Response = ServiceResponse<BoxBody>, Error = Error>,
+        peer: &str,
+        forwarded_for: Option<&str>,
+    ) -> ServiceResponse<BoxBody> {
+        let mut req = test::TestRequest::get()
+            .uri("/")
+            .peer_addr(format!("{}:1234", peer).parse().unwrap());
+        if let Some(forwarded_for) = forwarded_for {
+            req = req.insert_header(("x-forwarded-for", forwarded_for));
+        }
+        test::call_service(app, req.to_request()).await
+    }
+
+    async fn app(
+        clock: ManualClock,
+    ) -> impl Service<actix_http::Request, Response = ServiceResponse<BoxBody>, Error = Error> {
+        test::init_service(
+            App::new()
+                .app_data(Data::new(limiter(clock)))
+                .route("/", web::get().to(|| async { HttpResponse::Ok().finish() }))
+                .wrap(RateLimit),
+        )
+        .await
+    }
+
+    #[actix_web::test]
+    async fn allows_a_burst_then_answers_429() {
+        let app = app(ManualClock::new()).await;
+        for _ in 0..2 {
+            assert_eq!(get(&app, "192.0.2.1", None).await.status(), StatusCode::OK);
+        }
+        let res = get(&app, "192.0.2.1", None).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers().get(RETRY_AFTER).unwrap(), "1");
+
+        // others have buckets of their own
+        assert_eq!(get(&app, "192.0.2.2", None).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn refills_over_time() {
+        let clock = ManualClock::new();
+        let app = app(clock.clone()).await;
+        for _ in 0..2 {
+            get(&app, "192.0.2.1", None).await;
+        }
+        clock.advance(Duration::from_millis(500));
+        let res = get(&app, "192.0.2.1", None).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers().get(RETRY_AFTER).unwrap(), "1");
+
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(get(&app, "192.0.2.1", None).await.status(), StatusCode::OK);
+        assert_eq!(
+            get(&app, "192.0.2.1", None).await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        // never more than the burst, however long it has been
+        clock.advance(Duration::from_secs(3600));
+        for _ in 0..2 {
+            assert_eq!(get(&app, "192.0.2.1", None).await.status(), StatusCode::OK);
+        }
+        assert_eq!(
+            get(&app, "192.0.2.1", None).await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[actix_web::test]
+    async fn believes_forwarded_for_only_from_trusted_proxies() {
+        let app = app(ManualClock::new()).await;
+
+        // behind the proxy, clients are told apart by the address it forwarded for
+        for _ in 0..2 {
+            assert_eq!(
+                get(&app, PROXY, Some("192.0.2.1")).await.status(),
+                StatusCode::OK
+            );
+        }
+        let limited = get(&app, PROXY, Some("192.0.2.1")).await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        // an address the client put in front of its own doesn't help it
+        let spoofed = get(&app, PROXY, Some("198.51.100.7, 192.0.2.1")).await;
+        assert_eq!(spoofed.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            get(&app, PROXY, Some("192.0.2.2")).await.status(),
+            StatusCode::OK
+        );
+
+        // anyone else is limited by their own address, whatever they claim
+        for forwarded_for in ["192.0.2.3", "192.0.2.4"] {
+            let res = get(&app, "203.0.113.9", Some(forwarded_for)).await;
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+        let res = get(&app, "203.0.113.9", Some("192.0.2.5")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
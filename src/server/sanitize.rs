@@ -0,0 +1,149 @@
+//! Cleaning HTML written by authors before it reaches readers. Anything rendered as HTML
+//! rather than text, with `inner_html` or otherwise, has to go through [`sanitize_html`].
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use crate::model::post_body::{parse_blocks, Block};
+use crate::server::images::ImagePolicy;
+
+/// Formatting, links and images; no scripts, styles, forms or frames.
+const ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "del",
+    "em",
+    "figcaption",
+    "figure",
+    "h2",
+    "h3",
+    "h4",
+    "hr",
+    "i",
+    "img",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "strong",
+    "sub",
+    "sup",
+    "ul",
+];
+
+/// Schemes allowed in links. `javascript:` and `data:` are the ones this is about.
+const ALLOWED_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// `html` with everything outside the allowlist removed, and images shown as `images`
+/// allows.
+pub fn sanitize_html(html: &str, images: &ImagePolicy) -> String {
+    let tag_attributes: HashMap<&str, HashSet<&str>> = [
+        ("a", ["href", "title"].into_iter().collect()),
+        ("abbr", ["title"].into_iter().collect()),
+        (
+            "img",
+            ["src", "alt", "title", "width", "height"]
+                .into_iter()
+                .collect(),
+        ),
+    ]
+    .into_iter()
+    .collect();
+
+    ammonia::Builder::default()
+        .tags(ALLOWED_TAGS.iter().copied().collect())
+        .tag_attributes(tag_attributes)
+        .generic_attributes(HashSet::new())
+        .url_schemes(ALLOWED_URL_SCHEMES.iter().copied().collect())
+        .link_rel(Some("noopener noreferrer nofollow"))
+        .attribute_filter(|element, attribute, value| match (element, attribute) {
+            ("img", "src") => images.src(value).map(Cow::Owned),
+            _ => Some(value.into()),
+        })
+        .clean(html)
+        .to_string()
+}
+
+/// The paragraphs of a post's text, sanitized, for
+/// [`Post::paragraphs_html`](crate::model::blog_post::Post::paragraphs_html).
+pub fn paragraphs_html(text: &str, images: &ImagePolicy) -> Vec<String> {
+    parse_blocks(text)
+        .into_iter()
+        .filter_map(|block| match block {
+            Block::Paragraph(paragraph) => Some(sanitize_html(&paragraph, images)),
+            Block::Heading(_) => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(html: &str) -> String {
+        sanitize_html(html, &ImagePolicy::for_tests(false))
+    }
+
+    #[test]
+    fn removes_scripts() {
+        assert_eq!(clean("<p>hi<script>alert(1)</script></p>"), "<p>hi</p>");
+        assert_eq!(clean("<iframe src=\"https://example.com\"></iframe>"), "");
+    }
+
+    #[test]
+    fn removes_event_handlers() {
+        assert_eq!(
+            clean("<b onclick=\"alert(1)\">bold</b><img src=\"/a.png\" onerror=\"alert(1)\">"),
+            "<b>bold</b><img src=\"/a.png\">"
+        );
+    }
+
+    #[test]
+    fn removes_script_and_data_links() {
+        for href in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            "data:text/html,<script>alert(1)</script>",
+        ] {
+            let html = format!("<a href=\"{}\">link</a>", href);
+            assert_eq!(
+                clean(&html),
+                "<a rel=\"noopener noreferrer nofollow\">link</a>",
+                "{}",
+                href
+            );
+        }
+        assert_eq!(
+            clean("<a href=\"https://example.com\">link</a>"),
+            "<a href=\"https://example.com\" rel=\"noopener noreferrer nofollow\">link</a>"
+        );
+    }
+
+    #[test]
+    fn shows_images_as_the_policy_allows() {
+        assert_eq!(clean("<img src=\"javascript:alert(1)\">"), "<img>");
+        let proxied = sanitize_html(
+            "<img src=\"https://example.com/a.png\">",
+            &ImagePolicy::for_tests(true),
+        );
+        assert!(
+            proxied.starts_with("<img src=\"/image?url=https%3A%2F%2Fexample.com%2Fa.png&amp;sig=")
+        );
+    }
+
+    #[test]
+    fn keeps_text_as_text() {
+        assert_eq!(
+            paragraphs_html(
+                "# Title\n\na < b & c\n\n<em>then</em>",
+                &ImagePolicy::for_tests(false)
+            ),
+            ["a &lt; b &amp; c", "<em>then</em>"]
+        );
+    }
+}
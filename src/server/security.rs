@@ -0,0 +1,223 @@
+//! Security headers on every response: Content-Security-Policy, HSTS and the rest.
+//!
+//! The CSP only lets scripts run if they carry the nonce Leptos generates for each render,
+//! which it puts on the hydration script and the inline scripts it streams. Pages record
+//! their nonce with [`record_nonce`], and the header is built around it. Pages served from
+//! the page cache get a fresh nonce each time, see [`with_fresh_nonce`]; the middleware has
+//! to wrap the page cache for that.
+
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{
+    HeaderName, HeaderValue, CONTENT_SECURITY_POLICY, PERMISSIONS_POLICY, REFERRER_POLICY,
+    STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS,
+};
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{Error, HttpMessage, HttpRequest};
+use leptos::nonce::use_nonce;
+use leptos::use_context;
+use uuid::Uuid;
+
+use crate::server::images::ImagePolicy;
+
+/// Where the nonce goes in [`SecurityConfig::csp`].
+const NONCE_PLACEHOLDER: &str = "{nonce}";
+
+/// The nonce of the page rendered for a request.
+#[derive(Clone)]
+struct CspNonce(String);
+
+/// Records the nonce of the page being rendered, for the `Content-Security-Policy` header.
+pub fn record_nonce() {
+    if let (Some(nonce), Some(req)) = (use_nonce(), use_context::<HttpRequest>()) {
+        set_nonce(&req, nonce.to_string());
+    }
+}
+
+pub(crate) fn set_nonce(req: &HttpRequest, nonce: String) {
+    req.extensions_mut().insert(CspNonce(nonce));
+}
+
+/// The nonce the page for `req` was rendered with, if it was.
+pub(crate) fn nonce_of(req: &HttpRequest) -> Option<String> {
+    req.extensions()
+        .get::<CspNonce>()
+        .map(|CspNonce(nonce)| nonce.clone())
+}
+
+/// A copy of `body`, a page rendered with `nonce`, with a nonce of its own, which is then
+/// what the CSP header of the response to `req` allows. Pages served from a cache go through
+/// this, so no two responses share a nonce.
+pub(crate) fn with_fresh_nonce(req: &HttpRequest, body: &Bytes, nonce: &str) -> Bytes {
+    let Ok(html) = std::str::from_utf8(body) else {
+        return body.clone();
+    };
+    let fresh = Uuid::new_v4().simple().to_string();
+    let html = html.replace(
+        &format!("nonce=\"{}\"", nonce),
+        &format!("nonce=\"{}\"", fresh),
+    );
+    set_nonce(req, fresh);
+    Bytes::from(html)
+}
+
+/// The value of each header, or `None` to leave it out.
+#[derive(Clone)]
+pub struct SecurityConfig {
+    /// With `{nonce}` standing in for the nonce of each page.
+    pub csp: Option<String>,
+    /// Added to the CSP. Which sites may show the blog in a frame.
+    pub frame_ancestors: Option<String>,
+    pub hsts: Option<String>,
+    pub content_type_options: Option<String>,
+    pub referrer_policy: Option<String>,
+    pub permissions_policy: Option<String>,
+}
+
+impl SecurityConfig {
+    /// Reads `SECURITY_CSP`, `SECURITY_FRAME_ANCESTORS`, `SECURITY_HSTS`,
+    /// `SECURITY_CONTENT_TYPE_OPTIONS`, `SECURITY_REFERRER_POLICY` and
+    /// `SECURITY_PERMISSIONS_POLICY`. Unset ones get a strict default, and empty ones are
+    /// left out of responses. With the image proxy on, the default CSP only allows images
+    /// from this site.
+    pub fn from_env(images: &ImagePolicy) -> SecurityConfig {
+        let var = |name: &str, default: &str| match std::env::var(name) {
+            Ok(value) if value.trim().is_empty() => None,
+            Ok(value) => Some(value),
+            Err(_) => Some(default.to_string()),
+        };
+
+        // unless they are proxied, images can come from anywhere, since posts link to them by URL
+        let img_src = if images.proxy {
+            "'self'"
+        } else {
+            "'self' https: data:"
+        };
+        SecurityConfig {
+            csp: var(
+                "SECURITY_CSP",
+                &format!(
+                    "default-src 'self'; \
+                     script-src 'nonce-{{nonce}}' 'strict-dynamic' 'wasm-unsafe-eval'; \
+                     style-src 'self' 'unsafe-inline'; \
+                     img-src {}; \
+                     object-src 'none'; base-uri 'self'; form-action 'self'",
+                    img_src
+                ),
+            ),
+            frame_ancestors: var("SECURITY_FRAME_ANCESTORS", "'none'"),
+            hsts: var("SECURITY_HSTS", "max-age=31536000; includeSubDomains"),
+            content_type_options: var("SECURITY_CONTENT_TYPE_OPTIONS", "nosniff"),
+            referrer_policy: var(
+                "SECURITY_REFERRER_POLICY",
+                "strict-origin-when-cross-origin",
+            ),
+            permissions_policy: var(
+                "SECURITY_PERMISSIONS_POLICY",
+                "camera=(), microphone=(), geolocation=(), payment=(), usb=()",
+            ),
+        }
+    }
+
+    /// The CSP for a response. Without a nonce, as for anything but a rendered page,
+    /// no inline script is allowed.
+    fn csp_for(&self, nonce: Option<&str>) -> Option<String> {
+        let source = format!("'nonce-{}'", NONCE_PLACEHOLDER);
+        let mut csp = match (&self.csp, nonce) {
+            (Some(csp), Some(nonce)) => csp.replace(NONCE_PLACEHOLDER, nonce),
+            (Some(csp), None) => csp.replace(&source, "").replace(NONCE_PLACEHOLDER, ""),
+            (None, _) => {
+                return self
+                    .frame_ancestors
+                    .as_ref()
+                    .map(|ancestors| format!("frame-ancestors {}", ancestors));
+            }
+        };
+        if let Some(ancestors) = &self.frame_ancestors {
+            csp = format!(
+                "{}; frame-ancestors {}",
+                csp.trim_end_matches([' ', ';']),
+                ancestors
+            );
+        }
+        Some(csp)
+    }
+}
+
+/// Middleware adding the headers in its [`SecurityConfig`] to responses that don't set them.
+pub struct SecurityHeaders(pub SecurityConfig);
+
+impl<S, B> Transform<S, ServiceRequest> for SecurityHeaders
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = SecurityHeadersMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SecurityHeadersMiddleware {
+            service: Rc::new(service),
+            config: Rc::new(self.0.clone()),
+        }))
+    }
+}
+
+pub struct SecurityHeadersMiddleware<S> {
+    service: Rc<S>,
+    config: Rc<SecurityConfig>,
+}
+
+impl<S, B> Service<ServiceRequest> for SecurityHeadersMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let config = Rc::clone(&self.config);
+
+        Box::pin(async move {
+            let mut res = service.call(req).await?;
+
+            let nonce = nonce_of(res.request());
+            // a browser revalidating a page keeps the CSP it got with it, which has the nonce
+            // that is in its copy; a fresh one would stop the page's scripts running
+            let csp = match res.status() {
+                StatusCode::NOT_MODIFIED => None,
+                _ => config.csp_for(nonce.as_deref()),
+            };
+            let headers: [(HeaderName, Option<String>); 5] = [
+                (CONTENT_SECURITY_POLICY, csp),
+                (STRICT_TRANSPORT_SECURITY, config.hsts.clone()),
+                (X_CONTENT_TYPE_OPTIONS, config.content_type_options.clone()),
+                (REFERRER_POLICY, config.referrer_policy.clone()),
+                (PERMISSIONS_POLICY, config.permissions_policy.clone()),
+            ];
+            for (name, value) in headers {
+                let value = value.and_then(|value| HeaderValue::from_str(&value).ok());
+                if let Some(value) = value {
+                    if !res.headers().contains_key(&name) {
+                        res.headers_mut().insert(name, value);
+                    }
+                }
+            }
+
+            Ok(res.map_into_boxed_body())
+        })
+    }
+}
@@ -0,0 +1,76 @@
+//! Structured logging with `tracing`: a span for every request, tagged with a generated
+//! request id, and spans for the server functions it calls.
+
+use std::future::Future;
+
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{HttpMessage, HttpRequest};
+use leptos::{use_context, ServerFnError};
+use tracing::Instrument;
+use tracing_actix_web::RequestId;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+/// Response header echoing the request id, for quoting in bug reports.
+const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Installs the subscriber, which also picks up `log` records such as sqlx's queries.
+///
+/// `RUST_LOG` filters what is logged (default `info`), and `LOG_FORMAT=json` switches from
+/// human-readable output to one JSON object per line. Closing spans log how long they took.
+pub fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE);
+
+    match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => subscriber
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init(),
+        _ => subscriber.pretty().init(),
+    }
+}
+
+/// The id of the request being handled, from inside a server function or component.
+pub fn request_id() -> Option<String> {
+    let req = use_context::<HttpRequest>()?;
+    let extensions = req.extensions();
+    extensions.get::<RequestId>().map(|id| id.to_string())
+}
+
+/// Runs the body of a server function in a span named after it.
+///
+/// Called from the browser, the span sits under the request's own span. While a page is
+/// being rendered, server functions run as separate tasks outside it, so the span carries
+/// the request id as well.
+pub async fn traced<T>(
+    name: &'static str,
+    body: impl Future<Output = Result<T, ServerFnError>>,
+) -> Result<T, ServerFnError> {
+    let span = tracing::info_span!("server_fn", name, request_id = tracing::field::Empty);
+    if let Some(request_id) = request_id() {
+        span.record("request_id", request_id.as_str());
+    }
+
+    let result = body.instrument(span.clone()).await;
+    if let Err(e) = &result {
+        span.in_scope(|| tracing::warn!(error = %e, "server function failed"));
+    }
+    result
+}
+
+/// Echoes the request id in the `X-Request-Id` response header.
+pub fn set_request_id_header<B>(res: &mut ServiceResponse<B>) {
+    let request_id = res
+        .request()
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| HeaderValue::from_str(&id.to_string()).ok());
+    if let Some(request_id) = request_id {
+        res.headers_mut().insert(REQUEST_ID_HEADER, request_id);
+    }
+}
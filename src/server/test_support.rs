@@ -0,0 +1,21 @@
+//! Shared setup for the server's tests.
+
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{Pool, Sqlite};
+
+use super::health::MIGRATOR;
+
+/// A fresh in-memory database with every migration applied. One connection, as each
+/// connection to `sqlite::memory:` would get a database of its own.
+pub async fn test_pool() -> Pool<Sqlite> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("could not open an in-memory database");
+    MIGRATOR
+        .run(&pool)
+        .await
+        .expect("could not run the migrations");
+    pool
+}
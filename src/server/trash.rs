@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use sqlx::{Pool, Sqlite};
+
+use crate::repository::blog_repository::purge_deleted_before;
+
+/// How long deleted posts stay in the trash before they are purged for good.
+pub struct TrashConfig {
+    pub retention: chrono::Duration,
+    pub purge_interval: Duration,
+}
+
+impl TrashConfig {
+    /// Reads `TRASH_RETENTION_DAYS` (default 30) and `TRASH_PURGE_INTERVAL_SECS` (default 3600,
+    /// at least 1).
+    pub fn from_env() -> TrashConfig {
+        let retention_days = std::env::var("TRASH_RETENTION_DAYS")
+            .ok()
+            .and_then(|days| days.parse().ok())
+            .unwrap_or(30);
+        let purge_interval_secs = std::env::var("TRASH_PURGE_INTERVAL_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(3600)
+            // a timer can't fire every 0 seconds
+            .max(1);
+
+        TrashConfig {
+            retention: chrono::Duration::days(retention_days),
+            purge_interval: Duration::from_secs(purge_interval_secs),
+        }
+    }
+}
+
+/// Periodically purges posts that have been in the trash longer than the retention period.
+pub fn spawn_purge_task(pool: Pool<Sqlite>, config: TrashConfig) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(config.purge_interval);
+        loop {
+            interval.tick().await;
+            let cutoff = chrono::Local::now().naive_local() - config.retention;
+            match purge_deleted_before(&pool, cutoff).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("purged {} post(s) from the trash", purged),
+                Err(e) => tracing::error!("could not purge the trash: {}", e),
+            }
+        }
+    });
+}
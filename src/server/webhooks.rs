@@ -0,0 +1,530 @@
+//! Webhooks: a signed JSON POST to each configured URL when a post is created, updated,
+//! published or deleted.
+//!
+//! Deliveries are queued in the `webhook_delivery` table and sent by a background task,
+//! which retries failures with exponential backoff. Queuing them keeps saving a post fast,
+//! and lets deliveries survive a receiver being down or the server restarting.
+
+use std::time::Duration;
+
+use actix_web::web::Data;
+use hmac::{Hmac, Mac};
+use leptos_actix::extract;
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::types::chrono::NaiveDateTime;
+use sqlx::{FromRow, Pool, Sqlite};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::model::post_body::excerpt;
+use crate::model::webhook::{DeliveryStatus, WebhookDelivery};
+use crate::repository::blog_repository::find_post;
+
+/// Header carrying `sha256=<hex HMAC of the body>`.
+const SIGNATURE_HEADER: &str = "x-webhook-signature";
+const EVENT_HEADER: &str = "x-webhook-event";
+/// The same on every attempt, so receivers can ignore repeats.
+const DELIVERY_HEADER: &str = "x-webhook-delivery";
+
+/// How many due deliveries are sent per round.
+const BATCH_SIZE: i64 = 50;
+
+/// Length of the excerpt sent for posts without a hand-written one.
+const EXCERPT_LENGTH: usize = 280;
+
+/// The longest wait between two attempts, however large the retry delay.
+const MAX_BACKOFF: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+pub struct WebhookConfig {
+    pub urls: Vec<String>,
+    /// Key for the HMAC-SHA256 signature of each payload.
+    pub secret: String,
+    /// Prefixed to post paths in payloads, like `https://blog.example.com`.
+    pub site_url: String,
+    /// How many times a delivery is attempted before it is marked failed.
+    pub max_attempts: i64,
+    /// How long to wait before the first retry; each one after waits twice as long.
+    pub retry_delay: Duration,
+    pub poll_interval: Duration,
+}
+
+impl WebhookConfig {
+    /// Reads `WEBHOOK_URLS` (comma-separated, default none), `WEBHOOK_SECRET`, `SITE_URL`,
+    /// `WEBHOOK_MAX_ATTEMPTS` (default 8), `WEBHOOK_RETRY_DELAY_SECS` (default 30) and
+    /// `WEBHOOK_POLL_INTERVAL_SECS` (default 5). Webhooks are off without a secret.
+    pub fn from_env() -> WebhookConfig {
+        let number = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        let mut urls: Vec<String> = std::env::var("WEBHOOK_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect();
+        let secret = std::env::var("WEBHOOK_SECRET").unwrap_or_default();
+        if !urls.is_empty() && secret.is_empty() {
+            tracing::warn!(
+                "WEBHOOK_URLS is set but WEBHOOK_SECRET isn't, so no webhooks will be sent"
+            );
+            urls.clear();
+        }
+
+        WebhookConfig {
+            urls,
+            secret,
+            site_url: std::env::var("SITE_URL")
+                .unwrap_or_default()
+                .trim_end_matches('/')
+                .to_string(),
+            max_attempts: number("WEBHOOK_MAX_ATTEMPTS", 8) as i64,
+            retry_delay: Duration::from_secs(number("WEBHOOK_RETRY_DELAY_SECS", 30)),
+            poll_interval: Duration::from_secs(number("WEBHOOK_POLL_INTERVAL_SECS", 5)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PostEvent {
+    Created,
+    Updated,
+    Published,
+    Deleted,
+}
+
+impl PostEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostEvent::Created => "post.created",
+            PostEvent::Updated => "post.updated",
+            PostEvent::Published => "post.published",
+            PostEvent::Deleted => "post.deleted",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload {
+    event: &'static str,
+    occurred_at: NaiveDateTime,
+    post: PostPayload,
+}
+
+#[derive(Serialize)]
+struct PostPayload {
+    id: String,
+    title: String,
+    status: &'static str,
+    url: String,
+    excerpt: String,
+    image_url: String,
+    author_handle: Option<String>,
+    tags: Vec<String>,
+    dt: NaiveDateTime,
+}
+
+/// Queues a delivery of `event` for the post `post_id` to every webhook URL. Only works
+/// while handling a request, which is where the [`WebhookConfig`] comes from.
+///
+/// The post has been saved by the time this is called, so a failure here is logged
+/// rather than returned: the save shouldn't look like it failed because of a webhook.
+pub async fn post_event(pool: &Pool<Sqlite>, event: PostEvent, post_id: &str) {
+    let config = match extract(|config: Data<WebhookConfig>| async move { config }).await {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!(error = %e, "no webhook configuration");
+            return;
+        }
+    };
+    if config.urls.is_empty() {
+        return;
+    }
+    if let Err(e) = queue(pool, &config, event, post_id).await {
+        tracing::error!(event = event.as_str(), post_id, error = %e, "could not queue webhooks");
+    }
+}
+
+async fn queue(
+    pool: &Pool<Sqlite>,
+    config: &WebhookConfig,
+    event: PostEvent,
+    post_id: &str,
+) -> Result<(), AppError> {
+    let Some(post) = find_post(pool, post_id).await? else {
+        return Ok(());
+    };
+    let tags = sqlx::query_scalar("SELECT tag FROM post_tag WHERE post_id = ? ORDER BY tag")
+        .bind(post_id)
+        .fetch_all(pool)
+        .await?;
+
+    let now = chrono::Local::now().naive_local();
+    let payload = Payload {
+        event: event.as_str(),
+        occurred_at: now,
+        post: PostPayload {
+            url: format!("{}/view/{}", config.site_url, post.id),
+            excerpt: post
+                .excerpt
+                .clone()
+                .unwrap_or_else(|| excerpt(&post.text, EXCERPT_LENGTH)),
+            image_url: post.image_url,
+            id: post.id,
+            title: post.title,
+            status: post.status.as_str(),
+            author_handle: post.author_handle,
+            tags,
+            dt: post.dt,
+        },
+    };
+    let payload = serde_json::to_string(&payload)
+        .map_err(|e| AppError::Internal(format!("could not serialize webhook payload: {}", e)))?;
+
+    for url in &config.urls {
+        sqlx::query("INSERT INTO webhook_delivery (id, event, post_id, url, payload, created_at, next_attempt_at) VALUES ($1, $2, $3, $4, $5, $6, $6)")
+            .bind(Uuid::new_v4().to_string())
+            .bind(event.as_str())
+            .bind(post_id)
+            .bind(url)
+            .bind(&payload)
+            .bind(now)
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body` keyed with `secret`.
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+/// How long to wait after the `attempts`th failed attempt before the next one:
+/// 30s, 1m, 2m, 4m, ... with the default delay, up to [`MAX_BACKOFF`]. Newsletter emails
+/// are retried the same way.
+pub(crate) fn backoff(retry_delay: Duration, attempts: i64) -> Duration {
+    retry_delay
+        .saturating_mul(2u32.pow((attempts - 1).clamp(0, 16) as u32))
+        .min(MAX_BACKOFF)
+}
+
+/// Periodically sends the deliveries that are due.
+pub fn spawn_delivery_task(pool: Pool<Sqlite>, config: Data<WebhookConfig>) {
+    if config.urls.is_empty() {
+        return;
+    }
+    actix_web::rt::spawn(async move {
+        let client = awc::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .finish();
+        let mut interval = actix_web::rt::time::interval(config.poll_interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = deliver_due(&pool, &client, &config).await {
+                tracing::error!(error = %e, "could not send webhooks");
+            }
+        }
+    });
+}
+
+/// A delivery that is due, along with the body to send.
+#[derive(FromRow)]
+struct DueDelivery {
+    #[sqlx(flatten)]
+    delivery: WebhookDelivery,
+    payload: String,
+}
+
+async fn deliver_due(
+    pool: &Pool<Sqlite>,
+    client: &awc::Client,
+    config: &WebhookConfig,
+) -> Result<(), sqlx::Error> {
+    let now = chrono::Local::now().naive_local();
+    let due: Vec<DueDelivery> = sqlx::query_as(
+        "SELECT * FROM webhook_delivery WHERE status = ? AND next_attempt_at <= ? ORDER BY created_at LIMIT ?",
+    )
+    .bind(DeliveryStatus::Pending)
+    .bind(now)
+    .bind(BATCH_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    for DueDelivery { delivery, payload } in due {
+        let result = client
+            .post(delivery.url.as_str())
+            .content_type("application/json")
+            .insert_header((SIGNATURE_HEADER, sign(&config.secret, &payload)))
+            .insert_header((EVENT_HEADER, delivery.event.as_str()))
+            .insert_header((DELIVERY_HEADER, delivery.id.as_str()))
+            .send_body(payload)
+            .await;
+
+        let attempts = delivery.attempts + 1;
+        let (response_status, error) = match result {
+            Ok(res) if res.status().is_success() => (Some(res.status().as_u16()), None),
+            Ok(res) => (
+                Some(res.status().as_u16()),
+                Some(format!("responded {}", res.status())),
+            ),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        let now = chrono::Local::now().naive_local();
+
+        match error {
+            None => {
+                tracing::info!(
+                    delivery = %delivery.id,
+                    url = %delivery.url,
+                    event = %delivery.event,
+                    "delivered webhook"
+                );
+                sqlx::query("UPDATE webhook_delivery SET status = ?, attempts = ?, last_response_status = ?, last_error = NULL, delivered_at = ? WHERE id = ?")
+                    .bind(DeliveryStatus::Delivered)
+                    .bind(attempts)
+                    .bind(response_status)
+                    .bind(now)
+                    .bind(&delivery.id)
+                    .execute(pool)
+                    .await?;
+            }
+            Some(error) => {
+                let status = if attempts >= config.max_attempts {
+                    DeliveryStatus::Failed
+                } else {
+                    DeliveryStatus::Pending
+                };
+                let next_attempt_at = now
+                    + chrono::Duration::from_std(backoff(config.retry_delay, attempts))
+                        .unwrap_or(chrono::Duration::zero());
+                tracing::warn!(
+                    delivery = %delivery.id,
+                    url = %delivery.url,
+                    attempts,
+                    error = %error,
+                    "webhook delivery failed"
+                );
+                sqlx::query("UPDATE webhook_delivery SET status = ?, attempts = ?, last_response_status = ?, last_error = ?, next_attempt_at = ? WHERE id = ?")
+                    .bind(status)
+                    .bind(attempts)
+                    .bind(response_status)
+                    .bind(error)
+                    .bind(next_attempt_at)
+                    .bind(&delivery.id)
+                    .execute(pool)
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use actix_web::http::StatusCode;
+    use actix_web::{web, App, HttpRequest, HttpResponse};
+
+    use super::*;
+    use crate::server::test_support::test_pool;
+
+    const SECRET: &str = "s3cret";
+
+    /// What the stub receiver got: the signature header, the delivery id header and the body.
+    #[derive(Clone, Default)]
+    struct Received(Arc<Mutex<Vec<(String, String, String)>>>);
+
+    /// A local HTTP server standing in for a webhook receiver, answering every call with `status`.
+    fn receiver(status: u16, received: Received) -> actix_test::TestServer {
+        actix_test::start(move || {
+            let received = received.clone();
+            App::new().default_service(web::to(move |req: HttpRequest, body: String| {
+                let received = received.clone();
+                async move {
+                    let header = |name: &str| {
+                        req.headers()
+                            .get(name)
+                            .and_then(|value| value.to_str().ok())
+                            .unwrap_or_default()
+                            .to_string()
+                    };
+                    received.0.lock().unwrap().push((
+                        header(SIGNATURE_HEADER),
+                        header(DELIVERY_HEADER),
+                        body,
+                    ));
+                    HttpResponse::build(StatusCode::from_u16(status).unwrap()).finish()
+                }
+            }))
+        })
+    }
+
+    fn config(url: String, max_attempts: i64) -> WebhookConfig {
+        WebhookConfig {
+            urls: vec![url],
+            secret: SECRET.to_string(),
+            site_url: "https://blog.example.com".to_string(),
+            max_attempts,
+            retry_delay: Duration::from_secs(30),
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+
+    async fn insert_delivery(pool: &Pool<Sqlite>, url: &str, payload: &str) -> String {
+        let id = Uuid::new_v4().to_string();
+        let due = chrono::Local::now().naive_local() - chrono::Duration::seconds(1);
+        sqlx::query("INSERT INTO webhook_delivery (id, event, post_id, url, payload, created_at, next_attempt_at) VALUES ($1, 'post.published', 'post-1', $2, $3, $4, $4)")
+            .bind(&id)
+            .bind(url)
+            .bind(payload)
+            .bind(due)
+            .execute(pool)
+            .await
+            .unwrap();
+        id
+    }
+
+    async fn delivery(pool: &Pool<Sqlite>, id: &str) -> WebhookDelivery {
+        sqlx::query_as("SELECT * FROM webhook_delivery WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    /// Makes the delivery due right away, rather than waiting out its backoff.
+    async fn make_due(pool: &Pool<Sqlite>, id: &str) {
+        sqlx::query("UPDATE webhook_delivery SET next_attempt_at = ? WHERE id = ?")
+            .bind(chrono::Local::now().naive_local() - chrono::Duration::seconds(1))
+            .bind(id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        assert_eq!(
+            sign("key", "The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_from_the_retry_delay() {
+        let delay = Duration::from_secs(30);
+        assert_eq!(backoff(delay, 1), Duration::from_secs(30));
+        assert_eq!(backoff(delay, 2), Duration::from_secs(60));
+        assert_eq!(backoff(delay, 3), Duration::from_secs(120));
+        assert_eq!(backoff(delay, 4), Duration::from_secs(240));
+        // capped, rather than overflowing
+        assert_eq!(backoff(delay, 100), backoff(delay, 17));
+        assert_eq!(backoff(Duration::from_secs(u64::MAX), 3), MAX_BACKOFF);
+    }
+
+    #[actix_web::test]
+    async fn delivers_a_signed_payload() {
+        let pool = test_pool().await;
+        let received = Received::default();
+        let server = receiver(200, received.clone());
+        let config = config(server.url("/hook"), 3);
+        let payload = r#"{"event":"post.published"}"#;
+        let id = insert_delivery(&pool, &server.url("/hook"), payload).await;
+
+        deliver_due(&pool, &awc::Client::default(), &config)
+            .await
+            .unwrap();
+
+        let received = received.0.lock().unwrap().clone();
+        assert_eq!(received.len(), 1);
+        let (signature, delivery_id, body) = &received[0];
+        assert_eq!(body, payload);
+        assert_eq!(delivery_id, &id);
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        assert_eq!(
+            signature,
+            &format!("sha256={:x}", mac.finalize().into_bytes())
+        );
+
+        let delivery = delivery(&pool, &id).await;
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.last_response_status, Some(200));
+        assert_eq!(delivery.last_error, None);
+        assert!(delivery.delivered_at.is_some());
+    }
+
+    #[actix_web::test]
+    async fn retries_with_backoff_and_then_gives_up() {
+        let pool = test_pool().await;
+        let received = Received::default();
+        let server = receiver(500, received.clone());
+        let config = config(server.url("/hook"), 2);
+        let id = insert_delivery(&pool, &server.url("/hook"), "{}").await;
+        let client = awc::Client::default();
+
+        let before = chrono::Local::now().naive_local();
+        deliver_due(&pool, &client, &config).await.unwrap();
+        let after = chrono::Local::now().naive_local();
+
+        let failed_once = delivery(&pool, &id).await;
+        assert_eq!(failed_once.status, DeliveryStatus::Pending);
+        assert_eq!(failed_once.attempts, 1);
+        assert_eq!(failed_once.last_response_status, Some(500));
+        assert!(failed_once.last_error.is_some());
+        // due again after the first retry delay
+        assert!(failed_once.next_attempt_at >= before + chrono::Duration::seconds(30));
+        assert!(failed_once.next_attempt_at <= after + chrono::Duration::seconds(30));
+
+        // not due yet, so nothing is sent
+        deliver_due(&pool, &client, &config).await.unwrap();
+        assert_eq!(received.0.lock().unwrap().len(), 1);
+
+        make_due(&pool, &id).await;
+        deliver_due(&pool, &client, &config).await.unwrap();
+        assert_eq!(received.0.lock().unwrap().len(), 2);
+
+        let given_up = delivery(&pool, &id).await;
+        assert_eq!(given_up.status, DeliveryStatus::Failed);
+        assert_eq!(given_up.attempts, 2);
+        assert_eq!(given_up.delivered_at, None);
+
+        // failed deliveries stay in the log, but aren't sent again
+        make_due(&pool, &id).await;
+        deliver_due(&pool, &client, &config).await.unwrap();
+        assert_eq!(received.0.lock().unwrap().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn records_unreachable_receivers() {
+        let pool = test_pool().await;
+        // nothing listens on port 9 of localhost
+        let config = config("http://127.0.0.1:9/hook".to_string(), 3);
+        let id = insert_delivery(&pool, "http://127.0.0.1:9/hook", "{}").await;
+
+        deliver_due(&pool, &awc::Client::default(), &config)
+            .await
+            .unwrap();
+
+        let delivery = delivery(&pool, &id).await;
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.last_response_status, None);
+        assert!(delivery.last_error.is_some());
+    }
+}
@@ -4,6 +4,11 @@
         relative: true,
         files: ["*.html", "./src/**/*.rs"],
       },
+      // `dark`/`light` on <html> force a theme, otherwise follow the system preference
+      darkMode: ['variant', [
+        '@media (prefers-color-scheme: dark) { &:not(.light *) }',
+        '&:is(.dark *)',
+      ]],
       theme: {
         extend: {},
       },